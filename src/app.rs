@@ -1,15 +1,52 @@
-use crate::clicker::Clicker;
+use crate::backend::{try_create_enigo, InputBackend};
+use crate::bounds::{BoundsBehavior, MoveBounds};
+use crate::chain::{
+    Chain, ChainAction, ChainContext, ChainLibrary, ChainRunner, ChainStage, StopAfter,
+};
+use crate::clicker::{Clicker, ClickerConfig, CPS_HARD_LIMIT};
+use crate::coords::{CoordSpace, Layout, ScreenPoint};
+use crate::delay::DelayDistribution;
+use crate::drag::Dragger;
+use crate::hotkeys;
+use crate::idle::YieldSettings;
+use crate::macro_editor::{button_combo, MacroEditor};
+use crate::macros::{Key, Macro, MacroLibrary, PlaybackSettings, MAX_SPEED, MIN_SPEED};
+use crate::monitors::{self, Monitor};
+use crate::motion::{CursorRestore, GlideSettings, MotionStyle};
 use crate::mouse_button::SerializableMouseButton;
-use crate::mouse_mover::MouseMover;
+use crate::mouse_mover::{MouseMover, MouseMoverConfig};
+use crate::overlay::{paint_path, show_path_overlay};
+use crate::pattern::{
+    JiggleSettings, MovementPattern, PatternCenter, PatternSettings, WanderSettings,
+};
+use crate::player::MacroPlayer;
+use crate::recorder::Recorder;
+use crate::rules::{
+    Automation, Profile, Rule, RuleAction, RuleEvent, RuleLibrary, RuleMonitor, RuleState, Trigger,
+};
+use crate::scheduler::JitterStats;
+use crate::script::{Script, ScriptLibrary, ScriptRunner, ScriptSettings};
+use crate::steps::{Program, Step};
+use crate::typing::{TextTyper, TypingStyle};
+use crate::waypoints::{parse_csv, PathMode, Waypoint};
+use crate::windows::{self, WindowTarget};
+use crate::xdotool;
 use device_query::{DeviceQuery, DeviceState, Keycode};
 use eframe::egui;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::Duration;
 use std::time::Instant;
 
+/// Which automation a window picked by clicking becomes the target of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WindowPick {
+    Clicker,
+    Mover,
+}
+
 #[derive(Debug)]
 pub struct MourseApp {
     clicker: Clicker,
@@ -17,6 +54,38 @@ pub struct MourseApp {
     device_state: DeviceState,
     last_key_press: Instant,
     config_path: PathBuf,
+    waypoint_csv_path: String,
+    waypoint_status: Option<String>,
+    show_path_overlay: bool,
+    monitors: Vec<Monitor>,
+    /// Set while waiting for the user to click a window. The flag records
+    /// that the click on the Pick button itself has been released.
+    picking_window: Option<(WindowPick, bool)>,
+    window_status: Option<String>,
+    recorder: Recorder,
+    player: MacroPlayer,
+    macros: MacroLibrary,
+    macro_name: String,
+    macro_status: Option<String>,
+    editor: MacroEditor,
+    xdotool_path: String,
+    scripts: ScriptLibrary,
+    script_runner: ScriptRunner,
+    typer: TextTyper,
+    typing_status: Option<String>,
+    dragger: Dragger,
+    drag_status: Option<String>,
+    chains: ChainLibrary,
+    chain_runner: ChainRunner,
+    chain_status: Option<String>,
+    rules: RuleLibrary,
+    rule_monitor: RuleMonitor,
+    rule_status: Option<String>,
+    profile_name: String,
+    /// Index of the Key held rule waiting for a key press.
+    rule_key_capture: Option<usize>,
+    drag_key_capture: bool,
+    chain_key_capture: bool,
 }
 
 impl MourseApp {
@@ -40,12 +109,15 @@ impl MourseApp {
         }
     }
 
+    pub fn read_config() -> Option<(ClickerConfig, MouseMoverConfig)> {
+        let config_str = fs::read_to_string(Self::get_config_path()).ok()?;
+        ron::from_str(&config_str).ok()
+    }
+
     fn load_config(&mut self) {
-        if let Ok(config_str) = fs::read_to_string(&self.config_path) {
-            if let Ok((clicker_config, mover_config)) = ron::from_str(&config_str) {
-                self.clicker.set_config(clicker_config);
-                self.mouse_mover.set_config(mover_config);
-            }
+        if let Some((clicker_config, mover_config)) = Self::read_config() {
+            self.clicker.set_config(clicker_config);
+            self.mouse_mover.set_config(mover_config);
         }
     }
 
@@ -68,6 +140,980 @@ impl MourseApp {
         }
     }
 
+    /// Geometry for previews. The target window is only tracked while an
+    /// automation runs, so window-relative points are left out.
+    fn layout(&self) -> Layout {
+        Layout {
+            monitors: self.monitors.clone(),
+            window: None,
+        }
+    }
+
+    fn window_target(&mut self, pick: WindowPick) -> &mut WindowTarget {
+        match pick {
+            WindowPick::Clicker => &mut self.clicker.config.window,
+            WindowPick::Mover => &mut self.mouse_mover.config.window,
+        }
+    }
+
+    fn poll_window_pick(&mut self, ctx: &egui::Context) {
+        let Some((pick, released)) = self.picking_window else {
+            return;
+        };
+        let mouse = self.device_state.get_mouse();
+        let pressed = mouse.button_pressed.get(1).copied().unwrap_or(false);
+        if !released {
+            if !pressed {
+                self.picking_window = Some((pick, true));
+            }
+        } else if pressed {
+            self.picking_window = None;
+            self.window_status = Some(match windows::window_at(mouse.coords) {
+                Some(info) => {
+                    let message = format!("Picked \"{}\" ({})", info.title, info.class);
+                    *self.window_target(pick) = if info.class.is_empty() {
+                        WindowTarget::Title(info.title)
+                    } else {
+                        WindowTarget::Class(info.class)
+                    };
+                    self.save_config();
+                    message
+                }
+                None => "No window found under the cursor".to_string(),
+            });
+        }
+        ctx.request_repaint();
+    }
+
+    fn window_target_ui(&mut self, ui: &mut egui::Ui, pick: WindowPick) {
+        let id = match pick {
+            WindowPick::Clicker => "clicker_window",
+            WindowPick::Mover => "mover_window",
+        };
+        ui.horizontal(|ui| {
+            if window_target_combo(ui, id, self.window_target(pick)) {
+                self.save_config();
+            }
+            let picking = self.picking_window.is_some_and(|(p, _)| p == pick);
+            let label = if picking { "Click a window..." } else { "Pick" };
+            if ui.button(label).clicked() && !picking {
+                self.picking_window = Some((pick, false));
+                self.window_status = None;
+            }
+        });
+        if let Some(status) = &self.window_status {
+            ui.small(status);
+        }
+    }
+
+    fn finish_recording(&mut self) {
+        let name = match self.macro_name.trim() {
+            "" => format!("Macro {}", self.macros.macros.len() + 1),
+            name => name.to_string(),
+        };
+        let recording = self.recorder.finish_recording(&name);
+        self.macro_status = Some(format!(
+            "Saved \"{}\" ({} steps)",
+            name,
+            recording.steps.len()
+        ));
+        self.macros.insert(recording);
+        self.macros.save();
+        self.macro_name = format!("Macro {}", self.macros.macros.len() + 1);
+    }
+
+    fn macros_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.add(egui::TextEdit::singleline(&mut self.macro_name).desired_width(120.0));
+        });
+        ui.horizontal(|ui| {
+            let settings = &mut self.macros.recorder;
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut settings.poll_hz)
+                        .range(10..=1000)
+                        .prefix("Poll: ")
+                        .suffix(" Hz"),
+                )
+                .changed();
+            changed |= ui
+                .checkbox(&mut settings.record_moves, "Record Moves")
+                .changed();
+        });
+
+        if self.recorder.is_recording() {
+            ui.label(format!(
+                "Recording... {} steps",
+                self.recorder.get_step_count()
+            ));
+            if ui.button("Stop Recording (F8)").clicked() {
+                self.finish_recording();
+            }
+        } else if ui.button("Start Recording (F8)").clicked() {
+            self.recorder
+                .start_recording(self.macros.recorder, self.hotkeys());
+        }
+        if let Some(status) = &self.macro_status {
+            ui.small(status);
+        }
+
+        let mut remove = None;
+        egui::Grid::new("macros").striped(true).show(ui, |ui| {
+            for (i, recorded) in self.macros.macros.iter().enumerate() {
+                let selected = self.macros.playback.selected == recorded.name;
+                if ui.selectable_label(selected, &recorded.name).clicked() && !selected {
+                    self.macros.playback.selected = recorded.name.clone();
+                    changed = true;
+                }
+                ui.label(format!("{} steps", recorded.steps.len()));
+                match Program::compile(&recorded.steps) {
+                    Ok(_) => ui.label("✔"),
+                    Err(e) => ui
+                        .colored_label(egui::Color32::YELLOW, "⚠")
+                        .on_hover_text(e.to_string()),
+                };
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.macros.macros.remove(i);
+            changed = true;
+        }
+
+        let layout = self.layout();
+        let hotkeys = self.hotkeys();
+        let mut keys = self.device_state.get_keys();
+        keys.retain(|key| !hotkeys.contains(key));
+        let can_test = !self.player.is_playing();
+        let selected = &self.macros.playback.selected;
+        let mut test = None;
+        if let Some(recording) = self
+            .macros
+            .macros
+            .iter_mut()
+            .find(|recording| recording.name == *selected)
+        {
+            egui::CollapsingHeader::new(format!("Edit \"{}\"", recording.name))
+                .id_salt("macro_editor")
+                .show(ui, |ui| {
+                    let response = self.editor.ui(
+                        ui,
+                        &recording.name,
+                        &mut recording.steps,
+                        &layout,
+                        &keys,
+                        can_test,
+                    );
+                    changed |= response.changed;
+                    test = response.test;
+                });
+        }
+        if let Some(step) = test {
+            self.test_step(step);
+        }
+        changed |= self.xdotool_ui(ui);
+
+        ui.separator();
+        let last_seed = self.player.get_last_seed();
+        changed |= playback_ui(ui, &mut self.macros.playback, last_seed);
+
+        ui.horizontal(|ui| {
+            ui.label("Events played:");
+            ui.label(format!("{}", self.player.get_event_count()));
+            if ui.small_button("Reset").clicked() {
+                self.player.reset_event_count();
+            }
+        });
+        if self.player.is_playing() {
+            let progress = self.player.get_progress();
+            let loops = match progress.loops {
+                Some(loops) => loops.to_string(),
+                None => "∞".to_string(),
+            };
+            ui.label(format!(
+                "Step {}, loop {}/{}",
+                progress.step,
+                progress.loop_index + 1,
+                loops
+            ));
+            jitter_label(ui, &self.player.get_jitter_stats());
+            if ui.button("Stop Playback (F9)").clicked() {
+                self.player.stop_playing();
+            }
+        } else if ui.button("Play Selected (F9)").clicked() {
+            self.start_playback();
+        }
+
+        if changed {
+            self.macros.save();
+        }
+    }
+
+    fn start_playback(&mut self) {
+        let settings = self.macros.playback.clone();
+        let Some(recording) = self.macros.get(&settings.selected) else {
+            self.macro_status = Some("Select a macro to play".to_string());
+            return;
+        };
+        if let Err(e) = self.player.start_playing(recording, settings) {
+            self.macro_status = Some(format!("Cannot play: {}", e));
+        }
+    }
+
+    fn xdotool_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.xdotool_path)
+                    .hint_text("path/to/script.sh")
+                    .desired_width(140.0),
+            );
+            if ui.button("Import xdotool").clicked() {
+                let path = Path::new(&self.xdotool_path);
+                let result = fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|script| xdotool::import(&script));
+                self.macro_status = Some(match result {
+                    Ok(steps) => {
+                        let name = path
+                            .file_stem()
+                            .map(|stem| stem.to_string_lossy().into_owned())
+                            .unwrap_or_else(|| self.xdotool_path.clone());
+                        let message = format!("Imported {} steps as \"{}\"", steps.len(), name);
+                        self.macros.insert(Macro {
+                            name: name.clone(),
+                            steps,
+                        });
+                        self.macros.playback.selected = name;
+                        changed = true;
+                        message
+                    }
+                    Err(e) => format!("Import failed: {}", e),
+                });
+            }
+            if ui.button("Export xdotool").clicked() {
+                let selected = &self.macros.playback.selected;
+                let result = match self.macros.get(selected) {
+                    Some(recording) => xdotool::export(selected, &recording.steps, &self.layout())
+                        .map_err(|e| e.to_string())
+                        .and_then(|script| {
+                            fs::write(&self.xdotool_path, script).map_err(|e| e.to_string())
+                        }),
+                    None => Err("select a macro first".to_string()),
+                };
+                self.macro_status = Some(match result {
+                    Ok(()) => format!("Exported \"{}\" to {}", selected, self.xdotool_path),
+                    Err(e) => format!("Export failed: {}", e),
+                });
+            }
+        });
+        changed
+    }
+
+    /// Plays a single step once at normal speed, e.g. from the editor.
+    fn test_step(&mut self, step: Step) {
+        let test = Macro {
+            name: format!("Test {}", step.name()),
+            steps: vec![step],
+        };
+        let settings = PlaybackSettings {
+            speed: 1.0,
+            loops: Some(1),
+            jitter_ms: 0,
+            ..self.macros.playback.clone()
+        };
+        if let Err(e) = self.player.start_playing(&test, settings) {
+            self.macro_status = Some(format!("Cannot test: {}", e));
+        }
+    }
+
+    fn scripts_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let mut remove = None;
+        egui::Grid::new("scripts").striped(true).show(ui, |ui| {
+            for (i, script) in self.scripts.scripts.iter().enumerate() {
+                let selected = self.scripts.settings.selected == script.name;
+                if ui.selectable_label(selected, &script.name).clicked() && !selected {
+                    self.scripts.settings.selected = script.name.clone();
+                    changed = true;
+                }
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.scripts.scripts.remove(i);
+            changed = true;
+        }
+        if ui.button("New Script").clicked() {
+            let name = format!("Script {}", self.scripts.scripts.len() + 1);
+            self.scripts.scripts.push(Script {
+                name: name.clone(),
+                source: "// click(\"left\"), move_to(x, y), key(\"Enter\"), sleep(ms),\n\
+                         // cursor() -> [x, y], rand(low, high), print(text)\n"
+                    .to_string(),
+            });
+            self.scripts.settings.selected = name;
+            changed = true;
+        }
+
+        let settings = &mut self.scripts.settings;
+        if let Some(script) = self
+            .scripts
+            .scripts
+            .iter_mut()
+            .find(|script| script.name == settings.selected)
+        {
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                if ui
+                    .add(egui::TextEdit::singleline(&mut script.name).desired_width(120.0))
+                    .changed()
+                {
+                    settings.selected = script.name.clone();
+                    changed = true;
+                }
+            });
+            changed |= ui
+                .add(
+                    egui::TextEdit::multiline(&mut script.source)
+                        .code_editor()
+                        .desired_rows(8)
+                        .desired_width(f32::INFINITY),
+                )
+                .changed();
+        }
+
+        let last_seed = self.script_runner.get_last_seed();
+        changed |= script_settings_ui(ui, &mut self.scripts.settings, last_seed);
+
+        if self.script_runner.is_running() {
+            if ui.button("Stop Script (F10)").clicked() {
+                self.script_runner.stop_running();
+            }
+        } else if ui.button("Run Selected (F10)").clicked() {
+            self.start_script();
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Output:");
+            if ui.small_button("Clear").clicked() {
+                self.script_runner.clear_output();
+            }
+        });
+        egui::ScrollArea::vertical()
+            .id_salt("script_output")
+            .max_height(120.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in self.script_runner.get_output() {
+                    ui.monospace(line);
+                }
+            });
+
+        if changed {
+            self.scripts.save();
+        }
+    }
+
+    fn start_script(&mut self) {
+        let settings = self.scripts.settings.clone();
+        match self.scripts.get(&settings.selected) {
+            Some(script) => {
+                let (name, source) = (script.name.clone(), script.source.clone());
+                self.script_runner.start_running(name, source, settings);
+            }
+            None => self.script_runner.log("Select a script to run".to_string()),
+        }
+    }
+
+    fn type_text_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let settings = &mut self.macros.typing;
+        changed |= ui
+            .add(
+                egui::TextEdit::multiline(&mut settings.text)
+                    .hint_text("Text to type, with {ENTER}, {TAB} etc. for keys")
+                    .desired_rows(3)
+                    .desired_width(f32::INFINITY),
+            )
+            .changed();
+        ui.small("{ENTER} {TAB} {ESC} {BACKSPACE} {DELETE} {UP} {F1}…, {{ and }} for braces");
+        changed |= typing_style_ui(ui, &mut settings.style);
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut settings.start_delay_ms)
+                        .speed(10.0)
+                        .prefix("Start delay: ")
+                        .suffix(" ms"),
+                )
+                .on_hover_text("Time to click into the field to type into")
+                .changed();
+        });
+        changed |= seed_ui(ui, &mut settings.seed, self.typer.get_last_seed());
+
+        if self.typer.is_typing() {
+            ui.label(format!("{} keystrokes left", self.typer.get_remaining()));
+            jitter_label(ui, &self.typer.get_jitter_stats());
+            if ui.button("Stop Typing").clicked() {
+                self.typer.stop_typing();
+            }
+        } else if ui.button("Start Typing").clicked() {
+            self.typing_status = self
+                .typer
+                .start_typing(self.macros.typing.clone())
+                .err()
+                .map(|e| format!("Cannot type: {}", e));
+        }
+        if let Some(status) = &self.typing_status {
+            ui.colored_label(egui::Color32::YELLOW, status);
+        }
+
+        if changed {
+            self.macros.save();
+        }
+    }
+
+    fn drag_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let layout = self.layout();
+        let key = self.unbound_key();
+        let settings = &mut self.macros.drag;
+        ui.horizontal(|ui| {
+            ui.label("Button:");
+            changed |= button_combo(ui, "drag_button", &mut settings.button);
+        });
+        ui.horizontal(|ui| {
+            ui.label("From:");
+            changed |= screen_point_ui(ui, "drag_from", &mut settings.from, &layout);
+        });
+        ui.horizontal(|ui| {
+            ui.label("To:");
+            changed |= screen_point_ui(ui, "drag_to", &mut settings.to, &layout);
+        });
+        changed |= glide_ui(ui, "drag_glide", &mut settings.glide);
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut settings.hover_ms)
+                        .speed(10.0)
+                        .prefix("Hover before release: ")
+                        .suffix(" ms"),
+                )
+                .changed();
+        });
+        changed |= seed_ui(ui, &mut settings.seed, self.dragger.get_last_seed());
+        if hotkey_ui(ui, &mut settings.hotkey, &mut self.drag_key_capture, key) {
+            self.last_key_press = Instant::now();
+            changed = true;
+        }
+
+        let hotkey = hotkey_label(self.macros.drag.hotkey);
+        if self.dragger.is_dragging() {
+            jitter_label(ui, &self.dragger.get_jitter_stats());
+            if ui.button(format!("Stop Drag{}", hotkey)).clicked() {
+                self.dragger.stop_dragging();
+            }
+        } else if ui.button(format!("Start Drag{}", hotkey)).clicked() {
+            self.start_drag();
+        }
+        if let Some(status) = &self.drag_status {
+            ui.colored_label(egui::Color32::YELLOW, status);
+        }
+
+        if changed {
+            self.macros.save();
+        }
+    }
+
+    fn start_drag(&mut self) {
+        let layout = self.layout();
+        self.drag_status = self
+            .dragger
+            .start_dragging(self.macros.drag.clone(), &layout)
+            .err()
+            .map(|e| format!("Cannot drag: {}", e));
+    }
+
+    fn chains_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        let mut remove = None;
+        egui::Grid::new("chains").striped(true).show(ui, |ui| {
+            for (i, chain) in self.chains.chains.iter().enumerate() {
+                let selected = self.chains.settings.selected == chain.name;
+                if ui.selectable_label(selected, &chain.name).clicked() && !selected {
+                    self.chains.settings.selected = chain.name.clone();
+                    changed = true;
+                }
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.chains.chains.remove(i);
+            changed = true;
+        }
+        if ui.button("New Chain").clicked() {
+            let name = format!("Chain {}", self.chains.chains.len() + 1);
+            self.chains.chains.push(Chain {
+                name: name.clone(),
+                stages: vec![ChainStage {
+                    action: ChainAction::Clicker,
+                    stop: StopAfter {
+                        count: Some(100),
+                        secs: None,
+                    },
+                }],
+                rounds: Some(1),
+            });
+            self.chains.settings.selected = name;
+            changed = true;
+        }
+
+        let progress = self.chain_runner.get_progress();
+        let settings = &mut self.chains.settings;
+        let macro_names: Vec<String> = self.macros.macros.iter().map(|m| m.name.clone()).collect();
+        if let Some(chain) = self
+            .chains
+            .chains
+            .iter_mut()
+            .find(|chain| chain.name == settings.selected)
+        {
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                if ui
+                    .add(egui::TextEdit::singleline(&mut chain.name).desired_width(120.0))
+                    .changed()
+                {
+                    settings.selected = chain.name.clone();
+                    changed = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                let mut forever = chain.rounds.is_none();
+                if ui.checkbox(&mut forever, "Repeat Forever").changed() {
+                    chain.rounds = if forever { None } else { Some(1) };
+                    changed = true;
+                }
+                if let Some(rounds) = &mut chain.rounds {
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(rounds)
+                                .range(1..=u32::MAX)
+                                .prefix("Rounds: "),
+                        )
+                        .changed();
+                }
+            });
+            let current = progress.as_ref().map(|progress| progress.stage);
+            changed |= chain_stages_ui(ui, &mut chain.stages, &macro_names, current);
+        }
+
+        changed |= seed_ui(
+            ui,
+            &mut self.chains.settings.seed,
+            self.chain_runner.get_last_seed(),
+        );
+
+        if let Some(progress) = progress {
+            let rounds = match progress.rounds {
+                Some(rounds) => rounds.to_string(),
+                None => "∞".to_string(),
+            };
+            ui.label(format!(
+                "Stage {}/{}: {}, round {}/{}",
+                progress.stage + 1,
+                progress.stages,
+                progress.action,
+                progress.round + 1,
+                rounds
+            ));
+            let mut done = Vec::new();
+            if let Some(count) = progress.stop.count {
+                done.push(format!("{}/{} {}", progress.count, count, progress.unit));
+            }
+            if let Some(secs) = progress.stop.secs {
+                done.push(format!("{:.0}/{} s", progress.elapsed.as_secs_f64(), secs));
+            }
+            ui.small(done.join(", "));
+            jitter_label(ui, &self.chain_runner.get_jitter_stats());
+        }
+        let key = self.unbound_key();
+        if hotkey_ui(
+            ui,
+            &mut self.chains.settings.hotkey,
+            &mut self.chain_key_capture,
+            key,
+        ) {
+            self.last_key_press = Instant::now();
+            changed = true;
+        }
+        let hotkey = hotkey_label(self.chains.settings.hotkey);
+        if self.chain_runner.is_running() {
+            if ui.button(format!("Stop Chain{}", hotkey)).clicked() {
+                self.chain_runner.stop_running();
+            }
+        } else if ui.button(format!("Run Selected{}", hotkey)).clicked() {
+            self.start_chain();
+        }
+        if let Some(status) = &self.chain_status {
+            ui.colored_label(egui::Color32::YELLOW, status);
+        }
+
+        if changed {
+            self.chains.save();
+        }
+    }
+
+    fn start_chain(&mut self) {
+        let Some(chain) = self.chains.get(&self.chains.settings.selected) else {
+            self.chain_status = Some("Select a chain to run".to_string());
+            return;
+        };
+        let context = ChainContext {
+            clicker: self.clicker.config.clone(),
+            mover: self.mouse_mover.config.clone(),
+            macros: self.macros.clone(),
+        };
+        self.chain_status = self
+            .chain_runner
+            .start_running(chain.clone(), context, self.chains.settings.seed)
+            .err()
+            .map(|e| format!("Cannot run: {}", e));
+    }
+
+    fn rules_ui(&mut self, ui: &mut egui::Ui) {
+        let mut watching = self.rule_monitor.is_monitoring();
+        if ui
+            .checkbox(&mut watching, "Watch Rules")
+            .on_hover_text("Rules only fire while this is on")
+            .changed()
+        {
+            if watching {
+                self.rule_monitor.start_monitoring(self.rules.rules.clone());
+            } else {
+                self.rule_monitor.stop_monitoring();
+            }
+            self.rules.watching = watching;
+            self.rules.save();
+        }
+
+        let mut changed = false;
+        let mut remove = None;
+        let states = self.rule_monitor.get_states();
+        let key = self.unbound_key();
+        let profile_names: Vec<String> = self
+            .rules
+            .profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect();
+        for (i, rule) in self.rules.rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui.checkbox(&mut rule.enabled, "").changed();
+                changed |= ui
+                    .add(egui::TextEdit::singleline(&mut rule.name).desired_width(110.0))
+                    .changed();
+                rule_state_label(ui, states.get(i).copied());
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(i);
+                }
+            });
+            ui.indent(("rule", i), |ui| {
+                changed |= rule_ui(ui, i, rule, &profile_names, &mut self.rule_key_capture, key);
+            });
+        }
+        if let Some(i) = remove {
+            self.rules.rules.remove(i);
+            self.rule_key_capture = None;
+            changed = true;
+        }
+        if ui.button("New Rule").clicked() {
+            self.rules.rules.push(Rule {
+                name: format!("Rule {}", self.rules.rules.len() + 1),
+                enabled: true,
+                trigger: Trigger::Idle { secs: 180 },
+                action: RuleAction::Start(Automation::Mover),
+                revert: true,
+            });
+            changed = true;
+        }
+
+        ui.separator();
+        ui.label("Profiles:");
+        let mut load = None;
+        let mut remove = None;
+        egui::Grid::new("profiles").striped(true).show(ui, |ui| {
+            for (i, profile) in self.rules.profiles.iter().enumerate() {
+                ui.label(&profile.name);
+                if ui.small_button("Load").clicked() {
+                    load = Some(profile.name.clone());
+                }
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(name) = load {
+            self.load_profile(&name);
+        }
+        if let Some(i) = remove {
+            self.rules.profiles.remove(i);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.profile_name).desired_width(120.0));
+            if ui
+                .button("Save Current")
+                .on_hover_text("Saves the clicker and mover settings, and which are running")
+                .clicked()
+            {
+                self.rules.insert_profile(Profile {
+                    name: self.profile_name.trim().to_string(),
+                    clicker: self.clicker.get_config(),
+                    mover: self.mouse_mover.get_config(),
+                    clicking: self.clicker.is_clicking(),
+                    moving: self.mouse_mover.is_moving(),
+                });
+                changed = true;
+            }
+        });
+
+        if let Some(status) = &self.rule_status {
+            ui.colored_label(egui::Color32::YELLOW, status);
+        }
+
+        if changed {
+            self.rules.save();
+            if self.rule_monitor.is_monitoring() {
+                self.rule_monitor.set_rules(self.rules.rules.clone());
+            }
+        }
+    }
+
+    fn apply_rule(&mut self, event: RuleEvent) {
+        let Some(rule) = self.rules.rules.get(event.rule).cloned() else {
+            return;
+        };
+        if event.fired {
+            match &rule.action {
+                RuleAction::Start(automation) => self.start_automation(*automation),
+                RuleAction::Stop(automation) => self.stop_automation(*automation),
+                RuleAction::SwitchProfile { name } => {
+                    self.load_profile(name);
+                }
+                RuleAction::StartProfile { name } => {
+                    if let Some(profile) = self.load_profile(name) {
+                        if profile.clicking {
+                            self.clicker.start_clicking();
+                        }
+                        if profile.moving {
+                            self.mouse_mover.start_moving();
+                        }
+                    }
+                }
+            }
+        } else {
+            for automation in rule.undo(&self.rules) {
+                self.stop_automation(automation);
+            }
+        }
+    }
+
+    /// Copies a profile's settings into the clicker and mover.
+    fn load_profile(&mut self, name: &str) -> Option<Profile> {
+        let Some(profile) = self.rules.profile(name).cloned() else {
+            self.rule_status = Some(format!("No profile named \"{}\"", name));
+            return None;
+        };
+        self.clicker.set_config(profile.clicker.clone());
+        self.mouse_mover.set_config(profile.mover.clone());
+        self.save_config();
+        self.rule_status = None;
+        Some(profile)
+    }
+
+    fn start_automation(&mut self, automation: Automation) {
+        match automation {
+            Automation::Clicker => self.clicker.start_clicking(),
+            Automation::Mover => self.mouse_mover.start_moving(),
+            Automation::Chain => {
+                if !self.chain_runner.is_running() {
+                    self.start_chain();
+                }
+            }
+        }
+    }
+
+    fn stop_automation(&mut self, automation: Automation) {
+        match automation {
+            Automation::Clicker => self.clicker.stop_clicking(),
+            Automation::Mover => self.mouse_mover.stop_moving(),
+            Automation::Chain => self.chain_runner.stop_running(),
+        }
+    }
+
+    fn waypoint_ui(&mut self, ui: &mut egui::Ui) {
+        let cursor = self.device_state.get_mouse().coords;
+        let layout = self.layout();
+        let path = &mut self.mouse_mover.config.waypoints;
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            changed |= ui
+                .radio_value(&mut path.mode, PathMode::Loop, "Loop")
+                .changed();
+            changed |= ui
+                .radio_value(&mut path.mode, PathMode::PingPong, "Ping-Pong")
+                .changed();
+            changed |= ui
+                .radio_value(&mut path.mode, PathMode::Once, "Once")
+                .changed();
+        });
+
+        let mut remove = None;
+        let mut raise = None;
+        egui::Grid::new("waypoints").striped(true).show(ui, |ui| {
+            for header in ["#", "X", "Y", "Space", "Rel", "Dwell", "Click", ""] {
+                ui.strong(header);
+            }
+            ui.end_row();
+            for (i, waypoint) in path.waypoints.iter_mut().enumerate() {
+                ui.label((i + 1).to_string());
+                let mut point = waypoint.point();
+                if screen_point_ui(ui, ("waypoint_space", i), &mut point, &layout) {
+                    (waypoint.x, waypoint.y, waypoint.space) = (point.x, point.y, point.space);
+                    changed = true;
+                }
+                changed |= ui.checkbox(&mut waypoint.relative, "").changed();
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut waypoint.dwell_ms)
+                            .speed(10.0)
+                            .suffix(" ms"),
+                    )
+                    .changed();
+                egui::ComboBox::from_id_salt(("waypoint_click", i))
+                    .width(60.0)
+                    .selected_text(match waypoint.click {
+                        Some(button) => format!("{:?}", button),
+                        None => "None".to_string(),
+                    })
+                    .show_ui(ui, |ui| {
+                        changed |= ui
+                            .selectable_value(&mut waypoint.click, None, "None")
+                            .changed();
+                        for button in [
+                            SerializableMouseButton::Left,
+                            SerializableMouseButton::Right,
+                            SerializableMouseButton::Middle,
+                        ] {
+                            changed |= ui
+                                .selectable_value(
+                                    &mut waypoint.click,
+                                    Some(button),
+                                    format!("{:?}", button),
+                                )
+                                .changed();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if i > 0 && ui.small_button("⏶").clicked() {
+                        raise = Some(i);
+                    }
+                    if ui.small_button("🗑").clicked() {
+                        remove = Some(i);
+                    }
+                });
+                ui.end_row();
+            }
+        });
+        if let Some(i) = raise {
+            path.waypoints.swap(i - 1, i);
+            changed = true;
+        }
+        if let Some(i) = remove {
+            path.waypoints.remove(i);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Add Cursor Position").clicked() {
+                path.waypoints.push(Waypoint {
+                    x: cursor.0 as f64,
+                    y: cursor.1 as f64,
+                    ..Waypoint::default()
+                });
+                changed = true;
+            }
+            ui.checkbox(&mut self.show_path_overlay, "Show on Screen");
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.waypoint_csv_path)
+                    .hint_text("path/to/waypoints.csv")
+                    .desired_width(140.0),
+            );
+            if ui.button("Import CSV").clicked() {
+                let result = fs::read_to_string(&self.waypoint_csv_path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| parse_csv(&text, monitors::primary_index(&self.monitors)));
+                self.waypoint_status = Some(match result {
+                    Ok(waypoints) => {
+                        let message = format!("Imported {} waypoints", waypoints.len());
+                        path.waypoints = waypoints;
+                        changed = true;
+                        message
+                    }
+                    Err(e) => format!("Import failed: {}", e),
+                });
+            }
+        });
+        if let Some(status) = &self.waypoint_status {
+            ui.small(status);
+        }
+
+        let points: Vec<(i32, i32)> = path
+            .waypoints
+            .iter()
+            .filter_map(|waypoint| waypoint.resolve(cursor, &layout))
+            .collect();
+        path_preview(ui, &points);
+
+        if changed {
+            self.save_config();
+        }
+    }
+
+    /// Every key that toggles an automation, the bound ones included.
+    fn hotkeys(&self) -> Vec<Keycode> {
+        hotkeys::with_bound(&[
+            self.macros.drag.hotkey.map(|key| key.0),
+            self.chains.settings.hotkey.map(|key| key.0),
+        ])
+    }
+
+    /// A key held down this frame that is not a hotkey, for key pickers.
+    fn unbound_key(&self) -> Option<Keycode> {
+        let hotkeys = self.hotkeys();
+        self.device_state
+            .get_keys()
+            .into_iter()
+            .find(|key| !hotkeys.contains(key))
+    }
+
     fn handle_toggle(
         &mut self,
         pressed: bool,
@@ -97,46 +1143,133 @@ impl Default for MourseApp {
             device_state: DeviceState::new(),
             last_key_press: std::time::Instant::now(),
             config_path: Self::get_config_path(),
+            waypoint_csv_path: String::new(),
+            waypoint_status: None,
+            show_path_overlay: false,
+            monitors: detect_monitors(),
+            picking_window: None,
+            window_status: None,
+            recorder: Recorder::default(),
+            player: MacroPlayer::default(),
+            macros: MacroLibrary::load(),
+            macro_name: "Macro 1".to_string(),
+            macro_status: None,
+            editor: MacroEditor::default(),
+            xdotool_path: String::new(),
+            scripts: ScriptLibrary::load(),
+            script_runner: ScriptRunner::default(),
+            typer: TextTyper::default(),
+            typing_status: None,
+            dragger: Dragger::default(),
+            drag_status: None,
+            chains: ChainLibrary::load(),
+            chain_runner: ChainRunner::default(),
+            chain_status: None,
+            rules: RuleLibrary::load(),
+            rule_monitor: RuleMonitor::default(),
+            rule_status: None,
+            profile_name: "Profile 1".to_string(),
+            rule_key_capture: None,
+            drag_key_capture: false,
+            chain_key_capture: false,
         };
         app.load_config();
+        if app.rules.watching {
+            app.rule_monitor.start_monitoring(app.rules.rules.clone());
+        }
         app
     }
 }
 
 impl eframe::App for MourseApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_window_pick(ctx);
+
         // Check for hotkeys with debouncing
         let keys: Vec<Keycode> = self.device_state.get_keys();
 
         // Debounced toggles
         self.handle_toggle(
-            keys.contains(&Keycode::F6),
+            keys.contains(&hotkeys::CLICKER),
             self.clicker.is_clicking(),
             |s| s.clicker.start_clicking(),
             |s| s.clicker.stop_clicking(),
         );
 
         self.handle_toggle(
-            keys.contains(&Keycode::F7),
+            keys.contains(&hotkeys::MOUSE_MOVER),
             self.mouse_mover.is_moving(),
             |s| s.mouse_mover.start_moving(),
             |s| s.mouse_mover.stop_moving(),
         );
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical(|ui| {
-                // Title with some spacing
-                ui.add_space(5.0);
-                ui.heading("Mourse");
-                ui.add_space(5.0);
+        self.handle_toggle(
+            keys.contains(&hotkeys::RECORDER),
+            self.recorder.is_recording(),
+            |s| s.recorder.start_recording(s.macros.recorder, s.hotkeys()),
+            |s| s.finish_recording(),
+        );
 
-                // Add config file button at the top level
-                if ui.button("Open Config File").clicked() {
-                    self.open_config_file();
-                }
-                ui.add_space(5.0);
+        self.handle_toggle(
+            keys.contains(&hotkeys::PLAYBACK),
+            self.player.is_playing(),
+            |s| s.start_playback(),
+            |s| s.player.stop_playing(),
+        );
 
-                ui.horizontal(|ui| {
+        self.handle_toggle(
+            keys.contains(&hotkeys::SCRIPT),
+            self.script_runner.is_running(),
+            |s| s.start_script(),
+            |s| s.script_runner.stop_running(),
+        );
+
+        self.handle_toggle(
+            bound(self.macros.drag.hotkey, &keys),
+            self.dragger.is_dragging(),
+            |s| s.start_drag(),
+            |s| s.dragger.stop_dragging(),
+        );
+
+        self.handle_toggle(
+            bound(self.chains.settings.hotkey, &keys),
+            self.chain_runner.is_running(),
+            |s| s.start_chain(),
+            |s| s.chain_runner.stop_running(),
+        );
+
+        for event in self.rule_monitor.take_events() {
+            self.apply_rule(event);
+        }
+
+        if self.show_path_overlay && self.mouse_mover.config.pattern == MovementPattern::Waypoints {
+            let cursor = self.device_state.get_mouse().coords;
+            let layout = self.layout();
+            let points: Vec<(i32, i32)> = self
+                .mouse_mover
+                .config
+                .waypoints
+                .waypoints
+                .iter()
+                .filter_map(|waypoint| waypoint.resolve(cursor, &layout))
+                .collect();
+            show_path_overlay(ctx, &points);
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::both().show(ui, |ui| {
+                // Title with some spacing
+                ui.add_space(5.0);
+                ui.heading("Mourse");
+                ui.add_space(5.0);
+
+                // Add config file button at the top level
+                if ui.button("Open Config File").clicked() {
+                    self.open_config_file();
+                }
+                ui.add_space(5.0);
+
+                ui.horizontal(|ui| {
                     ui.vertical(|ui| {
                         // Auto Clicker Settings
                         ui.group(|ui| {
@@ -150,16 +1283,61 @@ impl eframe::App for MourseApp {
                                     self.clicker.reset_click_count();
                                 }
                             });
+                            jitter_label(ui, &self.clicker.get_jitter_stats());
 
-                            ui.horizontal(|ui| {
-                                ui.label("Interval (ms):");
-                                let mut interval = self.clicker.get_interval();
-                                let slider = egui::Slider::new(&mut interval, 10..=1000);
-                                if ui.add(slider).changed() {
-                                    self.clicker.set_interval(interval);
-                                    self.save_config();
-                                }
-                            });
+                            if ui
+                                .checkbox(&mut self.clicker.config.cps_mode_enabled, "CPS Mode")
+                                .changed()
+                            {
+                                self.save_config();
+                            }
+
+                            if self.clicker.config.cps_mode_enabled {
+                                ui.horizontal(|ui| {
+                                    ui.label("Rate:");
+                                    let mut cps = self.clicker.get_cps();
+                                    let max_cps = self.clicker.get_max_cps();
+                                    let value = egui::DragValue::new(&mut cps)
+                                        .speed(0.5)
+                                        .range(0.1..=max_cps)
+                                        .max_decimals(2)
+                                        .suffix(" CPS");
+                                    if ui.add(value).changed() {
+                                        self.clicker.set_cps(cps);
+                                        self.save_config();
+                                    }
+                                    ui.label("Cap:");
+                                    let mut max_cps = self.clicker.get_max_cps();
+                                    let value = egui::DragValue::new(&mut max_cps)
+                                        .speed(1.0)
+                                        .range(1.0..=CPS_HARD_LIMIT)
+                                        .max_decimals(0);
+                                    if ui.add(value).changed() {
+                                        self.clicker.set_max_cps(max_cps);
+                                        self.save_config();
+                                    }
+                                });
+                            } else {
+                                ui.horizontal(|ui| {
+                                    ui.label("Interval (ms):");
+                                    let mut interval = self.clicker.get_interval();
+                                    let slider = egui::Slider::new(&mut interval, 10..=1000);
+                                    if ui.add(slider).changed() {
+                                        self.clicker.set_interval(interval);
+                                        self.save_config();
+                                    }
+                                });
+                            }
+
+                            if self.clicker.is_clicking() {
+                                let target =
+                                    1.0 / self.clicker.config.base_interval().as_secs_f64();
+                                ui.small(format!(
+                                    "Measured: {:.1} CPS (target {:.1})",
+                                    self.clicker.get_jitter_stats().measured_rate_hz(),
+                                    target
+                                ));
+                            }
 
                             ui.horizontal(|ui| {
                                 ui.label("Button:");
@@ -226,6 +1404,55 @@ impl eframe::App for MourseApp {
                                         self.save_config();
                                     }
                                 });
+
+                                let (min, max) = self.clicker.get_random_delay_range();
+                                if delay_distribution_ui(
+                                    ui,
+                                    "clicker_delay",
+                                    &mut self.clicker.config.delay_distribution,
+                                    min,
+                                    max,
+                                ) {
+                                    self.save_config();
+                                }
+                            }
+
+                            let cursor = self.device_state.get_mouse().coords;
+                            let layout = self.layout();
+                            ui.horizontal(|ui| {
+                                let mut fixed = self.clicker.config.fixed_point.is_some();
+                                if ui.checkbox(&mut fixed, "Fixed Point").changed() {
+                                    self.clicker.config.fixed_point =
+                                        fixed.then_some(ScreenPoint::absolute(cursor));
+                                    self.save_config();
+                                }
+                                if let Some(point) = &mut self.clicker.config.fixed_point {
+                                    if screen_point_ui(ui, "fixed_point_space", point, &layout) {
+                                        self.save_config();
+                                    }
+                                }
+                            });
+                            if self.clicker.config.needs_window() {
+                                self.window_target_ui(ui, WindowPick::Clicker);
+                            }
+                            if self.clicker.config.fixed_point.is_some() {
+                                ui.small(format!("Cursor is at ({}, {})", cursor.0, cursor.1));
+                                if restore_ui(
+                                    ui,
+                                    "clicker_restore",
+                                    &mut self.clicker.config.restore_cursor,
+                                ) {
+                                    self.save_config();
+                                }
+                            }
+
+                            if yield_ui(ui, &mut self.clicker.config.yield_to_user) {
+                                self.save_config();
+                            }
+
+                            let last_seed = self.clicker.get_last_seed();
+                            if seed_ui(ui, &mut self.clicker.config.seed, last_seed) {
+                                self.save_config();
                             }
 
                             let clicking_text = if self.clicker.is_clicking() {
@@ -233,6 +1460,12 @@ impl eframe::App for MourseApp {
                             } else {
                                 "Start Clicking (F6)"
                             };
+                            if self.clicker.is_yielding() {
+                                ui.small("Paused while you use the mouse or keyboard");
+                            }
+                            if self.clicker.is_waiting_for_window() {
+                                ui.small("Paused until the target window is open and focused");
+                            }
                             if ui.button(clicking_text).clicked() {
                                 if self.clicker.is_clicking() {
                                     self.clicker.stop_clicking();
@@ -246,7 +1479,7 @@ impl eframe::App for MourseApp {
 
                         // Mouse Mover Settings
                         ui.group(|ui| {
-                            ui.set_width(250.0);
+                            ui.set_min_width(250.0);
                             ui.heading("Random Mouse Mover");
 
                             ui.horizontal(|ui| {
@@ -256,6 +1489,7 @@ impl eframe::App for MourseApp {
                                     self.mouse_mover.reset_move_count();
                                 }
                             });
+                            jitter_label(ui, &self.mouse_mover.get_jitter_stats());
 
                             ui.horizontal(|ui| {
                                 ui.label("Move Interval (ms):");
@@ -267,15 +1501,69 @@ impl eframe::App for MourseApp {
                                 }
                             });
 
-                            ui.horizontal(|ui| {
-                                ui.label("Max Distance:");
-                                let mut max_distance = self.mouse_mover.get_max_distance();
-                                let slider = egui::Slider::new(&mut max_distance, 10..=500);
-                                if ui.add(slider).changed() {
-                                    self.mouse_mover.set_max_distance(max_distance);
+                            let config = &mut self.mouse_mover.config;
+                            if pattern_ui(
+                                ui,
+                                &mut config.pattern,
+                                &mut config.pattern_settings,
+                                &self.monitors,
+                            ) {
+                                self.save_config();
+                            }
+
+                            if self.mouse_mover.config.pattern == MovementPattern::KeepAwakeJiggle
+                                && jiggle_ui(ui, &mut self.mouse_mover.config.jiggle)
+                            {
+                                self.save_config();
+                            }
+
+                            if self.mouse_mover.config.pattern == MovementPattern::RandomJumps {
+                                ui.horizontal(|ui| {
+                                    ui.label("Max Distance:");
+                                    let mut max_distance = self.mouse_mover.get_max_distance();
+                                    let slider = egui::Slider::new(&mut max_distance, 10..=500);
+                                    if ui.add(slider).changed() {
+                                        self.mouse_mover.set_max_distance(max_distance);
+                                        self.save_config();
+                                    }
+                                });
+                            }
+
+                            if self.mouse_mover.config.pattern == MovementPattern::Waypoints {
+                                self.waypoint_ui(ui);
+                            }
+
+                            if self.mouse_mover.config.pattern == MovementPattern::Wander
+                                && wander_ui(ui, &mut self.mouse_mover.config.wander)
+                            {
+                                self.save_config();
+                            }
+
+                            if matches!(
+                                self.mouse_mover.config.pattern,
+                                MovementPattern::RandomJumps | MovementPattern::Wander
+                            ) {
+                                let config = &mut self.mouse_mover.config;
+                                if bounds_ui(
+                                    ui,
+                                    &mut config.bounds,
+                                    &mut config.bounds_behavior,
+                                    &self.monitors,
+                                ) {
                                     self.save_config();
                                 }
-                            });
+                                if ui.small_button("Refresh Monitors").clicked() {
+                                    self.monitors = detect_monitors();
+                                }
+                            }
+
+                            if self.mouse_mover.config.needs_window() {
+                                self.window_target_ui(ui, WindowPick::Mover);
+                            }
+
+                            if glide_ui(ui, "mover_glide", &mut self.mouse_mover.config.glide) {
+                                self.save_config();
+                            }
 
                             let mut random_delay = self.mouse_mover.is_random_delay();
                             if ui.checkbox(&mut random_delay, "Random Interval").changed() {
@@ -311,6 +1599,34 @@ impl eframe::App for MourseApp {
                                         self.save_config();
                                     }
                                 });
+
+                                let (min, max) = self.mouse_mover.get_random_delay_range();
+                                if delay_distribution_ui(
+                                    ui,
+                                    "mover_delay",
+                                    &mut self.mouse_mover.config.delay_distribution,
+                                    min,
+                                    max,
+                                ) {
+                                    self.save_config();
+                                }
+                            }
+
+                            if restore_ui(
+                                ui,
+                                "mover_restore",
+                                &mut self.mouse_mover.config.restore_cursor,
+                            ) {
+                                self.save_config();
+                            }
+
+                            if yield_ui(ui, &mut self.mouse_mover.config.yield_to_user) {
+                                self.save_config();
+                            }
+
+                            let last_seed = self.mouse_mover.get_last_seed();
+                            if seed_ui(ui, &mut self.mouse_mover.config.seed, last_seed) {
+                                self.save_config();
                             }
 
                             let moving_text = if self.mouse_mover.is_moving() {
@@ -318,6 +1634,12 @@ impl eframe::App for MourseApp {
                             } else {
                                 "Start Moving (F7)"
                             };
+                            if self.mouse_mover.is_yielding() {
+                                ui.small("Paused while you use the mouse or keyboard");
+                            }
+                            if self.mouse_mover.is_waiting_for_window() {
+                                ui.small("Paused until the target window is open and focused");
+                            }
                             if ui.button(moving_text).clicked() {
                                 if self.mouse_mover.is_moving() {
                                     self.mouse_mover.stop_moving();
@@ -326,6 +1648,54 @@ impl eframe::App for MourseApp {
                                 }
                             }
                         });
+
+                        ui.add_space(5.0);
+
+                        ui.group(|ui| {
+                            ui.set_min_width(250.0);
+                            ui.heading("Macros");
+                            self.macros_ui(ui);
+                        });
+
+                        ui.add_space(5.0);
+
+                        ui.group(|ui| {
+                            ui.set_min_width(250.0);
+                            ui.heading("Scripts");
+                            self.scripts_ui(ui);
+                        });
+
+                        ui.add_space(5.0);
+
+                        ui.group(|ui| {
+                            ui.set_min_width(250.0);
+                            ui.heading("Type Text");
+                            self.type_text_ui(ui);
+                        });
+
+                        ui.add_space(5.0);
+
+                        ui.group(|ui| {
+                            ui.set_min_width(250.0);
+                            ui.heading("Drag");
+                            self.drag_ui(ui);
+                        });
+
+                        ui.add_space(5.0);
+
+                        ui.group(|ui| {
+                            ui.set_min_width(250.0);
+                            ui.heading("Chains");
+                            self.chains_ui(ui);
+                        });
+
+                        ui.add_space(5.0);
+
+                        ui.group(|ui| {
+                            ui.set_min_width(250.0);
+                            ui.heading("Rules");
+                            self.rules_ui(ui);
+                        });
                     });
                 });
             });
@@ -335,7 +1705,1039 @@ impl eframe::App for MourseApp {
         ctx.request_repaint();
     }
 
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        // Keeps the path overlay window see-through; panels paint their own background
+        [0.0; 4]
+    }
+
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
         self.save_config();
     }
 }
+
+fn jitter_label(ui: &mut egui::Ui, stats: &JitterStats) {
+    ui.small(format!(
+        "Jitter: avg {:.2} ms, sd {:.2} ms, max {:.2} ms, resyncs {}",
+        stats.mean_lateness_us / 1000.0,
+        stats.stddev_lateness_us() / 1000.0,
+        stats.max_lateness_us / 1000.0,
+        stats.resyncs
+    ));
+}
+
+fn pattern_ui(
+    ui: &mut egui::Ui,
+    pattern: &mut MovementPattern,
+    settings: &mut PatternSettings,
+    monitors: &[Monitor],
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Pattern:");
+        egui::ComboBox::from_id_salt("mover_pattern")
+            .selected_text(pattern.name())
+            .show_ui(ui, |ui| {
+                for candidate in MovementPattern::ALL {
+                    let selected = pattern.name() == candidate.name();
+                    if ui.selectable_label(selected, candidate.name()).clicked() && !selected {
+                        *pattern = candidate;
+                        changed = true;
+                    }
+                }
+            });
+    });
+    if matches!(
+        pattern,
+        MovementPattern::RandomJumps
+            | MovementPattern::KeepAwakeJiggle
+            | MovementPattern::Wander
+            | MovementPattern::Waypoints
+    ) {
+        return changed;
+    }
+
+    ui.horizontal(|ui| {
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut settings.size_px)
+                    .range(5.0..=2000.0)
+                    .max_decimals(0)
+                    .prefix("Size: ")
+                    .suffix(" px"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut settings.period_s)
+                    .speed(0.1)
+                    .range(0.1..=600.0)
+                    .max_decimals(1)
+                    .prefix("Loop: ")
+                    .suffix(" s"),
+            )
+            .changed();
+        match pattern {
+            MovementPattern::Lissajous { freq_x, freq_y } => {
+                changed |= ui
+                    .add(egui::DragValue::new(freq_x).range(1..=10).prefix("a: "))
+                    .changed();
+                changed |= ui
+                    .add(egui::DragValue::new(freq_y).range(1..=10).prefix("b: "))
+                    .changed();
+            }
+            MovementPattern::Zigzag { teeth } => {
+                changed |= ui
+                    .add(egui::DragValue::new(teeth).range(1..=50).prefix("Teeth: "))
+                    .changed();
+            }
+            MovementPattern::Spiral { turns } => {
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(turns)
+                            .speed(0.1)
+                            .range(0.5..=20.0)
+                            .prefix("Turns: "),
+                    )
+                    .changed();
+            }
+            _ => {}
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Center:");
+        let candidates = [
+            ("Cursor", PatternCenter::Cursor),
+            ("Fixed", PatternCenter::Fixed { x: 960, y: 540 }),
+            ("Monitor", PatternCenter::Monitor { index: 0 }),
+        ];
+        for (name, candidate) in candidates {
+            let selected =
+                std::mem::discriminant(&settings.center) == std::mem::discriminant(&candidate);
+            if ui.radio(selected, name).clicked() && !selected {
+                settings.center = candidate;
+                changed = true;
+            }
+        }
+        match &mut settings.center {
+            PatternCenter::Cursor => {}
+            PatternCenter::Fixed { x, y } => {
+                changed |= ui.add(egui::DragValue::new(x).prefix("x: ")).changed();
+                changed |= ui.add(egui::DragValue::new(y).prefix("y: ")).changed();
+            }
+            PatternCenter::Monitor { index } => {
+                changed |= monitor_ui(ui, "pattern_monitor", index, monitors);
+            }
+        }
+    });
+
+    pattern_preview(ui, pattern, settings);
+    changed
+}
+
+fn wander_ui(ui: &mut egui::Ui, wander: &mut WanderSettings) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut wander.speed_px_per_s)
+                    .speed(5.0)
+                    .range(10.0..=5000.0)
+                    .max_decimals(0)
+                    .prefix("Speed: ")
+                    .suffix(" px/s"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut wander.roughness)
+                    .speed(0.01)
+                    .range(0.01..=10.0)
+                    .max_decimals(2)
+                    .prefix("Roughness: "),
+            )
+            .changed();
+    });
+    changed
+}
+
+fn jiggle_ui(ui: &mut egui::Ui, jiggle: &mut JiggleSettings) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut jiggle.every_s)
+                    .range(1..=3600)
+                    .prefix("Every: ")
+                    .suffix(" s"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut jiggle.idle_threshold_s)
+                    .range(0..=3600)
+                    .prefix("After idle: ")
+                    .suffix(" s"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut jiggle.distance_px)
+                    .range(1..=50)
+                    .suffix(" px"),
+            )
+            .changed();
+    });
+    changed
+}
+
+fn pattern_preview(ui: &mut egui::Ui, pattern: &MovementPattern, settings: &PatternSettings) {
+    let size = egui::vec2(ui.available_width(), 100.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let scale = rect.height() / 2.0 - 6.0;
+    let to_screen = |(x, y): (f64, f64)| rect.center() + egui::vec2(x as f32, y as f32) * scale;
+    let points: Vec<egui::Pos2> = (0..=240)
+        .map(|i| to_screen(pattern.offset(i as f64 / 240.0)))
+        .collect();
+    let stroke = egui::Stroke::new(1.0, ui.visuals().weak_text_color());
+    painter.add(egui::Shape::line(points, stroke));
+
+    // Animate a dot along the shape at the configured loop speed
+    let time = ui.input(|i| i.time);
+    let dot = to_screen(pattern.offset(time / settings.period_s.max(0.1)));
+    painter.circle_filled(dot, 4.0, ui.visuals().selection.bg_fill);
+}
+
+fn path_preview(ui: &mut egui::Ui, points: &[(i32, i32)]) {
+    let size = egui::vec2(ui.available_width(), 100.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    if points.is_empty() {
+        return;
+    }
+
+    // Fit the bounding box of the path into the preview, keeping its aspect ratio
+    let min_x = points.iter().map(|p| p.0).min().unwrap_or(0) as f32;
+    let max_x = points.iter().map(|p| p.0).max().unwrap_or(0) as f32;
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0) as f32;
+    let max_y = points.iter().map(|p| p.1).max().unwrap_or(0) as f32;
+    let inner = rect.shrink(12.0);
+    let scale =
+        (inner.width() / (max_x - min_x).max(1.0)).min(inner.height() / (max_y - min_y).max(1.0));
+    let offset = inner.center() - egui::vec2((min_x + max_x) / 2.0, (min_y + max_y) / 2.0) * scale;
+    let points: Vec<egui::Pos2> = points
+        .iter()
+        .map(|&(x, y)| offset + egui::vec2(x as f32, y as f32) * scale)
+        .collect();
+    paint_path(&painter, &points, ui.visuals().selection.bg_fill);
+}
+
+fn glide_ui(ui: &mut egui::Ui, id: &str, glide: &mut GlideSettings) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Motion:");
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(format!("{:?}", glide.style))
+            .show_ui(ui, |ui| {
+                for style in MotionStyle::ALL {
+                    changed |= ui
+                        .selectable_value(&mut glide.style, style, format!("{:?}", style))
+                        .changed();
+                }
+            });
+    });
+    if glide.style != MotionStyle::Instant {
+        ui.horizontal(|ui| {
+            ui.label("Speed:");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut glide.speed_px_per_s)
+                        .speed(10.0)
+                        .range(50.0..=5000.0)
+                        .max_decimals(0)
+                        .suffix(" px/s"),
+                )
+                .changed();
+            ui.label("Steps:");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut glide.step_hz)
+                        .speed(1.0)
+                        .range(10..=500)
+                        .suffix(" Hz"),
+                )
+                .changed();
+        });
+    }
+    changed
+}
+
+fn bounds_ui(
+    ui: &mut egui::Ui,
+    bounds: &mut MoveBounds,
+    behavior: &mut BoundsBehavior,
+    monitors: &[Monitor],
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Bounds:");
+        egui::ComboBox::from_id_salt("mover_bounds")
+            .selected_text(bounds.name())
+            .show_ui(ui, |ui| {
+                for candidate in MoveBounds::ALL {
+                    let selected = bounds.name() == candidate.name();
+                    if ui.selectable_label(selected, candidate.name()).clicked() && !selected {
+                        *bounds = candidate;
+                        changed = true;
+                    }
+                }
+            });
+    });
+
+    match bounds {
+        MoveBounds::Unbounded => return changed,
+        MoveBounds::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            ui.horizontal(|ui| {
+                changed |= ui.add(egui::DragValue::new(x).prefix("x: ")).changed();
+                changed |= ui.add(egui::DragValue::new(y).prefix("y: ")).changed();
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(width)
+                            .prefix("w: ")
+                            .range(1..=i32::MAX),
+                    )
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(height)
+                            .prefix("h: ")
+                            .range(1..=i32::MAX),
+                    )
+                    .changed();
+            });
+        }
+        MoveBounds::Radius { radius } => {
+            ui.horizontal(|ui| {
+                ui.label("Radius:");
+                changed |= ui
+                    .add(egui::DragValue::new(radius).range(1..=10000).suffix(" px"))
+                    .changed();
+            });
+        }
+        MoveBounds::CurrentMonitor | MoveBounds::Window => {}
+        MoveBounds::Monitor { index } => {
+            changed |= monitor_ui(ui, "bounds_monitor", index, monitors);
+        }
+    }
+
+    ui.horizontal(|ui| {
+        let mut reflect = *behavior == BoundsBehavior::Reflect;
+        if ui.radio_value(&mut reflect, true, "Reflect").changed() {
+            *behavior = BoundsBehavior::Reflect;
+            changed = true;
+        }
+        if ui
+            .radio_value(&mut reflect, false, "Pull to center")
+            .changed()
+        {
+            *behavior = BoundsBehavior::MeanReversion { strength: 0.1 };
+            changed = true;
+        }
+        if let BoundsBehavior::MeanReversion { strength } = behavior {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(strength)
+                        .speed(0.01)
+                        .range(0.0..=1.0)
+                        .max_decimals(2),
+                )
+                .changed();
+        }
+    });
+    changed
+}
+
+fn restore_ui(ui: &mut egui::Ui, id: &str, restore: &mut CursorRestore) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Restore Cursor on Stop:");
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(format!("{:?}", restore))
+            .show_ui(ui, |ui| {
+                for option in [
+                    CursorRestore::Off,
+                    CursorRestore::Instant,
+                    CursorRestore::Smooth,
+                ] {
+                    changed |= ui
+                        .selectable_value(restore, option, format!("{:?}", option))
+                        .changed();
+                }
+            });
+    });
+    changed
+}
+
+fn yield_ui(ui: &mut egui::Ui, settings: &mut YieldSettings) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= ui
+            .checkbox(&mut settings.enabled, "Yield to User")
+            .changed();
+        if settings.enabled {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut settings.quiet_period_ms)
+                        .speed(10.0)
+                        .range(100..=60000)
+                        .prefix("resume after ")
+                        .suffix(" ms"),
+                )
+                .changed();
+        }
+    });
+    changed
+}
+
+fn playback_ui(ui: &mut egui::Ui, settings: &mut PlaybackSettings, last_seed: u64) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Speed:");
+        changed |= ui
+            .add(
+                egui::Slider::new(&mut settings.speed, MIN_SPEED..=MAX_SPEED)
+                    .logarithmic(true)
+                    .max_decimals(2)
+                    .suffix("x"),
+            )
+            .changed();
+    });
+    ui.horizontal(|ui| {
+        let mut forever = settings.loops.is_none();
+        if ui.checkbox(&mut forever, "Loop Forever").changed() {
+            settings.loops = if forever { None } else { Some(1) };
+            changed = true;
+        }
+        if let Some(loops) = &mut settings.loops {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(loops)
+                        .range(1..=u32::MAX)
+                        .prefix("Loops: "),
+                )
+                .changed();
+        }
+    });
+    ui.horizontal(|ui| {
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut settings.loop_delay_ms)
+                    .speed(10.0)
+                    .prefix("Loop delay: ")
+                    .suffix(" ms"),
+            )
+            .changed();
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut settings.jitter_ms)
+                    .range(0..=1000)
+                    .prefix("Jitter: ±")
+                    .suffix(" ms"),
+            )
+            .changed();
+    });
+    changed |= seed_ui(ui, &mut settings.seed, last_seed);
+    changed
+}
+
+fn script_settings_ui(ui: &mut egui::Ui, settings: &mut ScriptSettings, last_seed: u64) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= limit_ui(
+            ui,
+            "Operation Limit",
+            &mut settings.max_operations,
+            10_000_000,
+            "",
+        );
+    });
+    ui.horizontal(|ui| {
+        changed |= limit_ui(ui, "Time Limit", &mut settings.time_limit_secs, 300, " s");
+    });
+    changed |= seed_ui(ui, &mut settings.seed, last_seed);
+    changed
+}
+
+/// Edits the stages of a chain, marking `current` while it runs.
+fn chain_stages_ui(
+    ui: &mut egui::Ui,
+    stages: &mut Vec<ChainStage>,
+    macro_names: &[String],
+    current: Option<usize>,
+) -> bool {
+    let mut changed = false;
+    let mut action = None;
+    let count = stages.len();
+    for (i, stage) in stages.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let marker = if current == Some(i) { "▶" } else { " " };
+            ui.monospace(format!("{}{:>2}.", marker, i + 1));
+            egui::ComboBox::from_id_salt(("chain_action", i))
+                .width(70.0)
+                .selected_text(stage.action.name())
+                .show_ui(ui, |ui| {
+                    for candidate in ChainAction::ALL {
+                        let selected = stage.action.name() == candidate.name();
+                        if ui.selectable_label(selected, candidate.name()).clicked() && !selected {
+                            stage.action = candidate;
+                            changed = true;
+                        }
+                    }
+                });
+            match &mut stage.action {
+                ChainAction::Macro { name } => {
+                    egui::ComboBox::from_id_salt(("chain_macro", i))
+                        .width(90.0)
+                        .selected_text(name.as_str())
+                        .show_ui(ui, |ui| {
+                            for candidate in macro_names {
+                                changed |= ui
+                                    .selectable_value(name, candidate.clone(), candidate)
+                                    .changed();
+                            }
+                        });
+                }
+                ChainAction::Type { text } => {
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(text)
+                                .hint_text("text{ENTER}")
+                                .desired_width(90.0),
+                        )
+                        .changed();
+                }
+                _ => {}
+            }
+            if stage.action != ChainAction::Wait {
+                let unit = format!(" {}", stage.action.unit());
+                changed |= limit_ui(ui, "Stop after", &mut stage.stop.count, 100, &unit);
+            }
+            changed |= limit_ui(ui, "or", &mut stage.stop.secs, 60, " s");
+            if ui
+                .add_enabled(i > 0, egui::Button::new("⏶").small())
+                .clicked()
+            {
+                action = Some((i, -1));
+            }
+            if ui
+                .add_enabled(i + 1 < count, egui::Button::new("⏷").small())
+                .clicked()
+            {
+                action = Some((i, 1));
+            }
+            if ui.small_button("🗑").clicked() {
+                action = Some((i, 0));
+            }
+        });
+    }
+    match action {
+        Some((i, 0)) => {
+            stages.remove(i);
+            changed = true;
+        }
+        Some((i, offset)) => {
+            stages.swap(i, i.saturating_add_signed(offset));
+            changed = true;
+        }
+        None => {}
+    }
+    if ui.button("Add Stage").clicked() {
+        stages.push(ChainStage {
+            action: ChainAction::Wait,
+            stop: StopAfter {
+                count: None,
+                secs: Some(5),
+            },
+        });
+        changed = true;
+    }
+    changed
+}
+
+fn rule_state_label(ui: &mut egui::Ui, state: Option<RuleState>) {
+    match state {
+        Some(RuleState::Fired) => ui.colored_label(egui::Color32::YELLOW, "Fired"),
+        Some(RuleState::Armed) => ui.colored_label(egui::Color32::GREEN, "Armed"),
+        Some(state) => ui.weak(state.name()),
+        None => ui.weak("Not watching"),
+    };
+}
+
+/// Edits a rule's trigger and action. `capture` is the index of the rule
+/// whose key button is waiting for `key`, the key pressed this frame.
+fn rule_ui(
+    ui: &mut egui::Ui,
+    id: usize,
+    rule: &mut Rule,
+    profiles: &[String],
+    capture: &mut Option<usize>,
+    key: Option<Keycode>,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("When");
+        egui::ComboBox::from_id_salt(("rule_trigger", id))
+            .width(110.0)
+            .selected_text(rule.trigger.name())
+            .show_ui(ui, |ui| {
+                for candidate in Trigger::ALL {
+                    let selected = rule.trigger.name() == candidate.name();
+                    if ui.selectable_label(selected, candidate.name()).clicked() && !selected {
+                        rule.trigger = candidate;
+                        changed = true;
+                    }
+                }
+            });
+        match &mut rule.trigger {
+            Trigger::Idle { secs } => {
+                changed |= ui
+                    .add(egui::DragValue::new(secs).range(1..=u64::MAX).suffix(" s"))
+                    .changed();
+            }
+            Trigger::KeyHeld { key: held } => {
+                if *capture != Some(id) {
+                    if ui
+                        .button(held.0.to_string())
+                        .on_hover_text("Click, then press a key")
+                        .clicked()
+                    {
+                        *capture = Some(id);
+                    }
+                } else if let Some(pressed) = key {
+                    held.0 = pressed;
+                    *capture = None;
+                    changed = true;
+                } else if ui.button("Press a key…").clicked() {
+                    *capture = None;
+                }
+            }
+            Trigger::At { hour, minute } => {
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(hour)
+                            .range(0..=23)
+                            .custom_formatter(|n, _| format!("{:02}", n)),
+                    )
+                    .changed();
+                ui.label(":");
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(minute)
+                            .range(0..=59)
+                            .custom_formatter(|n, _| format!("{:02}", n)),
+                    )
+                    .changed();
+            }
+            Trigger::WindowTitle { pattern } => {
+                changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(pattern)
+                            .hint_text("part of the title")
+                            .desired_width(110.0),
+                    )
+                    .changed();
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Then");
+        egui::ComboBox::from_id_salt(("rule_action", id))
+            .width(110.0)
+            .selected_text(rule.action.name())
+            .show_ui(ui, |ui| {
+                for candidate in RuleAction::ALL {
+                    let selected = rule.action.name() == candidate.name();
+                    if ui.selectable_label(selected, candidate.name()).clicked() && !selected {
+                        rule.action = candidate;
+                        changed = true;
+                    }
+                }
+            });
+        match &mut rule.action {
+            RuleAction::Start(automation) | RuleAction::Stop(automation) => {
+                egui::ComboBox::from_id_salt(("rule_automation", id))
+                    .width(70.0)
+                    .selected_text(automation.name())
+                    .show_ui(ui, |ui| {
+                        for candidate in Automation::ALL {
+                            changed |= ui
+                                .selectable_value(automation, candidate, candidate.name())
+                                .changed();
+                        }
+                    });
+            }
+            RuleAction::SwitchProfile { name } | RuleAction::StartProfile { name } => {
+                egui::ComboBox::from_id_salt(("rule_profile", id))
+                    .width(90.0)
+                    .selected_text(name.as_str())
+                    .show_ui(ui, |ui| {
+                        for candidate in profiles {
+                            changed |= ui
+                                .selectable_value(name, candidate.clone(), candidate)
+                                .changed();
+                        }
+                    });
+            }
+        }
+    });
+    if rule.can_revert() {
+        changed |= ui
+            .checkbox(&mut rule.revert, "Stop again when it no longer holds")
+            .changed();
+    }
+    changed
+}
+
+fn typing_style_ui(ui: &mut egui::Ui, style: &mut TypingStyle) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Delay per character:");
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut style.min_char_delay_ms)
+                    .range(0..=style.max_char_delay_ms)
+                    .suffix(" ms"),
+            )
+            .changed();
+        ui.label("to");
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut style.max_char_delay_ms)
+                    .range(style.min_char_delay_ms..=5000)
+                    .suffix(" ms"),
+            )
+            .changed();
+    });
+    changed |= delay_distribution_ui(
+        ui,
+        "typing_distribution",
+        &mut style.distribution,
+        style.min_char_delay_ms,
+        style.max_char_delay_ms,
+    );
+    ui.horizontal(|ui| {
+        changed |= ui
+            .add(
+                egui::Slider::new(&mut style.typo_percent, 0.0..=20.0)
+                    .max_decimals(1)
+                    .text("Typos")
+                    .suffix("%"),
+            )
+            .on_hover_text("Chance of hitting a neighbouring key, then backspacing")
+            .changed();
+    });
+    changed
+}
+
+fn limit_ui(
+    ui: &mut egui::Ui,
+    label: &str,
+    limit: &mut Option<u64>,
+    default: u64,
+    suffix: &str,
+) -> bool {
+    let mut changed = false;
+    let mut limited = limit.is_some();
+    if ui.checkbox(&mut limited, label).changed() {
+        *limit = limited.then_some(default);
+        changed = true;
+    }
+    if let Some(value) = limit {
+        changed |= ui
+            .add(
+                egui::DragValue::new(value)
+                    .range(1..=u64::MAX)
+                    .suffix(suffix),
+            )
+            .changed();
+    }
+    changed
+}
+
+/// Whether the bound `hotkey` is among the held `keys`.
+fn bound(hotkey: Option<Key>, keys: &[Keycode]) -> bool {
+    hotkey.is_some_and(|hotkey| keys.contains(&hotkey.0))
+}
+
+fn hotkey_label(hotkey: Option<Key>) -> String {
+    hotkey
+        .map(|hotkey| format!(" ({})", hotkey.0))
+        .unwrap_or_default()
+}
+
+/// Binds `hotkey` to the next key pressed after its button is clicked.
+/// `capturing` is set while waiting for `key`, the key pressed this frame.
+fn hotkey_ui(
+    ui: &mut egui::Ui,
+    hotkey: &mut Option<Key>,
+    capturing: &mut bool,
+    key: Option<Keycode>,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Hotkey:");
+        if !*capturing {
+            let label = hotkey.map_or("None".to_string(), |hotkey| hotkey.0.to_string());
+            if ui
+                .button(label)
+                .on_hover_text("Click, then press a key")
+                .clicked()
+            {
+                *capturing = true;
+            }
+            if hotkey.is_some() && ui.small_button("✖").on_hover_text("Unbind").clicked() {
+                *hotkey = None;
+                changed = true;
+            }
+        } else if let Some(pressed) = key {
+            *hotkey = Some(Key(pressed));
+            *capturing = false;
+            changed = true;
+        } else if ui.button("Press a key…").clicked() {
+            *capturing = false;
+        }
+    });
+    changed
+}
+
+fn seed_ui(ui: &mut egui::Ui, seed: &mut Option<u64>, last_seed: u64) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        let mut fixed = seed.is_some();
+        if ui.checkbox(&mut fixed, "Fixed Seed").changed() {
+            *seed = fixed.then_some(last_seed);
+            changed = true;
+        }
+        if let Some(value) = seed {
+            changed |= ui.add(egui::DragValue::new(value)).changed();
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.small(format!("Last run seed: {}", last_seed));
+        if ui.small_button("Reuse").clicked() {
+            *seed = Some(last_seed);
+            changed = true;
+        }
+    });
+    changed
+}
+
+fn delay_distribution_ui(
+    ui: &mut egui::Ui,
+    id: &str,
+    distribution: &mut DelayDistribution,
+    min_ms: u64,
+    max_ms: u64,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Distribution:");
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(distribution.name())
+            .show_ui(ui, |ui| {
+                for candidate in DelayDistribution::ALL {
+                    let selected = distribution.name() == candidate.name();
+                    if ui.selectable_label(selected, candidate.name()).clicked() && !selected {
+                        *distribution = candidate;
+                        changed = true;
+                    }
+                }
+            });
+    });
+
+    ui.horizontal(|ui| {
+        let mut param = |ui: &mut egui::Ui, label: &str, value: &mut f64, suffix: &str| {
+            ui.label(label);
+            changed |= ui
+                .add(
+                    egui::DragValue::new(value)
+                        .speed(0.5)
+                        .range(0.0..=1000.0)
+                        .max_decimals(2)
+                        .suffix(suffix),
+                )
+                .changed();
+        };
+        match distribution {
+            DelayDistribution::Uniform => {}
+            DelayDistribution::Normal { mean_ms, stddev_ms } => {
+                param(ui, "Mean:", mean_ms, " ms");
+                param(ui, "SD:", stddev_ms, " ms");
+            }
+            DelayDistribution::LogNormal { median_ms, sigma } => {
+                param(ui, "Median:", median_ms, " ms");
+                param(ui, "Sigma:", sigma, "");
+            }
+            DelayDistribution::Exponential { mean_ms } => {
+                param(ui, "Mean:", mean_ms, " ms");
+            }
+        }
+    });
+
+    // Sampling the histogram takes thousands of draws, so keep the bins in
+    // egui's memory and only resample when the inputs change
+    let cache_id = ui.id().with(id).with("histogram");
+    let key = (*distribution, min_ms, max_ms);
+    let cached =
+        ui.data(|data| data.get_temp::<((DelayDistribution, u64, u64), Vec<u32>)>(cache_id));
+    let bins = match cached {
+        Some((cached_key, bins)) if cached_key == key => bins,
+        _ => {
+            let bins = distribution.histogram(min_ms, max_ms, 40);
+            ui.data_mut(|data| data.insert_temp(cache_id, (key, bins.clone())));
+            bins
+        }
+    };
+    histogram_preview(ui, &bins);
+    changed
+}
+
+fn histogram_preview(ui: &mut egui::Ui, bins: &[u32]) {
+    let size = egui::vec2(ui.available_width(), 40.0);
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let peak = bins.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let bar_width = rect.width() / bins.len() as f32;
+    for (i, &count) in bins.iter().enumerate() {
+        let height = rect.height() * count as f32 / peak;
+        let x = rect.left() + i as f32 * bar_width;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(x, rect.bottom() - height),
+            egui::pos2(x + bar_width - 1.0, rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, ui.visuals().selection.bg_fill);
+    }
+}
+
+/// The monitor layout for the panels. If input cannot be set up, the GUI
+/// still starts and assumes a single main display.
+fn detect_monitors() -> Vec<Monitor> {
+    match try_create_enigo() {
+        Ok(enigo) => enigo.monitors(),
+        Err(e) => {
+            eprintln!("Failed to detect monitors: {}", e);
+            vec![Monitor::main_display((1920, 1080))]
+        }
+    }
+}
+
+fn monitor_ui(ui: &mut egui::Ui, id: &str, index: &mut usize, monitors: &[Monitor]) -> bool {
+    let mut changed = false;
+    let selected_text = monitors
+        .get(*index)
+        .map(Monitor::label)
+        .unwrap_or_else(|| format!("Monitor {} (not connected)", index));
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            for (i, monitor) in monitors.iter().enumerate() {
+                changed |= ui.selectable_value(index, i, monitor.label()).changed();
+            }
+        });
+    changed
+}
+
+/// X/Y fields plus a coordinate space picker. Switching space converts the
+/// point so it stays at the same place on screen where that is known.
+pub fn screen_point_ui(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    point: &mut ScreenPoint,
+    layout: &Layout,
+) -> bool {
+    let mut changed = false;
+    let speed = match point.space {
+        CoordSpace::MonitorPercent { .. } => 0.1,
+        _ => 1.0,
+    };
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut point.x)
+                .speed(speed)
+                .max_decimals(1),
+        )
+        .changed();
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut point.y)
+                .speed(speed)
+                .max_decimals(1),
+        )
+        .changed();
+
+    let mut spaces = vec![CoordSpace::Absolute, CoordSpace::Window];
+    spaces.extend((0..layout.monitors.len()).map(|monitor| CoordSpace::MonitorPercent { monitor }));
+    let label = |space: &CoordSpace| match space {
+        CoordSpace::MonitorPercent { monitor } => format!("% of monitor {}", monitor + 1),
+        other => other.name().to_string(),
+    };
+    egui::ComboBox::from_id_salt(id)
+        .width(90.0)
+        .selected_text(label(&point.space))
+        .show_ui(ui, |ui| {
+            for space in spaces {
+                if ui
+                    .selectable_label(point.space == space, label(&space))
+                    .clicked()
+                    && point.space != space
+                {
+                    *point = match point.resolve(layout) {
+                        Some(position) => ScreenPoint::from_pixels(position, space, layout),
+                        None => ScreenPoint { space, ..*point },
+                    };
+                    changed = true;
+                }
+            }
+        });
+    changed
+}
+
+fn window_target_combo(ui: &mut egui::Ui, id: &str, target: &mut WindowTarget) -> bool {
+    let mut changed = false;
+    ui.label("Window:");
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(target.name())
+        .show_ui(ui, |ui| {
+            for candidate in [
+                WindowTarget::Focused,
+                WindowTarget::Title(String::new()),
+                WindowTarget::Class(String::new()),
+            ] {
+                let selected = target.name() == candidate.name();
+                if ui.selectable_label(selected, candidate.name()).clicked() && !selected {
+                    *target = candidate;
+                    changed = true;
+                }
+            }
+        });
+    match target {
+        WindowTarget::Focused => {}
+        WindowTarget::Title(text) | WindowTarget::Class(text) => {
+            changed |= ui
+                .add(egui::TextEdit::singleline(text).desired_width(100.0))
+                .changed();
+        }
+    }
+    changed
+}
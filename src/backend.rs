@@ -0,0 +1,163 @@
+use crate::idle;
+use crate::monitors::{self, Monitor};
+use crate::screen;
+use enigo::{
+    Axis, Button, Coordinate, Direction, Enigo, InputResult, Key, Keyboard, Mouse, NewConError,
+    Settings,
+};
+use std::thread;
+use std::time::Duration;
+
+/// The subset of input injection the workers need, so they can run against
+/// either the real desktop or a recording mock.
+pub trait InputBackend {
+    fn button(&mut self, button: Button, direction: Direction) -> InputResult<()>;
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> InputResult<()>;
+    fn key(&mut self, key: Key, direction: Direction) -> InputResult<()>;
+    fn text(&mut self, text: &str) -> InputResult<()>;
+    fn scroll(&mut self, length: i32, axis: Axis) -> InputResult<()>;
+    fn location(&self) -> InputResult<(i32, i32)>;
+    fn main_display(&self) -> InputResult<(i32, i32)>;
+    fn monitors(&self) -> Vec<Monitor> {
+        let size = self.main_display().unwrap_or((1920, 1080));
+        vec![Monitor::main_display(size)]
+    }
+    /// Colour of a screen pixel, where the platform lets us read it.
+    fn pixel(&self, _x: i32, _y: i32) -> Option<[u8; 3]> {
+        None
+    }
+    /// Pauses between the sub-steps of a single action, e.g. a glide.
+    fn sleep(&mut self, duration: Duration);
+}
+
+impl InputBackend for Enigo {
+    fn button(&mut self, button: Button, direction: Direction) -> InputResult<()> {
+        Mouse::button(self, button, direction)
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> InputResult<()> {
+        Mouse::move_mouse(self, x, y, coordinate)?;
+        if let Ok(position) = Mouse::location(self) {
+            idle::note_injected_cursor(position);
+        }
+        Ok(())
+    }
+
+    fn key(&mut self, key: Key, direction: Direction) -> InputResult<()> {
+        Keyboard::key(self, key, direction)
+    }
+
+    fn text(&mut self, text: &str) -> InputResult<()> {
+        Keyboard::text(self, text)
+    }
+
+    fn scroll(&mut self, length: i32, axis: Axis) -> InputResult<()> {
+        Mouse::scroll(self, length, axis)
+    }
+
+    fn location(&self) -> InputResult<(i32, i32)> {
+        Mouse::location(self)
+    }
+
+    fn main_display(&self) -> InputResult<(i32, i32)> {
+        Mouse::main_display(self)
+    }
+
+    fn monitors(&self) -> Vec<Monitor> {
+        monitors::query_system().unwrap_or_else(|| {
+            let size = Mouse::main_display(self).unwrap_or((1920, 1080));
+            vec![Monitor::main_display(size)]
+        })
+    }
+
+    fn pixel(&self, x: i32, y: i32) -> Option<[u8; 3]> {
+        screen::pixel_at(x, y)
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+pub fn create_enigo() -> Enigo {
+    try_create_enigo().expect("Failed to create Enigo instance")
+}
+
+/// For callers that can carry on without input, such as the GUI starting up
+/// with no display or without input permissions.
+pub fn try_create_enigo() -> Result<Enigo, NewConError> {
+    Enigo::new(&Settings::default())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Button(Button, Direction),
+    Move(i32, i32, Coordinate),
+    Key(Key, Direction),
+    Text(String),
+    Scroll(i32, Axis),
+    Sleep(Duration),
+}
+
+/// Records every action instead of touching the desktop. Sleeps are recorded
+/// rather than performed, and the cursor position is tracked on a virtual
+/// 1920x1080 display so location queries behave like a real screen.
+#[derive(Debug)]
+pub struct MockBackend {
+    pub actions: Vec<Action>,
+    cursor: (i32, i32),
+    display: (i32, i32),
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self {
+            actions: Vec::new(),
+            cursor: (960, 540),
+            display: (1920, 1080),
+        }
+    }
+}
+
+impl InputBackend for MockBackend {
+    fn button(&mut self, button: Button, direction: Direction) -> InputResult<()> {
+        self.actions.push(Action::Button(button, direction));
+        Ok(())
+    }
+
+    fn move_mouse(&mut self, x: i32, y: i32, coordinate: Coordinate) -> InputResult<()> {
+        self.actions.push(Action::Move(x, y, coordinate));
+        self.cursor = match coordinate {
+            Coordinate::Abs => (x, y),
+            Coordinate::Rel => (self.cursor.0 + x, self.cursor.1 + y),
+        };
+        Ok(())
+    }
+
+    fn key(&mut self, key: Key, direction: Direction) -> InputResult<()> {
+        self.actions.push(Action::Key(key, direction));
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> InputResult<()> {
+        self.actions.push(Action::Text(text.to_string()));
+        Ok(())
+    }
+
+    fn scroll(&mut self, length: i32, axis: Axis) -> InputResult<()> {
+        self.actions.push(Action::Scroll(length, axis));
+        Ok(())
+    }
+
+    fn location(&self) -> InputResult<(i32, i32)> {
+        Ok(self.cursor)
+    }
+
+    fn main_display(&self) -> InputResult<(i32, i32)> {
+        Ok(self.display)
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.actions.push(Action::Sleep(duration));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::coords::{Layout, Rect};
+use crate::monitors::monitor_at;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum MoveBounds {
+    #[default]
+    Unbounded,
+    Rect {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    /// Circle around the cursor position at the time the mover started.
+    Radius {
+        radius: i32,
+    },
+    /// The monitor the cursor was on when the mover started.
+    CurrentMonitor,
+    Monitor {
+        index: usize,
+    },
+    /// The target window, following it as it moves or resizes.
+    Window,
+}
+
+impl MoveBounds {
+    pub const ALL: [MoveBounds; 6] = [
+        MoveBounds::Unbounded,
+        MoveBounds::Rect {
+            x: 0,
+            y: 0,
+            width: 800,
+            height: 600,
+        },
+        MoveBounds::Radius { radius: 200 },
+        MoveBounds::CurrentMonitor,
+        MoveBounds::Monitor { index: 0 },
+        MoveBounds::Window,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MoveBounds::Unbounded => "Unbounded",
+            MoveBounds::Rect { .. } => "Rectangle",
+            MoveBounds::Radius { .. } => "Radius",
+            MoveBounds::CurrentMonitor => "Current Monitor",
+            MoveBounds::Monitor { .. } => "Monitor",
+            MoveBounds::Window => "Window",
+        }
+    }
+
+    pub fn resolve(&self, anchor: (i32, i32), layout: &Layout) -> Option<Region> {
+        let monitors = &layout.monitors;
+        match *self {
+            MoveBounds::Unbounded => None,
+            MoveBounds::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => Some(Region::from(Rect {
+                x,
+                y,
+                width,
+                height,
+            })),
+            MoveBounds::Radius { radius } => Some(Region::Circle {
+                cx: anchor.0 as f64,
+                cy: anchor.1 as f64,
+                radius: radius.max(1) as f64,
+            }),
+            MoveBounds::CurrentMonitor => monitor_at(monitors, anchor).map(|m| m.rect().into()),
+            MoveBounds::Monitor { index } => monitors
+                .get(index)
+                .or_else(|| monitor_at(monitors, anchor))
+                .map(|m| m.rect().into()),
+            MoveBounds::Window => layout.window.map(Region::from),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum BoundsBehavior {
+    /// Steps that would leave the area bounce back off its edge.
+    #[default]
+    Reflect,
+    /// Every step is pulled towards the center by `strength` (0..1) of the
+    /// remaining distance, so the walk keeps returning to the middle.
+    MeanReversion { strength: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Rect {
+        left: f64,
+        top: f64,
+        right: f64,
+        bottom: f64,
+    },
+    Circle {
+        cx: f64,
+        cy: f64,
+        radius: f64,
+    },
+}
+
+impl From<Rect> for Region {
+    fn from(rect: Rect) -> Self {
+        Region::Rect {
+            left: rect.x as f64,
+            top: rect.y as f64,
+            right: (rect.x + rect.width.max(1) - 1) as f64,
+            bottom: (rect.y + rect.height.max(1) - 1) as f64,
+        }
+    }
+}
+
+impl Region {
+    pub fn center(&self) -> (f64, f64) {
+        match *self {
+            Region::Rect {
+                left,
+                top,
+                right,
+                bottom,
+            } => ((left + right) / 2.0, (top + bottom) / 2.0),
+            Region::Circle { cx, cy, .. } => (cx, cy),
+        }
+    }
+
+    /// Applies a relative step from `from`, keeping the result inside the region.
+    pub fn step(
+        &self,
+        from: (i32, i32),
+        delta: (i32, i32),
+        behavior: BoundsBehavior,
+    ) -> (i32, i32) {
+        let (mut x, mut y) = (
+            from.0 as f64 + delta.0 as f64,
+            from.1 as f64 + delta.1 as f64,
+        );
+        match behavior {
+            BoundsBehavior::Reflect => {
+                (x, y) = self.reflect(x, y);
+            }
+            BoundsBehavior::MeanReversion { strength } => {
+                let (cx, cy) = self.center();
+                let strength = strength.clamp(0.0, 1.0);
+                x += (cx - x) * strength;
+                y += (cy - y) * strength;
+            }
+        }
+        let (x, y) = self.clamp(x, y);
+        (x.round() as i32, y.round() as i32)
+    }
+
+    fn reflect(&self, x: f64, y: f64) -> (f64, f64) {
+        match *self {
+            Region::Rect {
+                left,
+                top,
+                right,
+                bottom,
+            } => (fold(x, left, right), fold(y, top, bottom)),
+            Region::Circle { cx, cy, radius } => {
+                let distance = (x - cx).hypot(y - cy);
+                if distance <= radius {
+                    return (x, y);
+                }
+                let reflected = fold(distance, 0.0, radius);
+                (
+                    cx + (x - cx) / distance * reflected,
+                    cy + (y - cy) / distance * reflected,
+                )
+            }
+        }
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        self.clamp(x, y) == (x, y)
+    }
+
+    pub fn clamp(&self, x: f64, y: f64) -> (f64, f64) {
+        match *self {
+            Region::Rect {
+                left,
+                top,
+                right,
+                bottom,
+            } => (x.clamp(left, right), y.clamp(top, bottom)),
+            Region::Circle { cx, cy, radius } => {
+                let distance = (x - cx).hypot(y - cy);
+                if distance <= radius {
+                    (x, y)
+                } else {
+                    (
+                        cx + (x - cx) / distance * radius,
+                        cy + (y - cy) / distance * radius,
+                    )
+                }
+            }
+        }
+    }
+}
+
+// Mirrors `value` back and forth between `min` and `max` like a ball bouncing
+// between two walls, so even steps larger than the area stay inside it.
+fn fold(value: f64, min: f64, max: f64) -> f64 {
+    let span = max - min;
+    if span <= 0.0 {
+        return min;
+    }
+    let offset = (value - min).rem_euclid(2.0 * span);
+    if offset <= span {
+        min + offset
+    } else {
+        min + 2.0 * span - offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fold_bounces_between_walls() {
+        assert_eq!(fold(5.0, 0.0, 10.0), 5.0);
+        assert_eq!(fold(12.0, 0.0, 10.0), 8.0);
+        assert_eq!(fold(-3.0, 0.0, 10.0), 3.0);
+        assert_eq!(fold(25.0, 0.0, 10.0), 5.0);
+        assert_eq!(fold(-25.0, 0.0, 10.0), 5.0);
+        assert_eq!(fold(7.0, 5.0, 5.0), 5.0);
+    }
+
+    fn square() -> Region {
+        Region::Rect {
+            left: 0.0,
+            top: 0.0,
+            right: 100.0,
+            bottom: 100.0,
+        }
+    }
+
+    #[test]
+    fn steps_reflect_off_edges() {
+        assert_eq!(
+            square().step((95, 50), (10, 0), BoundsBehavior::Reflect),
+            (95, 50)
+        );
+        assert_eq!(
+            square().step((10, 10), (-30, 250), BoundsBehavior::Reflect),
+            (20, 60)
+        );
+        let circle = Region::Circle {
+            cx: 0.0,
+            cy: 0.0,
+            radius: 10.0,
+        };
+        assert_eq!(circle.step((8, 0), (5, 0), BoundsBehavior::Reflect), (7, 0));
+        assert_eq!(
+            circle.step((0, 0), (0, -3), BoundsBehavior::Reflect),
+            (0, -3)
+        );
+    }
+
+    #[test]
+    fn mean_reversion_pulls_to_center() {
+        let half = BoundsBehavior::MeanReversion { strength: 0.5 };
+        assert_eq!(square().step((0, 0), (10, 10), half), (30, 30));
+        let over = BoundsBehavior::MeanReversion { strength: 2.0 };
+        assert_eq!(square().step((0, 0), (10, 10), over), (50, 50));
+        // Pulled inside or not, the result is clamped to the area
+        let none = BoundsBehavior::MeanReversion { strength: 0.0 };
+        assert_eq!(square().step((90, 90), (50, 0), none), (100, 90));
+    }
+}
@@ -0,0 +1,872 @@
+use crate::backend::{create_enigo, InputBackend};
+use crate::clicker::{ClickerConfig, ClickerWorker};
+use crate::coords::Layout;
+use crate::drag::DragWorker;
+use crate::idle::{IdleTracker, YieldGate};
+use crate::macros::{Key, MacroLibrary};
+use crate::mouse_mover::{MouseMoverConfig, MouseMoverWorker};
+use crate::player::PlayerWorker;
+use crate::scheduler::{DeadlineScheduler, JitterStats};
+use crate::seed::{session_rng, SessionRng};
+use crate::steps::{self, Program};
+use crate::typing::{self, TypingWorker};
+use crate::windows::{WindowTarget, WindowTracker};
+use device_query::DeviceState;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What one stage of a chain runs. The clicker and mover use their panel
+/// settings; typing and drags use the Type Text and Drag panels' settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ChainAction {
+    Clicker,
+    Mover,
+    Macro { name: String },
+    Type { text: String },
+    Drag,
+    Wait,
+}
+
+impl ChainAction {
+    pub const ALL: [ChainAction; 6] = [
+        ChainAction::Clicker,
+        ChainAction::Mover,
+        ChainAction::Macro {
+            name: String::new(),
+        },
+        ChainAction::Type {
+            text: String::new(),
+        },
+        ChainAction::Drag,
+        ChainAction::Wait,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChainAction::Clicker => "Clicker",
+            ChainAction::Mover => "Mover",
+            ChainAction::Macro { .. } => "Macro",
+            ChainAction::Type { .. } => "Type",
+            ChainAction::Drag => "Drag",
+            ChainAction::Wait => "Wait",
+        }
+    }
+
+    /// What `StopAfter::count` counts for this action.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            ChainAction::Clicker => "clicks",
+            ChainAction::Mover => "moves",
+            ChainAction::Macro { .. } => "plays",
+            ChainAction::Type { .. } | ChainAction::Drag => "runs",
+            ChainAction::Wait => "",
+        }
+    }
+}
+
+/// When a stage ends: after `count` clicks, moves or runs, or after `secs`,
+/// whichever comes first.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StopAfter {
+    pub count: Option<u64>,
+    pub secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainStage {
+    pub action: ChainAction,
+    #[serde(default)]
+    pub stop: StopAfter,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chain {
+    pub name: String,
+    pub stages: Vec<ChainStage>,
+    /// Times to run through the stages. `None` repeats until stopped.
+    pub rounds: Option<u32>,
+}
+
+/// The settings a chain runs its stages with, captured when it starts.
+#[derive(Debug, Clone, Default)]
+pub struct ChainContext {
+    pub clicker: ClickerConfig,
+    pub mover: MouseMoverConfig,
+    pub macros: MacroLibrary,
+}
+
+impl Chain {
+    /// Checks every stage can run and has a way to end, naming the first
+    /// that does not.
+    pub fn validate(&self, context: &ChainContext) -> Result<(), String> {
+        if self.stages.is_empty() {
+            return Err(format!("chain \"{}\" has no stages", self.name));
+        }
+        if self.rounds == Some(0) {
+            return Err("a chain must run at least 1 round".to_string());
+        }
+        for (i, stage) in self.stages.iter().enumerate() {
+            let fail =
+                |message: String| format!("stage {} ({}): {}", i + 1, stage.action.name(), message);
+            match stage.stop {
+                StopAfter { count: Some(0), .. } | StopAfter { secs: Some(0), .. } => {
+                    return Err(fail("stop limits must be above 0".into()));
+                }
+                StopAfter {
+                    count: None,
+                    secs: None,
+                } => return Err(fail("needs a count or a time to stop after".into())),
+                StopAfter { count: Some(_), .. } if stage.action == ChainAction::Wait => {
+                    return Err(fail("a wait can only stop after a time".into()));
+                }
+                _ => {}
+            }
+            match &stage.action {
+                ChainAction::Macro { name } => {
+                    let recording = context
+                        .macros
+                        .get(name)
+                        .ok_or_else(|| fail(format!("no macro named {:?}", name)))?;
+                    Program::compile(&recording.steps).map_err(|e| fail(e.to_string()))?;
+                    // Plays are counted as they finish, which never happens
+                    if stage.stop.secs.is_none() && steps::never_ends(&recording.steps) {
+                        return Err(fail(format!(
+                            "macro {:?} loops forever, so the stage needs a time to stop after",
+                            name
+                        )));
+                    }
+                }
+                ChainAction::Type { text } if text.is_empty() => {
+                    return Err(fail("there is no text to type".into()));
+                }
+                ChainAction::Type { text } => {
+                    typing::parse(text).map_err(fail)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChainProgress {
+    /// Zero-based index of the running stage.
+    pub stage: usize,
+    pub stages: usize,
+    pub action: &'static str,
+    pub unit: &'static str,
+    pub stop: StopAfter,
+    /// Clicks, moves or runs so far in this stage.
+    pub count: u64,
+    pub elapsed: Duration,
+    /// Zero-based index of the current round.
+    pub round: u32,
+    pub rounds: Option<u32>,
+}
+
+pub struct ChainTick {
+    pub delay: Duration,
+    pub finished: bool,
+}
+
+/// The worker running the current stage, which holds the backend while it
+/// runs. Between stages the backend sits in `Idle`. Workers are boxed as
+/// they differ a lot in size.
+enum Active<B: InputBackend> {
+    Idle(B),
+    Clicker(Box<ClickerWorker<B>>),
+    Mover(Box<MouseMoverWorker<B>>),
+    Macro(Box<PlayerWorker<B>>),
+    Type(Box<TypingWorker<B>>),
+    Drag(Box<DragWorker<B>>),
+}
+
+impl<B: InputBackend> Active<B> {
+    fn backend_mut(&mut self) -> &mut B {
+        match self {
+            Active::Idle(backend) => backend,
+            Active::Clicker(worker) => &mut worker.backend,
+            Active::Mover(worker) => &mut worker.backend,
+            Active::Macro(worker) => &mut worker.backend,
+            Active::Type(worker) => &mut worker.backend,
+            Active::Drag(worker) => &mut worker.backend,
+        }
+    }
+
+    /// Cleans up after the running worker and takes the backend back.
+    fn finish(self) -> B {
+        match self {
+            Active::Idle(backend) => backend,
+            Active::Clicker(mut worker) => {
+                worker.finish();
+                worker.backend
+            }
+            Active::Mover(mut worker) => {
+                worker.finish();
+                worker.backend
+            }
+            Active::Macro(mut worker) => {
+                worker.finish();
+                worker.backend
+            }
+            Active::Type(worker) => worker.backend,
+            Active::Drag(mut worker) => {
+                worker.finish();
+                worker.backend
+            }
+        }
+    }
+}
+
+/// Runs the stages of a chain in turn, one worker tick at a time, so a chain
+/// can be driven by the runner thread or stepped through in a dry run.
+pub struct ChainWorker<B: InputBackend> {
+    pub chain: Chain,
+    pub seed: u64,
+    context: ChainContext,
+    rng: SessionRng,
+    /// Handed to the mover so a glide stops part way when the chain does.
+    running: Arc<AtomicBool>,
+    desktop: bool,
+    active: Option<Active<B>>,
+    /// Holds clicker and mover stages back while the user is busy, as their
+    /// panels do.
+    yield_gate: Option<YieldGate>,
+    stage: usize,
+    round: u32,
+    count: u64,
+    elapsed: Duration,
+    /// The stage and round the last tick ran, for `add_busy_time`.
+    ticked: Option<(usize, u32)>,
+}
+
+impl<B: InputBackend> ChainWorker<B> {
+    /// Longest pause of a Wait stage per tick, so stopping a chain does not
+    /// wait out the whole stage.
+    const MAX_WAIT_SLICE: Duration = Duration::from_millis(100);
+
+    pub fn new(
+        chain: Chain,
+        context: ChainContext,
+        seed: Option<u64>,
+        backend: B,
+        running: Arc<AtomicBool>,
+    ) -> Self {
+        let (rng, seed) = session_rng(seed);
+        Self {
+            chain,
+            seed,
+            context,
+            rng,
+            running,
+            desktop: false,
+            active: Some(Active::Idle(backend)),
+            yield_gate: None,
+            stage: 0,
+            round: 0,
+            count: 0,
+            elapsed: Duration::ZERO,
+            ticked: None,
+        }
+    }
+
+    /// Lets stages watch the real desktop like the standalone panels do:
+    /// macros read the keyboard for their key steps, the clicker and mover
+    /// follow their target window and yield to the user, and the jiggle
+    /// waits for the user to be idle.
+    pub fn with_desktop(mut self) -> Self {
+        self.desktop = true;
+        self
+    }
+
+    /// Tracks the target window for window-relative points and bounds.
+    fn window_tracker(&self, needed: bool, target: &WindowTarget) -> Option<WindowTracker> {
+        if !self.desktop || !needed {
+            return None;
+        }
+        let tracker = WindowTracker::connect(target.clone());
+        if tracker.is_none() {
+            eprintln!("Failed to open the display to track the target window");
+        }
+        tracker
+    }
+
+    pub fn backend_mut(&mut self) -> &mut B {
+        self.active
+            .as_mut()
+            .expect("a worker is always active between ticks")
+            .backend_mut()
+    }
+
+    pub fn progress(&self) -> ChainProgress {
+        let stage = self.chain.stages.get(self.stage);
+        ChainProgress {
+            stage: self.stage,
+            stages: self.chain.stages.len(),
+            action: stage.map_or("", |stage| stage.action.name()),
+            unit: stage.map_or("", |stage| stage.action.unit()),
+            stop: stage.map(|stage| stage.stop).unwrap_or_default(),
+            count: self.count,
+            elapsed: self.elapsed,
+            round: self.round,
+            rounds: self.chain.rounds,
+        }
+    }
+
+    fn rounds_done(&self) -> bool {
+        self.chain.rounds.is_some_and(|rounds| self.round >= rounds)
+    }
+
+    fn stage_done(&self, stop: StopAfter) -> bool {
+        stop.count.is_some_and(|count| self.count >= count)
+            || stop
+                .secs
+                .is_some_and(|secs| self.elapsed >= Duration::from_secs(secs))
+    }
+
+    /// Starts a run of the current stage's action on `backend`, or gives the
+    /// backend back with the reason it cannot.
+    fn launch(&mut self, backend: B) -> Result<Active<B>, (B, String)> {
+        let seed = Some(self.rng.random());
+        let action = self.chain.stages[self.stage].action.clone();
+        Ok(match action {
+            ChainAction::Clicker => {
+                let config = ClickerConfig {
+                    seed,
+                    ..self.context.clicker.clone()
+                };
+                let tracker = self.window_tracker(config.needs_window(), &config.window);
+                if self.desktop {
+                    self.yield_gate = Some(YieldGate::new(
+                        config.yield_to_user,
+                        Arc::new(AtomicBool::new(false)),
+                    ));
+                }
+                let mut worker = ClickerWorker::new(config, backend);
+                if let Some(tracker) = tracker {
+                    worker = worker.with_window_tracker(tracker);
+                }
+                Active::Clicker(Box::new(worker))
+            }
+            ChainAction::Mover => {
+                let config = MouseMoverConfig {
+                    seed,
+                    ..self.context.mover.clone()
+                };
+                let tracker = self.window_tracker(config.needs_window(), &config.window);
+                if self.desktop {
+                    self.yield_gate = Some(YieldGate::new(
+                        config.yield_to_user,
+                        Arc::new(AtomicBool::new(false)),
+                    ));
+                }
+                let running = Arc::clone(&self.running);
+                let mut worker = MouseMoverWorker::new(config, backend, running);
+                if self.desktop {
+                    worker = worker.with_idle_tracker(IdleTracker::new());
+                }
+                if let Some(tracker) = tracker {
+                    worker = worker.with_window_tracker(tracker);
+                }
+                Active::Mover(Box::new(worker))
+            }
+            ChainAction::Macro { name } => {
+                let program = match self.context.macros.get(&name) {
+                    Some(recording) => Program::compile(&recording.steps),
+                    None => return Err((backend, format!("no macro named {:?}", name))),
+                };
+                let program = match program {
+                    Ok(program) => program,
+                    Err(e) => return Err((backend, e.to_string())),
+                };
+                let mut settings = self.context.macros.playback.clone();
+                settings.loops = Some(1);
+                settings.seed = seed;
+                let mut worker = PlayerWorker::new(name, program, settings, backend);
+                if self.desktop {
+                    worker = worker.with_key_state(DeviceState::new());
+                }
+                Active::Macro(Box::new(worker))
+            }
+            ChainAction::Type { text } => {
+                if let Err(e) = typing::parse(&text) {
+                    return Err((backend, e));
+                }
+                let mut settings = self.context.macros.typing.clone();
+                settings.text = text;
+                settings.seed = seed;
+                let worker = TypingWorker::new(&settings, backend);
+                Active::Type(Box::new(worker.expect("text was checked above")))
+            }
+            ChainAction::Drag => {
+                let mut settings = self.context.macros.drag.clone();
+                settings.seed = seed;
+                if let Err(e) = settings.resolve(&Layout::capture(&backend)) {
+                    return Err((backend, e));
+                }
+                let worker = DragWorker::new(&settings, backend);
+                Active::Drag(Box::new(worker.expect("points were checked above")))
+            }
+            ChainAction::Wait => Active::Idle(backend),
+        })
+    }
+
+    /// Moves on to the next stage, and the next round after the last.
+    fn advance(&mut self) {
+        self.stage += 1;
+        if self.stage >= self.chain.stages.len() {
+            self.stage = 0;
+            self.round += 1;
+        }
+        self.count = 0;
+        self.elapsed = Duration::ZERO;
+        self.yield_gate = None;
+    }
+
+    /// Runs one tick of the current stage and returns how long to pause
+    /// before the next.
+    pub fn step(&mut self) -> ChainTick {
+        // Every stage that cannot start is skipped, so a full pass of them
+        // means nothing in the chain can run
+        for _ in 0..=self.chain.stages.len() {
+            if self.rounds_done() || self.chain.stages.is_empty() {
+                return ChainTick {
+                    delay: Duration::ZERO,
+                    finished: true,
+                };
+            }
+            let stage = self.chain.stages[self.stage].clone();
+            if self.stage_done(stage.stop) {
+                let backend = self.active.take().expect("worker is active").finish();
+                self.active = Some(Active::Idle(backend));
+                self.advance();
+                continue;
+            }
+
+            let mut active = self.active.take().expect("worker is active");
+            if matches!(active, Active::Idle(_)) && stage.action != ChainAction::Wait {
+                active = match self.launch(active.finish()) {
+                    Ok(active) => active,
+                    Err((backend, e)) => {
+                        eprintln!(
+                            "Skipping chain stage {} ({}): {}",
+                            self.stage + 1,
+                            stage.action.name(),
+                            e
+                        );
+                        self.active = Some(Active::Idle(backend));
+                        self.advance();
+                        continue;
+                    }
+                };
+            }
+
+            let waiting = match &mut active {
+                Active::Clicker(_) | Active::Mover(_)
+                    if self
+                        .yield_gate
+                        .as_mut()
+                        .is_some_and(YieldGate::should_yield) =>
+                {
+                    true
+                }
+                Active::Clicker(worker) => !worker.window_ready(),
+                Active::Mover(worker) => !worker.window_ready(),
+                _ => false,
+            };
+            // A run that finishes counts once; the next tick starts another
+            let (delay, counted, finished) = match &mut active {
+                _ if waiting => (YieldGate::POLL, false, false),
+                Active::Idle(_) => {
+                    let remaining = stage
+                        .stop
+                        .secs
+                        .map(|secs| Duration::from_secs(secs).saturating_sub(self.elapsed))
+                        .unwrap_or_default();
+                    (remaining, false, false)
+                }
+                Active::Clicker(worker) => (worker.step(), true, false),
+                Active::Mover(worker) => {
+                    let tick = worker.step();
+                    (tick.delay, tick.moved, tick.finished)
+                }
+                Active::Macro(worker) => {
+                    let tick = worker.step();
+                    (tick.delay, tick.finished, tick.finished)
+                }
+                Active::Type(worker) => {
+                    let tick = worker.step();
+                    (tick.delay, tick.finished, tick.finished)
+                }
+                Active::Drag(worker) => {
+                    let tick = worker.step();
+                    (tick.delay, tick.finished, tick.finished)
+                }
+            };
+            if counted {
+                self.count += 1;
+            }
+            let mut delay = delay;
+            if let Some(secs) = stage.stop.secs {
+                delay = delay.min(Duration::from_secs(secs).saturating_sub(self.elapsed));
+            }
+            if stage.action == ChainAction::Wait {
+                delay = delay.min(Self::MAX_WAIT_SLICE);
+            }
+            self.elapsed += delay;
+            self.ticked = Some((self.stage, self.round));
+
+            // A mover that has run its course, e.g. a one-shot waypoint
+            // path, ends its stage early
+            let mover_done = finished && matches!(active, Active::Mover(_));
+            if finished {
+                active = Active::Idle(active.finish());
+            }
+            self.active = Some(active);
+            if mover_done {
+                self.advance();
+            }
+            return ChainTick {
+                delay,
+                finished: false,
+            };
+        }
+        ChainTick {
+            delay: Duration::ZERO,
+            finished: true,
+        }
+    }
+
+    /// Counts time spent inside the last tick, such as a mover's glide,
+    /// towards the stage it ran. The runner measures this by wall clock; dry
+    /// runs leave it out so a seed always ends its stages at the same tick.
+    pub fn add_busy_time(&mut self, busy: Duration) {
+        if self.ticked == Some((self.stage, self.round)) {
+            self.elapsed += busy;
+        }
+    }
+
+    /// Stops whatever stage is running, releasing anything it holds.
+    pub fn finish(&mut self) {
+        if let Some(active) = self.active.take() {
+            self.active = Some(Active::Idle(active.finish()));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainSettings {
+    /// Name of the chain to run.
+    pub selected: String,
+    pub seed: Option<u64>,
+    /// Key that starts and stops the selected chain. Unbound until the user
+    /// picks one.
+    pub hotkey: Option<Key>,
+}
+
+/// Everything stored in `chains.ron` next to the executable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainLibrary {
+    pub settings: ChainSettings,
+    pub chains: Vec<Chain>,
+}
+
+impl ChainLibrary {
+    fn path() -> PathBuf {
+        env::current_exe()
+            .expect("Failed to get executable path")
+            .parent()
+            .expect("Failed to get executable directory")
+            .join("chains.ron")
+    }
+
+    pub fn load() -> Self {
+        let Ok(text) = fs::read_to_string(Self::path()) else {
+            return Self::default();
+        };
+        ron::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Failed to parse chains: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Ok(text) = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            if let Err(e) = fs::write(Self::path(), text) {
+                eprintln!("Failed to save chains: {}", e);
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Chain> {
+        self.chains.iter().find(|chain| chain.name == name)
+    }
+}
+
+#[derive(Debug)]
+pub struct ChainRunner {
+    is_running: Arc<AtomicBool>,
+    progress: Arc<Mutex<Option<ChainProgress>>>,
+    jitter_stats: Arc<Mutex<JitterStats>>,
+    last_seed: Arc<AtomicU64>,
+}
+
+impl Default for ChainRunner {
+    fn default() -> Self {
+        Self {
+            is_running: Arc::new(AtomicBool::new(false)),
+            progress: Arc::new(Mutex::new(None)),
+            jitter_stats: Arc::new(Mutex::new(JitterStats::default())),
+            last_seed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl ChainRunner {
+    /// Validates the chain and starts running it, unless already running.
+    pub fn start_running(
+        &mut self,
+        chain: Chain,
+        context: ChainContext,
+        seed: Option<u64>,
+    ) -> Result<(), String> {
+        chain.validate(&context)?;
+        if !self.is_running.load(Ordering::SeqCst) {
+            self.is_running.store(true, Ordering::SeqCst);
+            let is_running = Arc::clone(&self.is_running);
+            let progress = Arc::clone(&self.progress);
+            let jitter_stats = Arc::clone(&self.jitter_stats);
+            let last_seed = Arc::clone(&self.last_seed);
+
+            thread::spawn(move || {
+                let running = Arc::clone(&is_running);
+                let mut worker =
+                    ChainWorker::new(chain, context, seed, create_enigo(), running).with_desktop();
+                last_seed.store(worker.seed, Ordering::SeqCst);
+                println!(
+                    "Running chain \"{}\" with seed {}",
+                    worker.chain.name, worker.seed
+                );
+                let mut scheduler = DeadlineScheduler::new(jitter_stats);
+
+                while is_running.load(Ordering::SeqCst) {
+                    let started = Instant::now();
+                    let tick = worker.step();
+                    worker.add_busy_time(started.elapsed());
+                    *progress.lock().unwrap() = Some(worker.progress());
+                    if tick.finished {
+                        is_running.store(false, Ordering::SeqCst);
+                        break;
+                    }
+                    scheduler.wait(tick.delay);
+                }
+                worker.finish();
+                *progress.lock().unwrap() = None;
+            });
+        }
+        Ok(())
+    }
+
+    pub fn stop_running(&mut self) {
+        self.is_running.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.is_running.load(Ordering::SeqCst)
+    }
+
+    /// The stage running now, or `None` between runs.
+    pub fn get_progress(&self) -> Option<ChainProgress> {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn get_last_seed(&self) -> u64 {
+        self.last_seed.load(Ordering::SeqCst)
+    }
+
+    pub fn get_jitter_stats(&self) -> JitterStats {
+        *self.jitter_stats.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Action, MockBackend};
+    use crate::macros::Macro;
+    use crate::mouse_button::SerializableMouseButton;
+    use crate::steps::Step;
+    use enigo::{Button, Direction};
+
+    fn stage(action: ChainAction, count: Option<u64>, secs: Option<u64>) -> ChainStage {
+        ChainStage {
+            action,
+            stop: StopAfter { count, secs },
+        }
+    }
+
+    fn chain(stages: Vec<ChainStage>, rounds: Option<u32>) -> Chain {
+        Chain {
+            name: "test".into(),
+            stages,
+            rounds,
+        }
+    }
+
+    fn context(macros: Vec<Macro>) -> ChainContext {
+        let mut context = ChainContext::default();
+        context.macros.macros = macros;
+        context
+    }
+
+    fn right_click() -> Macro {
+        Macro {
+            name: "right".into(),
+            steps: vec![Step::Click {
+                button: SerializableMouseButton::Right,
+            }],
+        }
+    }
+
+    /// Runs the chain to the end and returns the pause after each tick.
+    fn run(chain: Chain, context: ChainContext) -> (Vec<Duration>, Vec<Action>) {
+        let running = Arc::new(AtomicBool::new(true));
+        let mut worker = ChainWorker::new(chain, context, Some(3), MockBackend::default(), running);
+        let mut delays = Vec::new();
+        for _ in 0..100_000 {
+            let tick = worker.step();
+            if tick.finished {
+                worker.finish();
+                return (delays, std::mem::take(&mut worker.backend_mut().actions));
+            }
+            delays.push(tick.delay);
+        }
+        panic!("chain did not finish");
+    }
+
+    fn clicks(actions: &[Action]) -> Vec<Button> {
+        actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Button(button, Direction::Click) => Some(*button),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stages_advance_after_their_count() {
+        let stages = vec![
+            stage(ChainAction::Clicker, Some(3), None),
+            stage(
+                ChainAction::Macro {
+                    name: "right".into(),
+                },
+                Some(2),
+                None,
+            ),
+        ];
+        let (_, actions) = run(chain(stages, Some(1)), context(vec![right_click()]));
+        assert_eq!(
+            clicks(&actions),
+            [
+                Button::Left,
+                Button::Left,
+                Button::Left,
+                Button::Right,
+                Button::Right
+            ]
+        );
+    }
+
+    #[test]
+    fn stages_stop_after_their_time() {
+        let stages = vec![
+            stage(ChainAction::Wait, None, Some(1)),
+            // The clicker's default interval is a second
+            stage(ChainAction::Clicker, Some(100), Some(5)),
+        ];
+        let (delays, actions) = run(chain(stages, Some(1)), ChainContext::default());
+        let slice = ChainWorker::<MockBackend>::MAX_WAIT_SLICE;
+        assert_eq!(delays[..10], [slice; 10]);
+        assert_eq!(delays[10..], [Duration::from_secs(1); 5]);
+        assert_eq!(clicks(&actions).len(), 5);
+    }
+
+    #[test]
+    fn rounds_repeat_the_stages() {
+        let stages = vec![stage(ChainAction::Clicker, Some(2), None)];
+        let (_, actions) = run(chain(stages, Some(3)), ChainContext::default());
+        assert_eq!(clicks(&actions).len(), 6);
+    }
+
+    #[test]
+    fn stages_that_cannot_start_are_skipped() {
+        let stages = vec![
+            stage(
+                ChainAction::Macro {
+                    name: "missing".into(),
+                },
+                Some(1),
+                None,
+            ),
+            stage(ChainAction::Clicker, Some(1), None),
+        ];
+        let (_, actions) = run(chain(stages, Some(2)), ChainContext::default());
+        assert_eq!(clicks(&actions), [Button::Left, Button::Left]);
+    }
+
+    #[test]
+    fn same_seed_repeats_the_run() {
+        let mut context = context(vec![right_click()]);
+        context.clicker.random_delay_enabled = true;
+        let stages = vec![
+            stage(ChainAction::Clicker, None, Some(3)),
+            stage(ChainAction::Mover, Some(20), Some(60)),
+            stage(
+                ChainAction::Macro {
+                    name: "right".into(),
+                },
+                Some(1),
+                None,
+            ),
+        ];
+        let chain = chain(stages, Some(2));
+        assert_eq!(run(chain.clone(), context.clone()), run(chain, context));
+    }
+
+    #[test]
+    fn macro_stages_need_a_way_to_end() {
+        let endless = Macro {
+            name: "endless".into(),
+            steps: vec![Step::Loop {
+                label: "a".into(),
+                count: None,
+                steps: vec![Step::Wait { ms: 10 }],
+            }],
+        };
+        let context = context(vec![endless]);
+        let action = ChainAction::Macro {
+            name: "endless".into(),
+        };
+        let counted = chain(vec![stage(action.clone(), Some(1), None)], Some(1));
+        assert!(counted
+            .validate(&context)
+            .unwrap_err()
+            .contains("loops forever"));
+        let timed = chain(vec![stage(action, Some(1), Some(10))], Some(1));
+        timed.validate(&context).unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+use crate::app::MourseApp;
+use crate::backend::{create_enigo, MockBackend};
+use crate::chain::{ChainContext, ChainLibrary, ChainWorker};
+use crate::clicker::ClickerWorker;
+use crate::coords::Layout;
+use crate::drag::DragWorker;
+use crate::macros::{Macro, MacroLibrary};
+use crate::mouse_mover::MouseMoverWorker;
+use crate::player::PlayerWorker;
+use crate::script::{ScriptLibrary, ScriptWorker};
+use crate::steps::Program;
+use crate::typing::TypingWorker;
+use crate::xdotool;
+use enigo::{Enigo, Settings};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+const USAGE: &str =
+    "Usage: mourse --dry-run <clicker|mover|macro|script|type|drag|chain> [--seed <n>] [--steps <n>] \
+                     [--macro <name>] [--script <name>] [--text <text>] \
+                     [--chain <name>]
+       mourse --type <text> [--delay <min>[-<max>]] [--typos <percent>] \
+                     [--start-delay <ms>] [--seed <n>]
+       mourse --run-script <name|file.rhai> [--seed <n>]
+       mourse --import-xdotool <file.sh> [--name <name>]
+       mourse --export-xdotool <macro> [<file.sh>]
+       mourse --check-xdotool <macro|file.sh>";
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        Some("--dry-run") => dry_run(&args[1..]),
+        Some("--run-script") => run_script(&args[1..]),
+        Some("--type") => type_text(&args[1..]),
+        Some("--import-xdotool") => import_xdotool(&args[1..]),
+        Some("--export-xdotool") => export_xdotool(&args[1..]),
+        Some("--check-xdotool") => check_xdotool(&args[1..]),
+        Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// Replays a session against the mock backend using the saved config, so the
+/// action sequence for a given seed can be inspected without touching the desktop.
+fn dry_run(args: &[String]) -> Result<(), String> {
+    let target = args.first().ok_or(USAGE)?;
+    let mut seed = None;
+    let mut steps = 20;
+    let mut macro_name = None;
+    let mut script_name = None;
+    let mut text = None;
+    let mut chain_name = None;
+    let mut rest = args[1..].iter();
+    while let Some(flag) = rest.next() {
+        let value = rest.next().ok_or(USAGE)?;
+        match flag.as_str() {
+            "--seed" => seed = Some(parse(value)?),
+            "--steps" => steps = parse(value)?,
+            "--macro" => macro_name = Some(value.clone()),
+            "--script" => script_name = Some(value.clone()),
+            "--text" => text = Some(value.clone()),
+            "--chain" => chain_name = Some(value.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let (mut clicker_config, mut mover_config) = MourseApp::read_config().unwrap_or_default();
+    match target.as_str() {
+        "clicker" => {
+            clicker_config.seed = seed.or(clicker_config.seed);
+            let mut worker = ClickerWorker::new(clicker_config, MockBackend::default());
+            println!("seed {}", worker.seed);
+            print_steps(steps, || {
+                let delay = worker.step();
+                Some((std::mem::take(&mut worker.backend.actions), delay))
+            });
+            worker.finish();
+            println!("stop: {:?}", worker.backend.actions);
+        }
+        "mover" => {
+            mover_config.seed = seed.or(mover_config.seed);
+            let running = Arc::new(AtomicBool::new(true));
+            let mut worker = MouseMoverWorker::new(mover_config, MockBackend::default(), running);
+            println!("seed {}", worker.seed);
+            print_steps(steps, || {
+                let tick = worker.step();
+                (!tick.finished).then(|| (std::mem::take(&mut worker.backend.actions), tick.delay))
+            });
+            worker.finish();
+            println!("stop: {:?}", worker.backend.actions);
+        }
+        "macro" => {
+            let library = MacroLibrary::load();
+            let mut settings = library.playback.clone();
+            settings.seed = seed.or(settings.seed);
+            let name = macro_name.unwrap_or_else(|| settings.selected.clone());
+            let recording = library
+                .get(&name)
+                .ok_or_else(|| format!("No macro named {:?}", name))?;
+            let program = Program::compile(&recording.steps).map_err(|e| e.to_string())?;
+            settings.validate(&recording.steps)?;
+            let mut worker = PlayerWorker::new(name, program, settings, MockBackend::default());
+            println!("seed {}", worker.seed);
+            print_steps(steps, || {
+                let tick = worker.step();
+                (!tick.finished).then(|| (std::mem::take(&mut worker.backend.actions), tick.delay))
+            });
+            worker.finish();
+            println!("stop: {:?}", worker.backend.actions);
+        }
+        "script" => {
+            let library = ScriptLibrary::load();
+            let mut settings = library.settings.clone();
+            settings.seed = seed.or(settings.seed);
+            let source =
+                library.source(&script_name.unwrap_or_else(|| settings.selected.clone()))?;
+            let mut worker = ScriptWorker::new(settings, MockBackend::default());
+            println!("seed {}", worker.seed);
+            let result = worker.run(&source, Arc::new(AtomicBool::new(true)), |line| {
+                println!("print: {}", line)
+            });
+            for action in &worker.backend.borrow().actions {
+                println!("{:?}", action);
+            }
+            result?;
+            println!("finished");
+        }
+        "drag" => {
+            let mut settings = MacroLibrary::load().drag;
+            settings.seed = seed.or(settings.seed);
+            let mut worker = DragWorker::new(&settings, MockBackend::default())?;
+            println!("seed {}", worker.seed);
+            print_steps(steps, || {
+                let tick = worker.step();
+                (!tick.finished).then(|| (std::mem::take(&mut worker.backend.actions), tick.delay))
+            });
+            worker.finish();
+            println!("stop: {:?}", worker.backend.actions);
+        }
+        "chain" => {
+            let library = ChainLibrary::load();
+            let name = chain_name.unwrap_or_else(|| library.settings.selected.clone());
+            let chain = library
+                .get(&name)
+                .ok_or_else(|| format!("No chain named {:?}", name))?
+                .clone();
+            let context = ChainContext {
+                clicker: clicker_config,
+                mover: mover_config,
+                macros: MacroLibrary::load(),
+            };
+            chain.validate(&context)?;
+            let running = Arc::new(AtomicBool::new(true));
+            let seed = seed.or(library.settings.seed);
+            let mut worker =
+                ChainWorker::new(chain, context, seed, MockBackend::default(), running);
+            println!("seed {}", worker.seed);
+            let mut stage = None;
+            print_steps(steps, || {
+                let tick = worker.step();
+                let progress = worker.progress();
+                if stage != Some((progress.round, progress.stage)) && !tick.finished {
+                    stage = Some((progress.round, progress.stage));
+                    println!(
+                        "round {} stage {} ({})",
+                        progress.round + 1,
+                        progress.stage + 1,
+                        progress.action
+                    );
+                }
+                let actions = std::mem::take(&mut worker.backend_mut().actions);
+                (!tick.finished).then_some((actions, tick.delay))
+            });
+            worker.finish();
+            println!("stop: {:?}", worker.backend_mut().actions);
+        }
+        "type" => {
+            let mut settings = MacroLibrary::load().typing;
+            settings.seed = seed.or(settings.seed);
+            settings.text = text.unwrap_or(settings.text);
+            let mut worker = TypingWorker::new(&settings, MockBackend::default())?;
+            println!("seed {}", worker.seed);
+            print_steps(steps, || {
+                let tick = worker.step();
+                (!tick.finished).then(|| (std::mem::take(&mut worker.backend.actions), tick.delay))
+            });
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
+
+/// Runs a script against the real desktop, for starting automations from a
+/// shell or a launcher without the GUI.
+fn run_script(args: &[String]) -> Result<(), String> {
+    let name = args.first().ok_or(USAGE)?;
+    let library = ScriptLibrary::load();
+    let mut settings = library.settings.clone();
+    match args.get(1..) {
+        Some([]) | None => {}
+        Some([flag, value]) if flag == "--seed" => settings.seed = Some(parse(value)?),
+        _ => return Err(USAGE.to_string()),
+    }
+    let source = library.source(name)?;
+    let mut worker = ScriptWorker::new(settings, create_enigo());
+    println!("seed {}", worker.seed);
+    worker.run(&source, Arc::new(AtomicBool::new(true)), |line| {
+        println!("{}", line)
+    })
+}
+
+/// Types text into whatever has focus once the start delay is up, using the
+/// Type Text panel's saved settings for anything not given.
+fn type_text(args: &[String]) -> Result<(), String> {
+    let mut settings = MacroLibrary::load().typing;
+    settings.text = args.first().ok_or(USAGE)?.clone();
+    let mut rest = args[1..].iter();
+    while let Some(flag) = rest.next() {
+        let value = rest.next().ok_or(USAGE)?;
+        match flag.as_str() {
+            "--delay" => {
+                let (min, max) = value.split_once('-').unwrap_or((value, value));
+                settings.style.min_char_delay_ms = parse(min)?;
+                settings.style.max_char_delay_ms = parse(max)?;
+                if settings.style.min_char_delay_ms > settings.style.max_char_delay_ms {
+                    return Err(format!("Invalid delay range: {}", value));
+                }
+            }
+            "--typos" => settings.style.typo_percent = parse(value)?,
+            "--start-delay" => settings.start_delay_ms = parse(value)?,
+            "--seed" => settings.seed = Some(parse(value)?),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let mut worker = TypingWorker::new(&settings, create_enigo())?;
+    println!("seed {}", worker.seed);
+    std::thread::sleep(Duration::from_millis(settings.start_delay_ms));
+    loop {
+        let tick = worker.step();
+        if tick.finished {
+            return Ok(());
+        }
+        std::thread::sleep(tick.delay);
+    }
+}
+
+fn import_xdotool(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or(USAGE)?;
+    let name = match &args[1..] {
+        [] => Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.clone()),
+        [flag, name] if flag == "--name" => name.clone(),
+        _ => return Err(USAGE.to_string()),
+    };
+    let script = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let steps = xdotool::import(&script)?;
+    println!("Imported {} steps as \"{}\"", steps.len(), name);
+    let mut library = MacroLibrary::load();
+    library.insert(Macro { name, steps });
+    library.save();
+    Ok(())
+}
+
+fn export_xdotool(args: &[String]) -> Result<(), String> {
+    let (name, path) = match args {
+        [name] => (name, None),
+        [name, path] => (name, Some(path)),
+        _ => return Err(USAGE.to_string()),
+    };
+    let library = MacroLibrary::load();
+    let recording = library
+        .get(name)
+        .ok_or_else(|| format!("No macro named {:?}", name))?;
+    // Monitor-relative points need the real screen layout when there is one
+    let layout = match Enigo::new(&Settings::default()) {
+        Ok(enigo) => Layout::capture(&enigo),
+        Err(e) => {
+            eprintln!("No display ({}), assuming a single 1920x1080 screen", e);
+            Layout::capture(&MockBackend::default())
+        }
+    };
+    let script = xdotool::export(name, &recording.steps, &layout).map_err(|e| e.to_string())?;
+    match path {
+        Some(path) => fs::write(path, script).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            print!("{}", script);
+            Ok(())
+        }
+    }
+}
+
+/// Exports a macro (or an imported script) to xdotool, reads it back and
+/// checks both play the same actions on the mock backend.
+fn check_xdotool(args: &[String]) -> Result<(), String> {
+    let [source] = args else {
+        return Err(USAGE.to_string());
+    };
+    let library = MacroLibrary::load();
+    let steps = match library.get(source) {
+        Some(recording) => recording.steps.clone(),
+        None => {
+            let script = fs::read_to_string(source).map_err(|e| format!("{}: {}", source, e))?;
+            xdotool::import(&script)?
+        }
+    };
+    let layout = Layout::capture(&MockBackend::default());
+    let actions = xdotool::check_round_trip(source, &steps, &layout)?;
+    println!("Round trip matches over {} actions", actions);
+    Ok(())
+}
+
+fn print_steps<A: std::fmt::Debug>(steps: u64, mut step: impl FnMut() -> Option<(A, Duration)>) {
+    for i in 0..steps {
+        let Some((actions, delay)) = step() else {
+            println!("finished");
+            break;
+        };
+        println!("{:>4}: {:?} then wait {:?}", i, actions, delay);
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number: {}", value))
+}
@@ -1,19 +1,40 @@
+use crate::backend::{create_enigo, InputBackend};
+use crate::coords::{CoordSpace, Layout, ScreenPoint};
+use crate::delay::DelayDistribution;
+use crate::idle::{YieldGate, YieldSettings};
+use crate::motion::{CursorRestore, GlideSettings};
 use crate::mouse_button::SerializableMouseButton;
-use enigo::{Enigo, Mouse, Settings};
-use rand::Rng;
+use crate::scheduler::{DeadlineScheduler, JitterStats};
+use crate::seed::{session_rng, SessionRng};
+use crate::windows::{WindowState, WindowTarget, WindowTracker};
+use enigo::Coordinate;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+/// Absolute ceiling for `max_cps`, whatever the config file says.
+pub const CPS_HARD_LIMIT: f64 = 1000.0;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct ClickerConfig {
     pub click_interval_ms: u64,
     pub mouse_button: SerializableMouseButton,
     pub random_delay_enabled: bool,
     pub random_delay_min_ms: u64,
     pub random_delay_max_ms: u64,
+    pub delay_distribution: DelayDistribution,
+    pub cps_mode_enabled: bool,
+    pub clicks_per_second: f64,
+    pub max_cps: f64,
+    pub seed: Option<u64>,
+    pub yield_to_user: YieldSettings,
+    pub fixed_point: Option<ScreenPoint>,
+    pub restore_cursor: CursorRestore,
+    /// What window-relative fixed points are measured against.
+    pub window: WindowTarget,
 }
 
 impl Default for ClickerConfig {
@@ -24,6 +45,158 @@ impl Default for ClickerConfig {
             random_delay_enabled: false,
             random_delay_min_ms: 0,
             random_delay_max_ms: 500,
+            delay_distribution: DelayDistribution::Uniform,
+            cps_mode_enabled: false,
+            clicks_per_second: 20.0,
+            max_cps: 200.0,
+            seed: None,
+            yield_to_user: YieldSettings::default(),
+            fixed_point: None,
+            restore_cursor: CursorRestore::Off,
+            window: WindowTarget::Focused,
+        }
+    }
+}
+
+impl ClickerConfig {
+    pub fn needs_window(&self) -> bool {
+        self.fixed_point
+            .is_some_and(|point| point.space == CoordSpace::Window)
+    }
+
+    // A NaN or infinity from a hand-edited config falls back to the default
+    // like any other unreadable setting, rather than panicking in `clamp`
+    pub fn effective_max_cps(&self) -> f64 {
+        finite_or(self.max_cps, Self::default().max_cps).clamp(1.0, CPS_HARD_LIMIT)
+    }
+
+    pub fn effective_cps(&self) -> f64 {
+        finite_or(self.clicks_per_second, Self::default().clicks_per_second)
+            .clamp(0.1, self.effective_max_cps())
+    }
+
+    pub fn base_interval(&self) -> Duration {
+        if self.cps_mode_enabled {
+            Duration::from_secs_f64(1.0 / self.effective_cps())
+        } else {
+            Duration::from_millis(self.click_interval_ms)
+        }
+    }
+}
+
+fn finite_or(value: f64, default: f64) -> f64 {
+    if value.is_finite() {
+        value
+    } else {
+        default
+    }
+}
+
+pub struct ClickerWorker<B: InputBackend> {
+    pub config: ClickerConfig,
+    pub seed: u64,
+    pub backend: B,
+    rng: SessionRng,
+    base_interval: Duration,
+    home: Option<(i32, i32)>,
+    layout: Layout,
+    windows: Option<WindowTracker>,
+}
+
+impl<B: InputBackend> ClickerWorker<B> {
+    pub fn new(config: ClickerConfig, backend: B) -> Self {
+        let (rng, seed) = session_rng(config.seed);
+        // Only the fixed-point mode moves the cursor, so only then is there anything to restore
+        let home = config.fixed_point.and_then(|_| backend.location().ok());
+        let layout = if config.fixed_point.is_some() {
+            Layout::capture(&backend)
+        } else {
+            Layout::default()
+        };
+        if let Some(point) = config
+            .fixed_point
+            .filter(|p| p.space != CoordSpace::Window && p.resolve(&layout).is_none())
+        {
+            eprintln!("Fixed point {:?} is not on screen, skipping clicks", point);
+        }
+        Self {
+            layout,
+            windows: None,
+            base_interval: config.base_interval(),
+            home,
+            config,
+            seed,
+            backend,
+            rng,
+        }
+    }
+
+    pub fn with_window_tracker(mut self, tracker: WindowTracker) -> Self {
+        self.windows = Some(tracker);
+        self
+    }
+
+    /// Whether clicking can go ahead, i.e. the target window is open and
+    /// focused if the fixed point is relative to it. Also picks up any move
+    /// or resize of the window.
+    pub fn window_ready(&mut self) -> bool {
+        if !self.config.needs_window() {
+            return true;
+        }
+        let Some(tracker) = &mut self.windows else {
+            return true;
+        };
+        match tracker.state() {
+            WindowState::Ready(rect) => {
+                self.layout.window = Some(rect);
+                true
+            }
+            WindowState::Unfocused | WindowState::Missing => false,
+        }
+    }
+
+    /// Clicks once and returns how long to wait before the next click.
+    pub fn step(&mut self) -> Duration {
+        let target = self.config.fixed_point.map(|p| p.resolve(&self.layout));
+        if let Some(Some((x, y))) = target {
+            if let Err(e) = self.backend.move_mouse(x, y, Coordinate::Abs) {
+                eprintln!("Failed to move mouse: {}", e);
+            }
+        }
+        // A fixed point that cannot be resolved must not turn into clicks
+        // wherever the cursor happens to be
+        if target != Some(None) {
+            let mouse_button = self.config.mouse_button;
+            if let Err(e) = self
+                .backend
+                .button(mouse_button.into(), enigo::Direction::Click)
+            {
+                eprintln!("Failed to click mouse button: {}", e);
+            }
+        }
+
+        if self.config.random_delay_enabled {
+            self.base_interval
+                + Duration::from_millis(self.config.delay_distribution.sample(
+                    &mut self.rng,
+                    self.config.random_delay_min_ms,
+                    self.config.random_delay_max_ms,
+                ))
+        } else {
+            self.base_interval
+        }
+    }
+
+    /// Cleans up after the last click, returning the cursor if configured.
+    pub fn finish(&mut self) {
+        if let Some(home) = self.home {
+            let restore = self.config.restore_cursor;
+            restore.restore(
+                &mut self.backend,
+                home,
+                GlideSettings::default(),
+                &mut self.rng,
+            );
         }
     }
 }
@@ -33,6 +206,10 @@ pub struct Clicker {
     pub config: ClickerConfig,
     is_clicking: Arc<AtomicBool>,
     click_count: Arc<AtomicU64>,
+    jitter_stats: Arc<Mutex<JitterStats>>,
+    last_seed: Arc<AtomicU64>,
+    is_yielding: Arc<AtomicBool>,
+    waiting_for_window: Arc<AtomicBool>,
 }
 
 impl Default for Clicker {
@@ -47,6 +224,10 @@ impl Clicker {
             config,
             is_clicking: Arc::new(AtomicBool::new(false)),
             click_count: Arc::new(AtomicU64::new(0)),
+            jitter_stats: Arc::new(Mutex::new(JitterStats::default())),
+            last_seed: Arc::new(AtomicU64::new(0)),
+            is_yielding: Arc::new(AtomicBool::new(false)),
+            waiting_for_window: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -55,31 +236,48 @@ impl Clicker {
             self.is_clicking.store(true, Ordering::SeqCst);
             let is_clicking = Arc::clone(&self.is_clicking);
             let click_count = Arc::clone(&self.click_count);
+            let jitter_stats = Arc::clone(&self.jitter_stats);
+            let last_seed = Arc::clone(&self.last_seed);
+            let is_yielding = Arc::clone(&self.is_yielding);
+            let waiting_for_window = Arc::clone(&self.waiting_for_window);
             let config = self.config.clone();
 
             thread::spawn(move || {
-                let settings = Settings::default();
-                let mut enigo = Enigo::new(&settings).expect("Failed to create Enigo instance");
-                let mut rng = rand::rng();
+                let mut worker = ClickerWorker::new(config, create_enigo());
+                if worker.config.needs_window() {
+                    match WindowTracker::connect(worker.config.window.clone()) {
+                        Some(tracker) => worker = worker.with_window_tracker(tracker),
+                        None => eprintln!("Failed to open the display to track the target window"),
+                    }
+                }
+                last_seed.store(worker.seed, Ordering::SeqCst);
+                println!("Clicker started with seed {}", worker.seed);
+                let mut scheduler = DeadlineScheduler::new(jitter_stats);
+                if worker.config.cps_mode_enabled {
+                    scheduler = scheduler.precise();
+                }
+
+                let mut yield_gate = YieldGate::new(worker.config.yield_to_user, is_yielding);
 
                 while is_clicking.load(Ordering::SeqCst) {
-                    let mouse_button = config.mouse_button;
-                    if let Err(e) = enigo.button(mouse_button.into(), enigo::Direction::Click) {
-                        eprintln!("Failed to click mouse button: {}", e);
+                    if yield_gate.should_yield() {
+                        thread::sleep(YieldGate::POLL);
+                        scheduler.restart();
+                        continue;
                     }
+                    let window_ready = worker.window_ready();
+                    waiting_for_window.store(!window_ready, Ordering::SeqCst);
+                    if !window_ready {
+                        thread::sleep(YieldGate::POLL);
+                        scheduler.restart();
+                        continue;
+                    }
+                    let delay = worker.step();
                     click_count.fetch_add(1, Ordering::SeqCst);
-
-                    let delay = if config.random_delay_enabled {
-                        config.click_interval_ms
-                            + rng.random_range(
-                                config.random_delay_min_ms..=config.random_delay_max_ms,
-                            )
-                    } else {
-                        config.click_interval_ms
-                    };
-
-                    thread::sleep(Duration::from_millis(delay));
+                    scheduler.wait(delay);
                 }
+                waiting_for_window.store(false, Ordering::SeqCst);
+                worker.finish();
             });
         }
     }
@@ -100,6 +298,22 @@ impl Clicker {
         self.click_count.store(0, Ordering::SeqCst);
     }
 
+    pub fn is_yielding(&self) -> bool {
+        self.is_yielding.load(Ordering::SeqCst)
+    }
+
+    pub fn is_waiting_for_window(&self) -> bool {
+        self.waiting_for_window.load(Ordering::SeqCst)
+    }
+
+    pub fn get_last_seed(&self) -> u64 {
+        self.last_seed.load(Ordering::SeqCst)
+    }
+
+    pub fn get_jitter_stats(&self) -> JitterStats {
+        *self.jitter_stats.lock().unwrap()
+    }
+
     pub fn get_interval(&self) -> u64 {
         self.config.click_interval_ms
     }
@@ -108,6 +322,23 @@ impl Clicker {
         self.config.click_interval_ms = interval;
     }
 
+    pub fn get_cps(&self) -> f64 {
+        self.config.effective_cps()
+    }
+
+    pub fn set_cps(&mut self, cps: f64) {
+        self.config.clicks_per_second = cps.clamp(0.1, self.config.effective_max_cps());
+    }
+
+    pub fn get_max_cps(&self) -> f64 {
+        self.config.effective_max_cps()
+    }
+
+    pub fn set_max_cps(&mut self, max_cps: f64) {
+        self.config.max_cps = max_cps.clamp(1.0, CPS_HARD_LIMIT);
+        self.set_cps(self.config.effective_cps());
+    }
+
     pub fn get_mouse_button(&self) -> SerializableMouseButton {
         self.config.mouse_button
     }
@@ -136,3 +367,61 @@ impl Clicker {
         self.config = config;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Action, MockBackend};
+
+    #[test]
+    fn non_finite_rates_fall_back_to_defaults() {
+        let defaults = ClickerConfig {
+            cps_mode_enabled: true,
+            ..ClickerConfig::default()
+        };
+        for (cps, max_cps) in [
+            (f64::NAN, 200.0),
+            (20.0, f64::NAN),
+            (f64::INFINITY, f64::NEG_INFINITY),
+        ] {
+            let config = ClickerConfig {
+                clicks_per_second: cps,
+                max_cps,
+                ..defaults.clone()
+            };
+            assert_eq!(config.effective_max_cps(), defaults.max_cps);
+            assert_eq!(config.base_interval(), defaults.base_interval());
+        }
+        let fast = ClickerConfig {
+            clicks_per_second: 5000.0,
+            max_cps: f64::NAN,
+            ..defaults.clone()
+        };
+        assert_eq!(fast.effective_cps(), defaults.max_cps);
+    }
+
+    fn run(seed: u64) -> Vec<(Vec<Action>, Duration)> {
+        let config = ClickerConfig {
+            random_delay_enabled: true,
+            delay_distribution: DelayDistribution::Normal {
+                mean_ms: 250.0,
+                stddev_ms: 100.0,
+            },
+            seed: Some(seed),
+            ..ClickerConfig::default()
+        };
+        let mut worker = ClickerWorker::new(config, MockBackend::default());
+        (0..200)
+            .map(|_| {
+                let delay = worker.step();
+                (std::mem::take(&mut worker.backend.actions), delay)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_repeats_clicks() {
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+}
@@ -0,0 +1,195 @@
+use crate::backend::InputBackend;
+use crate::monitors::Monitor;
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    fn at_percent(&self, x: f64, y: f64) -> (i32, i32) {
+        (
+            self.x + (self.width as f64 * x / 100.0).round() as i32,
+            self.y + (self.height as f64 * y / 100.0).round() as i32,
+        )
+    }
+}
+
+/// What the numbers of a [`ScreenPoint`] are measured against.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum CoordSpace {
+    /// Desktop pixels.
+    #[default]
+    Absolute,
+    /// Percent of a monitor's width and height, so 50,50 is its middle.
+    MonitorPercent { monitor: usize },
+    /// Pixels from the top-left corner of the target window.
+    Window,
+}
+
+impl CoordSpace {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CoordSpace::Absolute => "px",
+            CoordSpace::MonitorPercent { .. } => "% of monitor",
+            CoordSpace::Window => "px in window",
+        }
+    }
+}
+
+/// The screen geometry coordinates are resolved against. Monitors are
+/// captured when an automation starts; the window is kept up to date by a
+/// [`WindowTracker`](crate::windows::WindowTracker).
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub monitors: Vec<Monitor>,
+    pub window: Option<Rect>,
+}
+
+impl Layout {
+    pub fn capture(backend: &impl InputBackend) -> Self {
+        Self {
+            monitors: backend.monitors(),
+            window: None,
+        }
+    }
+}
+
+/// A position that is stored in a portable coordinate space and converted to
+/// desktop pixels at run time. Older configs stored a plain `(x, y)` pixel
+/// pair, which still deserializes as an absolute point.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ScreenPoint {
+    pub x: f64,
+    pub y: f64,
+    pub space: CoordSpace,
+}
+
+impl ScreenPoint {
+    pub fn absolute((x, y): (i32, i32)) -> Self {
+        Self {
+            x: x as f64,
+            y: y as f64,
+            space: CoordSpace::Absolute,
+        }
+    }
+
+    /// Desktop pixels, or `None` if the monitor or window it refers to is
+    /// not there.
+    pub fn resolve(&self, layout: &Layout) -> Option<(i32, i32)> {
+        match self.space {
+            CoordSpace::Absolute => Some((self.x.round() as i32, self.y.round() as i32)),
+            CoordSpace::MonitorPercent { monitor } => layout
+                .monitors
+                .get(monitor)
+                .map(|monitor| monitor.rect().at_percent(self.x, self.y)),
+            CoordSpace::Window => layout.window.map(|window| {
+                (
+                    window.x + self.x.round() as i32,
+                    window.y + self.y.round() as i32,
+                )
+            }),
+        }
+    }
+
+    /// Re-expresses a desktop position in `space`, e.g. when the user picks
+    /// a different space for an existing point.
+    pub fn from_pixels(position: (i32, i32), space: CoordSpace, layout: &Layout) -> Self {
+        let rect = match space {
+            CoordSpace::Absolute => None,
+            CoordSpace::MonitorPercent { monitor } => {
+                layout.monitors.get(monitor).map(|m| m.rect())
+            }
+            CoordSpace::Window => layout.window,
+        };
+        let Some(rect) = rect else {
+            return Self {
+                space,
+                ..Self::absolute(position)
+            };
+        };
+        let (dx, dy) = ((position.0 - rect.x) as f64, (position.1 - rect.y) as f64);
+        let (x, y) = match space {
+            CoordSpace::MonitorPercent { .. } => (
+                dx * 100.0 / rect.width.max(1) as f64,
+                dy * 100.0 / rect.height.max(1) as f64,
+            ),
+            _ => (dx, dy),
+        };
+        Self { x, y, space }
+    }
+}
+
+impl<'de> Deserialize<'de> for ScreenPoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PointVisitor;
+
+        impl<'de> Visitor<'de> for PointVisitor {
+            type Value = ScreenPoint;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an (x, y) pixel pair or a point with x, y and space")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ScreenPoint, A::Error> {
+                let x: i32 = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let y: i32 = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                Ok(ScreenPoint::absolute((x, y)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ScreenPoint, A::Error> {
+                let mut point = ScreenPoint::absolute((0, 0));
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "x" => point.x = map.next_value()?,
+                        "y" => point.y = map.next_value()?,
+                        "space" => point.space = map.next_value()?,
+                        _ => {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(point)
+            }
+        }
+
+        deserializer.deserialize_any(PointVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_legacy_pixel_pairs() {
+        let point: ScreenPoint = ron::from_str("(10, -20)").unwrap();
+        assert_eq!(point, ScreenPoint::absolute((10, -20)));
+        let point: Option<ScreenPoint> = ron::from_str("Some((3, 4))").unwrap();
+        assert_eq!(point, Some(ScreenPoint::absolute((3, 4))));
+        assert!(ron::from_str::<ScreenPoint>("(10)").is_err());
+    }
+
+    #[test]
+    fn reads_points_with_a_space() {
+        let point = ScreenPoint {
+            x: 50.0,
+            y: 25.5,
+            space: CoordSpace::MonitorPercent { monitor: 1 },
+        };
+        let text = ron::to_string(&point).unwrap();
+        assert_eq!(ron::from_str::<ScreenPoint>(&text).unwrap(), point);
+        let point: ScreenPoint = ron::from_str("(x: 7.0, y: 8.0)").unwrap();
+        assert_eq!(point, ScreenPoint::absolute((7, 8)));
+    }
+}
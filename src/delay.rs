@@ -0,0 +1,132 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum DelayDistribution {
+    #[default]
+    Uniform,
+    Normal {
+        mean_ms: f64,
+        stddev_ms: f64,
+    },
+    LogNormal {
+        median_ms: f64,
+        sigma: f64,
+    },
+    /// Offset from the range minimum, so short delays are the most likely.
+    Exponential {
+        mean_ms: f64,
+    },
+}
+
+impl DelayDistribution {
+    pub const ALL: [DelayDistribution; 4] = [
+        DelayDistribution::Uniform,
+        DelayDistribution::Normal {
+            mean_ms: 100.0,
+            stddev_ms: 30.0,
+        },
+        DelayDistribution::LogNormal {
+            median_ms: 80.0,
+            sigma: 0.5,
+        },
+        DelayDistribution::Exponential { mean_ms: 60.0 },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DelayDistribution::Uniform => "Uniform",
+            DelayDistribution::Normal { .. } => "Normal",
+            DelayDistribution::LogNormal { .. } => "Log-normal",
+            DelayDistribution::Exponential { .. } => "Exponential",
+        }
+    }
+
+    /// Draws an extra delay in milliseconds, always clamped to `min_ms..=max_ms`.
+    pub fn sample(&self, rng: &mut impl Rng, min_ms: u64, max_ms: u64) -> u64 {
+        let max_ms = max_ms.max(min_ms);
+        let value = match *self {
+            DelayDistribution::Uniform => return rng.random_range(min_ms..=max_ms),
+            DelayDistribution::Normal { mean_ms, stddev_ms } => {
+                mean_ms + stddev_ms * standard_normal(rng)
+            }
+            DelayDistribution::LogNormal { median_ms, sigma } => {
+                median_ms * (sigma * standard_normal(rng)).exp()
+            }
+            DelayDistribution::Exponential { mean_ms } => {
+                let u: f64 = rng.random();
+                min_ms as f64 - mean_ms * (1.0 - u).ln()
+            }
+        };
+        (value.round().max(0.0) as u64).clamp(min_ms, max_ms)
+    }
+
+    /// Bucketed sample counts over `min_ms..=max_ms` for the GUI preview. Uses a
+    /// fixed seed so the preview does not flicker between frames.
+    pub fn histogram(&self, min_ms: u64, max_ms: u64, bins: usize) -> Vec<u32> {
+        const SAMPLES: usize = 2000;
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut counts = vec![0; bins];
+        let span = (max_ms.saturating_sub(min_ms) + 1) as f64;
+        for _ in 0..SAMPLES {
+            let offset = (self.sample(&mut rng, min_ms, max_ms) - min_ms) as f64;
+            let bin = ((offset / span) * bins as f64) as usize;
+            counts[bin.min(bins - 1)] += 1;
+        }
+        counts
+    }
+}
+
+// Box-Muller transform
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.random::<f64>();
+    let u2: f64 = rng.random();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_stay_in_range() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let far = [
+            DelayDistribution::Normal {
+                mean_ms: 1000.0,
+                stddev_ms: 500.0,
+            },
+            DelayDistribution::LogNormal {
+                median_ms: 1.0,
+                sigma: 3.0,
+            },
+            DelayDistribution::Exponential { mean_ms: 1000.0 },
+        ];
+        for distribution in DelayDistribution::ALL.into_iter().chain(far) {
+            for _ in 0..1000 {
+                let ms = distribution.sample(&mut rng, 50, 60);
+                assert!((50..=60).contains(&ms), "{:?} gave {}", distribution, ms);
+            }
+        }
+    }
+
+    #[test]
+    fn clamps_to_the_nearer_bound() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let high = DelayDistribution::Normal {
+            mean_ms: 1e6,
+            stddev_ms: 1.0,
+        };
+        let low = DelayDistribution::Normal {
+            mean_ms: -1e6,
+            stddev_ms: 1.0,
+        };
+        assert_eq!(high.sample(&mut rng, 10, 20), 20);
+        assert_eq!(low.sample(&mut rng, 10, 20), 10);
+        // A maximum below the minimum collapses onto the minimum
+        for distribution in DelayDistribution::ALL {
+            assert_eq!(distribution.sample(&mut rng, 100, 10), 100);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use crate::backend::{create_enigo, InputBackend};
+use crate::coords::{CoordSpace, Layout, ScreenPoint};
+use crate::macros::Key;
+use crate::motion::{GlideSettings, MotionStyle};
+use crate::mouse_button::SerializableMouseButton;
+use crate::scheduler::{DeadlineScheduler, JitterStats};
+use crate::seed::session_rng;
+use enigo::{Coordinate, Direction, InputResult};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Pause between pressing and the first move, so the target sees a press
+/// before the drag starts rather than a press already in motion.
+pub const PRESS_SETTLE: Duration = Duration::from_millis(50);
+
+/// The glide a new drag uses. Most drop targets ignore a drag that jumps
+/// straight to the end, so drags ease by default.
+pub fn default_glide() -> GlideSettings {
+    GlideSettings {
+        style: MotionStyle::EaseInOut,
+        ..GlideSettings::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DragAction {
+    Move(i32, i32),
+    Press,
+    Release,
+}
+
+impl DragAction {
+    pub fn send(
+        self,
+        backend: &mut impl InputBackend,
+        button: SerializableMouseButton,
+    ) -> InputResult<()> {
+        match self {
+            DragAction::Move(x, y) => backend.move_mouse(x, y, Coordinate::Abs),
+            DragAction::Press => backend.button(button.into(), Direction::Press),
+            DragAction::Release => backend.button(button.into(), Direction::Release),
+        }
+    }
+}
+
+/// Hands out the actions of one drag, each with the pause to leave after
+/// it: move to the start, press, glide to the end, dwell, release.
+#[derive(Debug, Clone)]
+pub struct Drag {
+    pub button: SerializableMouseButton,
+    queue: VecDeque<(DragAction, Duration)>,
+}
+
+impl Drag {
+    pub fn new(
+        button: SerializableMouseButton,
+        from: (i32, i32),
+        to: (i32, i32),
+        glide: GlideSettings,
+        hover_ms: u64,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let step = Duration::from_secs_f64(1.0 / glide.step_hz.max(1) as f64);
+        let mut queue = VecDeque::from([
+            (DragAction::Move(from.0, from.1), Duration::ZERO),
+            (DragAction::Press, PRESS_SETTLE),
+        ]);
+        queue.extend(
+            glide
+                .path(from, to, rng)
+                .into_iter()
+                .map(|(x, y)| (DragAction::Move(x, y), step)),
+        );
+        if let Some((_, pause)) = queue.back_mut() {
+            *pause = Duration::from_millis(hover_ms);
+        }
+        queue.push_back((DragAction::Release, Duration::ZERO));
+        Self { button, queue }
+    }
+
+    pub fn next(&mut self) -> Option<(DragAction, Duration)> {
+        self.queue.pop_front()
+    }
+}
+
+/// What the Drag panel and its hotkey drag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DragSettings {
+    pub button: SerializableMouseButton,
+    pub from: ScreenPoint,
+    pub to: ScreenPoint,
+    pub glide: GlideSettings,
+    /// Time to hold still over the end point before releasing, for drop
+    /// targets that only react to a hover.
+    pub hover_ms: u64,
+    pub seed: Option<u64>,
+    /// Key that starts and stops the drag. Unbound until the user picks one.
+    pub hotkey: Option<Key>,
+}
+
+impl Default for DragSettings {
+    fn default() -> Self {
+        let point = |x| ScreenPoint {
+            x,
+            y: 50.0,
+            space: CoordSpace::MonitorPercent { monitor: 0 },
+        };
+        Self {
+            button: SerializableMouseButton::Left,
+            from: point(40.0),
+            to: point(60.0),
+            glide: default_glide(),
+            hover_ms: 200,
+            seed: None,
+            hotkey: None,
+        }
+    }
+}
+
+impl DragSettings {
+    /// The start and end points in desktop pixels.
+    pub fn resolve(&self, layout: &Layout) -> Result<[(i32, i32); 2], String> {
+        let from = self
+            .from
+            .resolve(layout)
+            .ok_or("the start point is not on screen")?;
+        let to = self
+            .to
+            .resolve(layout)
+            .ok_or("the end point is not on screen")?;
+        Ok([from, to])
+    }
+}
+
+pub struct DragTick {
+    pub delay: Duration,
+    pub finished: bool,
+}
+
+pub struct DragWorker<B: InputBackend> {
+    pub seed: u64,
+    pub backend: B,
+    drag: Drag,
+    pressed: bool,
+}
+
+impl<B: InputBackend> DragWorker<B> {
+    pub fn new(settings: &DragSettings, backend: B) -> Result<Self, String> {
+        let [from, to] = settings.resolve(&Layout::capture(&backend))?;
+        let (mut rng, seed) = session_rng(settings.seed);
+        let drag = Drag::new(
+            settings.button,
+            from,
+            to,
+            settings.glide,
+            settings.hover_ms,
+            &mut rng,
+        );
+        Ok(Self {
+            seed,
+            backend,
+            drag,
+            pressed: false,
+        })
+    }
+
+    pub fn step(&mut self) -> DragTick {
+        let Some((action, delay)) = self.drag.next() else {
+            return DragTick {
+                delay: Duration::ZERO,
+                finished: true,
+            };
+        };
+        match action {
+            DragAction::Press => self.pressed = true,
+            DragAction::Release => self.pressed = false,
+            DragAction::Move(..) => {}
+        }
+        if let Err(e) = action.send(&mut self.backend, self.drag.button) {
+            eprintln!("Failed to drag: {}", e);
+        }
+        DragTick {
+            delay,
+            finished: false,
+        }
+    }
+
+    /// Releases the button if the drag was stopped part way.
+    pub fn finish(&mut self) {
+        if std::mem::take(&mut self.pressed) {
+            if let Err(e) = DragAction::Release.send(&mut self.backend, self.drag.button) {
+                eprintln!("Failed to release mouse button: {}", e);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Dragger {
+    is_dragging: Arc<AtomicBool>,
+    jitter_stats: Arc<Mutex<JitterStats>>,
+    last_seed: Arc<AtomicU64>,
+}
+
+impl Default for Dragger {
+    fn default() -> Self {
+        Self {
+            is_dragging: Arc::new(AtomicBool::new(false)),
+            jitter_stats: Arc::new(Mutex::new(JitterStats::default())),
+            last_seed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Dragger {
+    /// Checks both points are on screen and starts the drag, unless one is
+    /// already running.
+    pub fn start_dragging(
+        &mut self,
+        settings: DragSettings,
+        layout: &Layout,
+    ) -> Result<(), String> {
+        settings.resolve(layout)?;
+        if !self.is_dragging.load(Ordering::SeqCst) {
+            self.is_dragging.store(true, Ordering::SeqCst);
+            let is_dragging = Arc::clone(&self.is_dragging);
+            let jitter_stats = Arc::clone(&self.jitter_stats);
+            let last_seed = Arc::clone(&self.last_seed);
+
+            thread::spawn(move || {
+                let mut worker = match DragWorker::new(&settings, create_enigo()) {
+                    Ok(worker) => worker,
+                    Err(e) => {
+                        eprintln!("Failed to start drag: {}", e);
+                        is_dragging.store(false, Ordering::SeqCst);
+                        return;
+                    }
+                };
+                last_seed.store(worker.seed, Ordering::SeqCst);
+                println!("Dragging with seed {}", worker.seed);
+
+                let mut scheduler = DeadlineScheduler::new(jitter_stats).precise();
+                while is_dragging.load(Ordering::SeqCst) {
+                    let tick = worker.step();
+                    if tick.finished {
+                        break;
+                    }
+                    scheduler.wait(tick.delay);
+                }
+                worker.finish();
+                is_dragging.store(false, Ordering::SeqCst);
+            });
+        }
+        Ok(())
+    }
+
+    pub fn stop_dragging(&mut self) {
+        self.is_dragging.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.is_dragging.load(Ordering::SeqCst)
+    }
+
+    pub fn get_last_seed(&self) -> u64 {
+        self.last_seed.load(Ordering::SeqCst)
+    }
+
+    pub fn get_jitter_stats(&self) -> JitterStats {
+        *self.jitter_stats.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Action, MockBackend};
+    use enigo::Button;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn actions(drag: &mut Drag) -> Vec<(DragAction, Duration)> {
+        std::iter::from_fn(|| drag.next()).collect()
+    }
+
+    #[test]
+    fn drags_press_glide_hover_and_release() {
+        let glide = default_glide();
+        let mut rng = StdRng::seed_from_u64(3);
+        let path = glide.path((100, 100), (400, 300), &mut rng);
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut drag = Drag::new(
+            SerializableMouseButton::Left,
+            (100, 100),
+            (400, 300),
+            glide,
+            250,
+            &mut rng,
+        );
+        let actions = actions(&mut drag);
+        let step = Duration::from_secs_f64(1.0 / glide.step_hz as f64);
+
+        assert_eq!(actions.len(), path.len() + 3);
+        assert_eq!(actions[0], (DragAction::Move(100, 100), Duration::ZERO));
+        assert_eq!(actions[1], (DragAction::Press, PRESS_SETTLE));
+        let glided = &actions[2..actions.len() - 1];
+        for (i, (&(action, pause), &(x, y))) in glided.iter().zip(&path).enumerate() {
+            assert_eq!(action, DragAction::Move(x, y));
+            let expected = if i + 1 == path.len() {
+                Duration::from_millis(250)
+            } else {
+                step
+            };
+            assert_eq!(pause, expected);
+        }
+        assert_eq!(path.last(), Some(&(400, 300)));
+        assert_eq!(actions.last(), Some(&(DragAction::Release, Duration::ZERO)));
+    }
+
+    #[test]
+    fn finish_releases_a_held_button() {
+        let settings = DragSettings {
+            button: SerializableMouseButton::Right,
+            seed: Some(1),
+            ..DragSettings::default()
+        };
+        let mut worker = DragWorker::new(&settings, MockBackend::default()).unwrap();
+        worker.step();
+        worker.step();
+        worker.finish();
+        worker.finish();
+        assert_eq!(
+            worker.backend.actions[1..],
+            [
+                Action::Button(Button::Right, Direction::Press),
+                Action::Button(Button::Right, Direction::Release),
+            ]
+        );
+
+        let mut worker = DragWorker::new(&settings, MockBackend::default()).unwrap();
+        while !worker.step().finished {}
+        let sent = worker.backend.actions.len();
+        worker.finish();
+        assert_eq!(worker.backend.actions.len(), sent);
+    }
+}
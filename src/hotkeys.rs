@@ -0,0 +1,20 @@
+use device_query::Keycode;
+
+pub const CLICKER: Keycode = Keycode::F6;
+pub const MOUSE_MOVER: Keycode = Keycode::F7;
+pub const RECORDER: Keycode = Keycode::F8;
+pub const PLAYBACK: Keycode = Keycode::F9;
+pub const SCRIPT: Keycode = Keycode::F10;
+
+/// Every fixed key Mourse reacts to, so input monitors can ignore them. The
+/// drag and chain hotkeys are bound in their panels instead, since the keys
+/// after F10 are fullscreen and developer tools in browsers.
+pub const ALL: &[Keycode] = &[CLICKER, MOUSE_MOVER, RECORDER, PLAYBACK, SCRIPT];
+
+/// The fixed hotkeys plus the bound ones.
+pub fn with_bound(bound: &[Option<Keycode>]) -> Vec<Keycode> {
+    ALL.iter()
+        .copied()
+        .chain(bound.iter().flatten().copied())
+        .collect()
+}
@@ -0,0 +1,129 @@
+use crate::hotkeys;
+use device_query::{DeviceQuery, DeviceState, Keycode, MouseState};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Where Mourse itself last put the cursor, shared by every worker so that one
+// worker's moves are not taken for the user by another worker's tracker.
+static INJECTED_CURSOR: Mutex<Option<(i32, i32)>> = Mutex::new(None);
+
+pub fn note_injected_cursor(position: (i32, i32)) {
+    *INJECTED_CURSOR.lock().unwrap() = Some(position);
+}
+
+fn is_injected_cursor(position: (i32, i32)) -> bool {
+    INJECTED_CURSOR
+        .lock()
+        .unwrap()
+        .is_some_and(|(x, y)| (x - position.0).abs() <= 1 && (y - position.1).abs() <= 1)
+}
+
+/// Tracks how long the physical mouse and keyboard have been left alone by
+/// polling their state and noting when anything changes. Cursor positions
+/// that match Mourse's own injected moves do not count as input.
+pub struct IdleTracker {
+    device_state: DeviceState,
+    last_mouse: MouseState,
+    last_keys: Vec<Keycode>,
+    last_input: Instant,
+    count_buttons: bool,
+    ignored_keys: &'static [Keycode],
+}
+
+impl IdleTracker {
+    pub fn new() -> Self {
+        let device_state = DeviceState::new();
+        Self {
+            last_mouse: device_state.get_mouse(),
+            last_keys: device_state.get_keys(),
+            last_input: Instant::now(),
+            device_state,
+            count_buttons: true,
+            ignored_keys: &[],
+        }
+    }
+
+    /// Injected clicks are indistinguishable from real ones, so trackers
+    /// running next to the clicker should look at movement and keys only.
+    pub fn ignoring_buttons(mut self) -> Self {
+        self.count_buttons = false;
+        self
+    }
+
+    pub fn ignoring_keys(mut self, keys: &'static [Keycode]) -> Self {
+        self.ignored_keys = keys;
+        self
+    }
+
+    pub fn idle_for(&mut self) -> Duration {
+        let mouse = self.device_state.get_mouse();
+        let mut keys = self.device_state.get_keys();
+        keys.retain(|key| !self.ignored_keys.contains(key));
+
+        let moved = mouse.coords != self.last_mouse.coords && !is_injected_cursor(mouse.coords);
+        let clicked = self.count_buttons && mouse.button_pressed != self.last_mouse.button_pressed;
+        if moved || clicked || keys != self.last_keys {
+            self.last_input = Instant::now();
+        }
+        self.last_mouse = mouse;
+        self.last_keys = keys;
+        self.last_input.elapsed()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct YieldSettings {
+    pub enabled: bool,
+    pub quiet_period_ms: u64,
+}
+
+impl Default for YieldSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            quiet_period_ms: 1500,
+        }
+    }
+}
+
+/// Holds a worker back while the user is using the mouse or keyboard.
+pub struct YieldGate {
+    tracker: Option<IdleTracker>,
+    quiet_period: Duration,
+    paused: Arc<AtomicBool>,
+}
+
+impl YieldGate {
+    pub const POLL: Duration = Duration::from_millis(50);
+
+    pub fn new(settings: YieldSettings, paused: Arc<AtomicBool>) -> Self {
+        paused.store(false, Ordering::SeqCst);
+        Self {
+            tracker: settings.enabled.then(|| {
+                IdleTracker::new()
+                    .ignoring_buttons()
+                    .ignoring_keys(hotkeys::ALL)
+            }),
+            quiet_period: Duration::from_millis(settings.quiet_period_ms),
+            paused,
+        }
+    }
+
+    pub fn should_yield(&mut self) -> bool {
+        let Some(tracker) = &mut self.tracker else {
+            return false;
+        };
+        let yielding = tracker.idle_for() < self.quiet_period;
+        self.paused.store(yielding, Ordering::SeqCst);
+        yielding
+    }
+}
+
+impl Drop for YieldGate {
+    fn drop(&mut self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,620 @@
+use crate::app::screen_point_ui;
+use crate::coords::{CoordSpace, Layout, ScreenPoint};
+use crate::drag;
+use crate::macros::Key;
+use crate::motion::MotionStyle;
+use crate::mouse_button::SerializableMouseButton;
+use crate::steps::{Condition, Input, Program, Step};
+use crate::typing::TypingStyle;
+use device_query::Keycode;
+use eframe::egui;
+use std::collections::HashSet;
+
+/// A new step of each kind, in the order the insert menu lists them.
+fn templates() -> [Step; 12] {
+    [
+        Step::Move {
+            to: ScreenPoint {
+                x: 50.0,
+                y: 50.0,
+                space: CoordSpace::MonitorPercent { monitor: 0 },
+            },
+        },
+        Step::Click {
+            button: SerializableMouseButton::Left,
+        },
+        Step::Press {
+            input: Input::Key(Key(Keycode::LShift)),
+        },
+        Step::Release {
+            input: Input::Key(Key(Keycode::LShift)),
+        },
+        Step::Drag {
+            button: SerializableMouseButton::Left,
+            from: ScreenPoint {
+                x: 40.0,
+                y: 50.0,
+                space: CoordSpace::MonitorPercent { monitor: 0 },
+            },
+            to: ScreenPoint {
+                x: 60.0,
+                y: 50.0,
+                space: CoordSpace::MonitorPercent { monitor: 0 },
+            },
+            glide: drag::default_glide(),
+            hover_ms: 200,
+        },
+        Step::Scroll {
+            amount: 1,
+            horizontal: false,
+        },
+        Step::Type {
+            text: "text".to_string(),
+            style: TypingStyle::default(),
+        },
+        Step::Wait { ms: 100 },
+        Step::WaitForKey {
+            key: Key(Keycode::Space),
+            timeout_ms: None,
+        },
+        Step::Loop {
+            label: "loop".to_string(),
+            count: Some(2),
+            steps: vec![Step::Wait { ms: 100 }],
+        },
+        Step::Break {
+            label: "loop".to_string(),
+        },
+        Step::If {
+            condition: Condition::KeyDown {
+                key: Key(Keycode::LShift),
+            },
+            then: Vec::new(),
+            otherwise: Vec::new(),
+        },
+    ]
+}
+
+/// The step lists nested in `step`, with the path prefix of their steps and
+/// a heading where a step has more than one.
+fn blocks<'a>(
+    step: &'a mut Step,
+    path: &str,
+) -> Vec<(String, Option<&'static str>, &'a mut Vec<Step>)> {
+    match step {
+        Step::Loop { steps, .. } => vec![(format!("{}.", path), None, steps)],
+        Step::If {
+            then, otherwise, ..
+        } => vec![
+            (format!("{}.then.", path), Some("Then:"), then),
+            (format!("{}.else.", path), Some("Else:"), otherwise),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Scales the waits and key timeouts of the selected steps, and of every
+/// step nested inside a selected one, by `factor`.
+fn adjust_timing(steps: &mut [Step], prefix: &str, selected: &HashSet<String>, factor: f64) {
+    for (i, step) in steps.iter_mut().enumerate() {
+        let path = format!("{}{}", prefix, i + 1);
+        if selected.contains(&path) {
+            scale_timing(step, factor);
+        } else {
+            for (prefix, _, steps) in blocks(step, &path) {
+                adjust_timing(steps, &prefix, selected, factor);
+            }
+        }
+    }
+}
+
+fn scale_timing(step: &mut Step, factor: f64) {
+    let scale = |ms: &mut u64| *ms = (*ms as f64 * factor).round() as u64;
+    match step {
+        Step::Wait { ms } => scale(ms),
+        Step::WaitForKey {
+            timeout_ms: Some(ms),
+            ..
+        } => scale(ms),
+        _ => {
+            for (_, _, steps) in blocks(step, "") {
+                steps.iter_mut().for_each(|step| scale_timing(step, factor));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RowAction {
+    Raise(usize),
+    Lower(usize),
+    Insert(usize),
+    Duplicate(usize),
+    Delete(usize),
+}
+
+/// What one frame of the editor reports back to the app.
+#[derive(Debug, Default)]
+pub struct EditorResponse {
+    pub changed: bool,
+    /// A step the user asked to run on its own.
+    pub test: Option<Step>,
+}
+
+/// Per-frame inputs shared by every row.
+struct Pass<'a> {
+    layout: &'a Layout,
+    /// A key held down this frame, for key fields waiting for one.
+    key: Option<Keycode>,
+    error_path: Option<String>,
+    can_test: bool,
+    response: EditorResponse,
+}
+
+/// Edits a macro's steps as an indented list, with nested loops and
+/// branches shown under the step that owns them. Steps are addressed by the
+/// same dotted paths validation errors use.
+#[derive(Debug)]
+pub struct MacroEditor {
+    /// Name of the macro being edited; selection resets when it changes.
+    editing: String,
+    selected: HashSet<String>,
+    timing_percent: f64,
+    insert_kind: usize,
+    /// Id of the key field waiting for a key press.
+    capturing: Option<String>,
+}
+
+impl Default for MacroEditor {
+    fn default() -> Self {
+        Self {
+            editing: String::new(),
+            selected: HashSet::new(),
+            timing_percent: 100.0,
+            insert_kind: 1,
+            capturing: None,
+        }
+    }
+}
+
+impl MacroEditor {
+    /// `keys` are the keys held this frame, with Mourse's hotkeys left out.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        name: &str,
+        steps: &mut Vec<Step>,
+        layout: &Layout,
+        keys: &[Keycode],
+        can_test: bool,
+    ) -> EditorResponse {
+        if self.editing != name {
+            self.editing = name.to_string();
+            self.selected.clear();
+            self.capturing = None;
+        }
+        let error = Program::compile(steps).err();
+        let mut pass = Pass {
+            layout,
+            key: keys.first().copied(),
+            error_path: error.as_ref().map(|e| e.path.clone()),
+            can_test,
+            response: EditorResponse::default(),
+        };
+
+        ui.horizontal(|ui| {
+            let templates = templates();
+            egui::ComboBox::from_id_salt("editor_insert_kind")
+                .width(90.0)
+                .selected_text(templates[self.insert_kind].name())
+                .show_ui(ui, |ui| {
+                    for (i, template) in templates.iter().enumerate() {
+                        ui.selectable_value(&mut self.insert_kind, i, template.name());
+                    }
+                });
+            if ui.button("Add Step").clicked() {
+                steps.push(templates[self.insert_kind].clone());
+                pass.response.changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.timing_percent)
+                    .range(1.0..=1000.0)
+                    .max_decimals(0)
+                    .suffix("%"),
+            );
+            let any_selected = !self.selected.is_empty();
+            if ui
+                .add_enabled(any_selected, egui::Button::new("Adjust Timing"))
+                .on_hover_text("Scales waits and key timeouts of the selected steps")
+                .clicked()
+            {
+                adjust_timing(steps, "", &self.selected, self.timing_percent / 100.0);
+                pass.response.changed = true;
+            }
+            if ui
+                .add_enabled(any_selected, egui::Button::new("Clear Selection"))
+                .clicked()
+            {
+                self.selected.clear();
+            }
+        });
+        match &error {
+            Some(e) => ui.colored_label(egui::Color32::YELLOW, format!("⚠ {}", e)),
+            None => ui.small("✔ Valid"),
+        };
+
+        self.list(ui, steps, "", &mut pass);
+        pass.response
+    }
+
+    fn list(&mut self, ui: &mut egui::Ui, steps: &mut Vec<Step>, prefix: &str, pass: &mut Pass) {
+        let count = steps.len();
+        let mut action = None;
+        for (i, step) in steps.iter_mut().enumerate() {
+            let path = format!("{}{}", prefix, i + 1);
+            ui.horizontal(|ui| {
+                let mut selected = self.selected.contains(&path);
+                if ui.checkbox(&mut selected, "").changed() {
+                    if selected {
+                        self.selected.insert(path.clone());
+                    } else {
+                        self.selected.remove(&path);
+                    }
+                }
+                if pass.error_path.as_deref() == Some(path.as_str()) {
+                    ui.colored_label(egui::Color32::YELLOW, &path);
+                } else {
+                    ui.label(&path);
+                }
+                ui.strong(step.name());
+                pass.response.changed |= self.step_ui(ui, step, &path, pass);
+
+                if i > 0 && ui.small_button("⏶").clicked() {
+                    action = Some(RowAction::Raise(i));
+                }
+                if i + 1 < count && ui.small_button("⏷").clicked() {
+                    action = Some(RowAction::Lower(i));
+                }
+                if ui
+                    .add_enabled(pass.can_test, egui::Button::new("▶").small())
+                    .on_hover_text("Run this step now")
+                    .clicked()
+                {
+                    pass.response.test = Some(step.clone());
+                }
+                if ui
+                    .small_button("➕")
+                    .on_hover_text("Insert a new step after this one")
+                    .clicked()
+                {
+                    action = Some(RowAction::Insert(i));
+                }
+                if ui.small_button("⧉").on_hover_text("Duplicate").clicked() {
+                    action = Some(RowAction::Duplicate(i));
+                }
+                if ui.small_button("🗑").clicked() {
+                    action = Some(RowAction::Delete(i));
+                }
+            });
+            for (prefix, heading, steps) in blocks(step, &path) {
+                ui.indent(&prefix, |ui| {
+                    if let Some(heading) = heading {
+                        ui.small(heading);
+                    }
+                    self.list(ui, steps, &prefix, pass);
+                });
+            }
+        }
+        if steps.is_empty() && !prefix.is_empty() && ui.small_button("➕ Add Step").clicked() {
+            steps.push(templates()[self.insert_kind].clone());
+            pass.response.changed = true;
+        }
+
+        let Some(action) = action else {
+            return;
+        };
+        match action {
+            RowAction::Raise(i) => steps.swap(i - 1, i),
+            RowAction::Lower(i) => steps.swap(i, i + 1),
+            RowAction::Insert(i) => steps.insert(i + 1, templates()[self.insert_kind].clone()),
+            RowAction::Duplicate(i) => steps.insert(i + 1, steps[i].clone()),
+            RowAction::Delete(i) => {
+                steps.remove(i);
+            }
+        }
+        // Paths below the change now name different steps
+        self.selected.clear();
+        self.capturing = None;
+        pass.response.changed = true;
+    }
+
+    fn step_ui(&mut self, ui: &mut egui::Ui, step: &mut Step, path: &str, pass: &Pass) -> bool {
+        let mut changed = false;
+        match step {
+            Step::Move { to } => {
+                changed |= screen_point_ui(ui, ("step_point", path), to, pass.layout);
+            }
+            Step::Click { button } => {
+                changed |= button_combo(ui, ("step_button", path), button);
+            }
+            Step::Press { input } | Step::Release { input } => {
+                changed |= self.input_ui(ui, path, input, pass.key);
+            }
+            Step::Drag {
+                button,
+                from,
+                to,
+                glide,
+                hover_ms,
+            } => {
+                changed |= button_combo(ui, ("step_button", path), button);
+                changed |= screen_point_ui(ui, ("step_from", path), from, pass.layout);
+                ui.label("→");
+                changed |= screen_point_ui(ui, ("step_to", path), to, pass.layout);
+                egui::ComboBox::from_id_salt(("step_motion", path))
+                    .width(80.0)
+                    .selected_text(format!("{:?}", glide.style))
+                    .show_ui(ui, |ui| {
+                        for style in MotionStyle::ALL {
+                            changed |= ui
+                                .selectable_value(&mut glide.style, style, format!("{:?}", style))
+                                .changed();
+                        }
+                    });
+                if glide.style != MotionStyle::Instant {
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut glide.speed_px_per_s)
+                                .speed(10.0)
+                                .range(50.0..=5000.0)
+                                .max_decimals(0)
+                                .suffix(" px/s"),
+                        )
+                        .changed();
+                }
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(hover_ms)
+                            .speed(10.0)
+                            .prefix("hover ")
+                            .suffix(" ms"),
+                    )
+                    .on_hover_text("Time over the end point before releasing")
+                    .changed();
+            }
+            Step::Scroll { amount, horizontal } => {
+                changed |= ui.add(egui::DragValue::new(amount)).changed();
+                changed |= ui.checkbox(horizontal, "Horizontal").changed();
+            }
+            Step::Type { text, style } => {
+                changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(text)
+                            .hint_text("text{ENTER}")
+                            .desired_width(120.0),
+                    )
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut style.min_char_delay_ms)
+                            .range(0..=style.max_char_delay_ms)
+                            .suffix(" ms"),
+                    )
+                    .on_hover_text("Shortest pause after each character")
+                    .changed();
+                ui.label("to");
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut style.max_char_delay_ms)
+                            .range(style.min_char_delay_ms..=5000)
+                            .suffix(" ms"),
+                    )
+                    .changed();
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut style.typo_percent)
+                            .range(0.0..=20.0)
+                            .max_decimals(1)
+                            .suffix("% typos"),
+                    )
+                    .changed();
+            }
+            Step::Wait { ms } => {
+                changed |= ui
+                    .add(egui::DragValue::new(ms).speed(10.0).suffix(" ms"))
+                    .changed();
+            }
+            Step::WaitForKey { key, timeout_ms } => {
+                changed |= self.key_ui(ui, format!("{}:key", path), key, pass.key);
+                let mut limited = timeout_ms.is_some();
+                if ui.checkbox(&mut limited, "Timeout").changed() {
+                    *timeout_ms = limited.then_some(1000);
+                    changed = true;
+                }
+                if let Some(ms) = timeout_ms {
+                    changed |= ui
+                        .add(egui::DragValue::new(ms).speed(10.0).suffix(" ms"))
+                        .changed();
+                }
+            }
+            Step::Loop { label, count, .. } => {
+                changed |= ui
+                    .add(egui::TextEdit::singleline(label).desired_width(60.0))
+                    .changed();
+                let mut forever = count.is_none();
+                if ui.checkbox(&mut forever, "Forever").changed() {
+                    *count = if forever { None } else { Some(2) };
+                    changed = true;
+                }
+                if let Some(count) = count {
+                    changed |= ui
+                        .add(egui::DragValue::new(count).range(1..=u32::MAX).suffix("x"))
+                        .changed();
+                }
+            }
+            Step::Break { label } => {
+                changed |= ui
+                    .add(egui::TextEdit::singleline(label).desired_width(60.0))
+                    .changed();
+            }
+            Step::If { condition, .. } => {
+                changed |= self.condition_ui(ui, format!("{}:if", path), condition, pass.key);
+            }
+        }
+        changed
+    }
+
+    fn input_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        path: &str,
+        input: &mut Input,
+        pressed: Option<Keycode>,
+    ) -> bool {
+        let mut changed = false;
+        let is_key = matches!(input, Input::Key(_));
+        egui::ComboBox::from_id_salt(("step_input", path))
+            .width(60.0)
+            .selected_text(if is_key { "Key" } else { "Mouse" })
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(!is_key, "Mouse").clicked() && is_key {
+                    *input = Input::Button(SerializableMouseButton::Left);
+                    changed = true;
+                }
+                if ui.selectable_label(is_key, "Key").clicked() && !is_key {
+                    *input = Input::Key(Key(Keycode::LShift));
+                    changed = true;
+                }
+            });
+        changed
+            | match input {
+                Input::Button(button) => button_combo(ui, ("step_input_button", path), button),
+                Input::Key(key) => self.key_ui(ui, format!("{}:input", path), key, pressed),
+            }
+    }
+
+    fn condition_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        id: String,
+        condition: &mut Condition,
+        pressed: Option<Keycode>,
+    ) -> bool {
+        let mut negated = matches!(condition, Condition::Not(_));
+        if ui.checkbox(&mut negated, "Not").changed() {
+            *condition = match &*condition {
+                Condition::Not(inner) => (**inner).clone(),
+                other => Condition::Not(Box::new(other.clone())),
+            };
+            return true;
+        }
+        let condition = match condition {
+            Condition::Not(inner) => inner.as_mut(),
+            other => other,
+        };
+
+        let mut changed = false;
+        let is_pixel = matches!(condition, Condition::Pixel { .. });
+        egui::ComboBox::from_id_salt(("step_condition", &id))
+            .width(70.0)
+            .selected_text(if is_pixel { "Pixel" } else { "Key down" })
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(is_pixel, "Pixel").clicked() && !is_pixel {
+                    *condition = Condition::Pixel {
+                        x: 0,
+                        y: 0,
+                        rgb: (0, 0, 0),
+                        tolerance: 0,
+                    };
+                    changed = true;
+                }
+                if ui.selectable_label(!is_pixel, "Key down").clicked() && is_pixel {
+                    *condition = Condition::KeyDown {
+                        key: Key(Keycode::LShift),
+                    };
+                    changed = true;
+                }
+            });
+        match condition {
+            Condition::Pixel {
+                x,
+                y,
+                rgb,
+                tolerance,
+            } => {
+                changed |= ui.add(egui::DragValue::new(x).prefix("x: ")).changed();
+                changed |= ui.add(egui::DragValue::new(y).prefix("y: ")).changed();
+                let mut color = [rgb.0, rgb.1, rgb.2];
+                if ui.color_edit_button_srgb(&mut color).changed() {
+                    *rgb = (color[0], color[1], color[2]);
+                    changed = true;
+                }
+                changed |= ui
+                    .add(egui::DragValue::new(tolerance).prefix("±"))
+                    .changed();
+            }
+            Condition::KeyDown { key } => {
+                changed |= self.key_ui(ui, format!("{}:key", id), key, pressed);
+            }
+            // Hand-written files may stack negations
+            inner @ Condition::Not(_) => {
+                changed |= self.condition_ui(ui, format!("{}.not", id), inner, pressed);
+            }
+        }
+        changed
+    }
+
+    /// A button showing the key; clicking it waits for the next key press.
+    fn key_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        id: String,
+        key: &mut Key,
+        pressed: Option<Keycode>,
+    ) -> bool {
+        if self.capturing.as_ref() != Some(&id) {
+            if ui
+                .button(key.0.to_string())
+                .on_hover_text("Click, then press a key")
+                .clicked()
+            {
+                self.capturing = Some(id);
+            }
+            return false;
+        }
+        if let Some(pressed) = pressed {
+            key.0 = pressed;
+            self.capturing = None;
+            return true;
+        }
+        if ui.button("Press a key…").clicked() {
+            self.capturing = None;
+        }
+        false
+    }
+}
+
+pub fn button_combo(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    button: &mut SerializableMouseButton,
+) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id)
+        .width(60.0)
+        .selected_text(format!("{:?}", button))
+        .show_ui(ui, |ui| {
+            for choice in [
+                SerializableMouseButton::Left,
+                SerializableMouseButton::Right,
+                SerializableMouseButton::Middle,
+            ] {
+                changed |= ui
+                    .selectable_value(button, choice, format!("{:?}", choice))
+                    .changed();
+            }
+        });
+    changed
+}
@@ -0,0 +1,418 @@
+use crate::coords::ScreenPoint;
+use crate::drag::DragSettings;
+use crate::mouse_button::SerializableMouseButton;
+use crate::steps::{self, Input, Step};
+use crate::typing::TypeTextSettings;
+use device_query::Keycode;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A keyboard key, stored by its `device_query` name (e.g. `LControl`) so
+/// macro files stay readable and editable by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key(pub Keycode);
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Keycode::from_str(&name)
+            .map(Key)
+            .map_err(|_| de::Error::custom(format!("unknown key {:?}", name)))
+    }
+}
+
+impl Key {
+    /// The key to inject for playback. Letters, digits and punctuation are
+    /// sent as the character on a US layout, matching how `device_query`
+    /// names them by position.
+    pub fn to_enigo(self) -> enigo::Key {
+        use enigo::Key as E;
+        match self.0 {
+            Keycode::Key0 => E::Unicode('0'),
+            Keycode::Key1 => E::Unicode('1'),
+            Keycode::Key2 => E::Unicode('2'),
+            Keycode::Key3 => E::Unicode('3'),
+            Keycode::Key4 => E::Unicode('4'),
+            Keycode::Key5 => E::Unicode('5'),
+            Keycode::Key6 => E::Unicode('6'),
+            Keycode::Key7 => E::Unicode('7'),
+            Keycode::Key8 => E::Unicode('8'),
+            Keycode::Key9 => E::Unicode('9'),
+            Keycode::A => E::Unicode('a'),
+            Keycode::B => E::Unicode('b'),
+            Keycode::C => E::Unicode('c'),
+            Keycode::D => E::Unicode('d'),
+            Keycode::E => E::Unicode('e'),
+            Keycode::F => E::Unicode('f'),
+            Keycode::G => E::Unicode('g'),
+            Keycode::H => E::Unicode('h'),
+            Keycode::I => E::Unicode('i'),
+            Keycode::J => E::Unicode('j'),
+            Keycode::K => E::Unicode('k'),
+            Keycode::L => E::Unicode('l'),
+            Keycode::M => E::Unicode('m'),
+            Keycode::N => E::Unicode('n'),
+            Keycode::O => E::Unicode('o'),
+            Keycode::P => E::Unicode('p'),
+            Keycode::Q => E::Unicode('q'),
+            Keycode::R => E::Unicode('r'),
+            Keycode::S => E::Unicode('s'),
+            Keycode::T => E::Unicode('t'),
+            Keycode::U => E::Unicode('u'),
+            Keycode::V => E::Unicode('v'),
+            Keycode::W => E::Unicode('w'),
+            Keycode::X => E::Unicode('x'),
+            Keycode::Y => E::Unicode('y'),
+            Keycode::Z => E::Unicode('z'),
+            Keycode::F1 => E::F1,
+            Keycode::F2 => E::F2,
+            Keycode::F3 => E::F3,
+            Keycode::F4 => E::F4,
+            Keycode::F5 => E::F5,
+            Keycode::F6 => E::F6,
+            Keycode::F7 => E::F7,
+            Keycode::F8 => E::F8,
+            Keycode::F9 => E::F9,
+            Keycode::F10 => E::F10,
+            Keycode::F11 => E::F11,
+            Keycode::F12 => E::F12,
+            Keycode::F13 => E::F13,
+            Keycode::F14 => E::F14,
+            Keycode::F15 => E::F15,
+            Keycode::F16 => E::F16,
+            Keycode::F17 => E::F17,
+            Keycode::F18 => E::F18,
+            Keycode::F19 => E::F19,
+            Keycode::F20 => E::F20,
+            Keycode::Escape => E::Escape,
+            Keycode::Space => E::Space,
+            Keycode::LControl => E::LControl,
+            Keycode::RControl => E::RControl,
+            Keycode::LShift => E::LShift,
+            Keycode::RShift => E::RShift,
+            Keycode::LAlt | Keycode::RAlt => E::Alt,
+            Keycode::Command | Keycode::RCommand | Keycode::LMeta | Keycode::RMeta => E::Meta,
+            Keycode::LOption | Keycode::ROption => E::Option,
+            Keycode::Enter | Keycode::NumpadEnter => E::Return,
+            Keycode::Up => E::UpArrow,
+            Keycode::Down => E::DownArrow,
+            Keycode::Left => E::LeftArrow,
+            Keycode::Right => E::RightArrow,
+            Keycode::Backspace => E::Backspace,
+            Keycode::CapsLock => E::CapsLock,
+            Keycode::Tab => E::Tab,
+            Keycode::Home => E::Home,
+            Keycode::End => E::End,
+            Keycode::PageUp => E::PageUp,
+            Keycode::PageDown => E::PageDown,
+            #[cfg(not(target_os = "macos"))]
+            Keycode::Insert => E::Insert,
+            // Apple keyboards put Help where Insert usually is
+            #[cfg(target_os = "macos")]
+            Keycode::Insert => E::Help,
+            Keycode::Delete => E::Delete,
+            Keycode::Numpad0 => E::Numpad0,
+            Keycode::Numpad1 => E::Numpad1,
+            Keycode::Numpad2 => E::Numpad2,
+            Keycode::Numpad3 => E::Numpad3,
+            Keycode::Numpad4 => E::Numpad4,
+            Keycode::Numpad5 => E::Numpad5,
+            Keycode::Numpad6 => E::Numpad6,
+            Keycode::Numpad7 => E::Numpad7,
+            Keycode::Numpad8 => E::Numpad8,
+            Keycode::Numpad9 => E::Numpad9,
+            Keycode::NumpadSubtract => E::Subtract,
+            Keycode::NumpadAdd => E::Add,
+            Keycode::NumpadDivide => E::Divide,
+            Keycode::NumpadMultiply => E::Multiply,
+            Keycode::NumpadDecimal => E::Decimal,
+            Keycode::NumpadEquals | Keycode::Equal => E::Unicode('='),
+            Keycode::Grave => E::Unicode('`'),
+            Keycode::Minus => E::Unicode('-'),
+            Keycode::LeftBracket => E::Unicode('['),
+            Keycode::RightBracket => E::Unicode(']'),
+            Keycode::BackSlash => E::Unicode('\\'),
+            Keycode::Semicolon => E::Unicode(';'),
+            Keycode::Apostrophe => E::Unicode('\''),
+            Keycode::Comma => E::Unicode(','),
+            Keycode::Dot => E::Unicode('.'),
+            Keycode::Slash => E::Unicode('/'),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "StoredMacro")]
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+/// A macro as read from `macros.ron`. Recordings from before macros had
+/// steps hold timestamped events instead, which are converted on load.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct StoredMacro {
+    name: String,
+    steps: Vec<Step>,
+    events: Vec<TimedEvent>,
+}
+
+#[derive(Deserialize)]
+enum InputEvent {
+    Move {
+        x: i32,
+        y: i32,
+    },
+    Button {
+        button: SerializableMouseButton,
+        pressed: bool,
+    },
+    Key {
+        key: Key,
+        pressed: bool,
+    },
+}
+
+#[derive(Deserialize)]
+struct TimedEvent {
+    /// Milliseconds since the recording started.
+    at_ms: u64,
+    event: InputEvent,
+}
+
+impl From<StoredMacro> for Macro {
+    fn from(stored: StoredMacro) -> Self {
+        let steps = if stored.steps.is_empty() {
+            steps_from_events(&stored.events)
+        } else {
+            stored.steps
+        };
+        Self {
+            name: stored.name,
+            steps,
+        }
+    }
+}
+
+/// Turns a recording into steps, with the gaps between events as waits.
+fn steps_from_events(events: &[TimedEvent]) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let mut last_ms = events.first().map_or(0, |event| event.at_ms);
+    for timed in events {
+        if timed.at_ms > last_ms {
+            steps.push(Step::Wait {
+                ms: timed.at_ms - last_ms,
+            });
+            last_ms = timed.at_ms;
+        }
+        steps.push(match timed.event {
+            InputEvent::Move { x, y } => Step::Move {
+                to: ScreenPoint::absolute((x, y)),
+            },
+            InputEvent::Button { button, pressed } => Step::press(Input::Button(button), pressed),
+            InputEvent::Key { key, pressed } => Step::press(Input::Key(key), pressed),
+        });
+    }
+    steps
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecorderSettings {
+    pub poll_hz: u32,
+    /// Off records only buttons and keys, which keeps macros small when the
+    /// path between clicks does not matter.
+    pub record_moves: bool,
+}
+
+impl Default for RecorderSettings {
+    fn default() -> Self {
+        Self {
+            poll_hz: 100,
+            record_moves: true,
+        }
+    }
+}
+
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaybackSettings {
+    /// Name of the macro to play.
+    pub selected: String,
+    /// Multiplies playback speed, within `MIN_SPEED..=MAX_SPEED`.
+    pub speed: f64,
+    /// `None` loops until stopped.
+    pub loops: Option<u32>,
+    pub loop_delay_ms: u64,
+    /// Each gap between events is shifted by up to this much either way.
+    pub jitter_ms: u64,
+    pub seed: Option<u64>,
+}
+
+impl Default for PlaybackSettings {
+    fn default() -> Self {
+        Self {
+            selected: String::new(),
+            speed: 1.0,
+            loops: Some(1),
+            loop_delay_ms: 500,
+            jitter_ms: 0,
+            seed: None,
+        }
+    }
+}
+
+impl PlaybackSettings {
+    /// `speed` within `MIN_SPEED..=MAX_SPEED`. A hand-edited NaN or infinity
+    /// plays at normal speed rather than reaching `Duration::div_f64`, which
+    /// panics on it.
+    pub fn clamped_speed(&self) -> f64 {
+        if self.speed.is_finite() {
+            self.speed.clamp(MIN_SPEED, MAX_SPEED)
+        } else {
+            1.0
+        }
+    }
+
+    /// Checks these settings can play `steps`. Looping forever without a
+    /// loop delay needs a wait that always runs, or the macro would replay
+    /// as fast as the machine allows.
+    pub fn validate(&self, steps: &[Step]) -> Result<(), String> {
+        if self.loops.is_none() && self.loop_delay_ms == 0 && !steps::always_waits(steps) {
+            return Err(
+                "a macro that repeats forever needs a loop delay or a Wait that always runs".into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Everything stored in `macros.ron` next to the executable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroLibrary {
+    pub recorder: RecorderSettings,
+    pub playback: PlaybackSettings,
+    /// The Type Text panel, kept here since it types the same way Type
+    /// steps do.
+    pub typing: TypeTextSettings,
+    /// The Drag panel, which drags the same way Drag steps do.
+    pub drag: DragSettings,
+    pub macros: Vec<Macro>,
+}
+
+impl MacroLibrary {
+    fn path() -> PathBuf {
+        env::current_exe()
+            .expect("Failed to get executable path")
+            .parent()
+            .expect("Failed to get executable directory")
+            .join("macros.ron")
+    }
+
+    pub fn load() -> Self {
+        let Ok(text) = fs::read_to_string(Self::path()) else {
+            return Self::default();
+        };
+        ron::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Failed to parse macros: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Ok(text) = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            if let Err(e) = fs::write(Self::path(), text) {
+                eprintln!("Failed to save macros: {}", e);
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Macro> {
+        self.macros.iter().find(|m| m.name == name)
+    }
+
+    /// Adds a macro, replacing any existing one with the same name.
+    pub fn insert(&mut self, new: Macro) {
+        match self.macros.iter_mut().find(|m| m.name == new.name) {
+            Some(existing) => *existing = new,
+            None => self.macros.push(new),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_events_load_as_steps() {
+        let text = r#"(macros: [
+            (name: "old", events: [
+                (at_ms: 0, event: Move(x: 1, y: 2)),
+                (at_ms: 0, event: Button(button: Left, pressed: true)),
+                (at_ms: 40, event: Button(button: Left, pressed: false)),
+                (at_ms: 55, event: Key(key: "Enter", pressed: true)),
+            ]),
+            (name: "new", steps: [Wait(ms: 5)]),
+        ])"#;
+        let library: MacroLibrary = ron::from_str(text).unwrap();
+        let left = Input::Button(SerializableMouseButton::Left);
+        assert_eq!(
+            library.macros[0].steps,
+            [
+                Step::Move {
+                    to: ScreenPoint::absolute((1, 2)),
+                },
+                Step::Press { input: left },
+                Step::Wait { ms: 40 },
+                Step::Release { input: left },
+                Step::Wait { ms: 15 },
+                Step::Press {
+                    input: Input::Key(Key(Keycode::Enter)),
+                },
+            ]
+        );
+        assert_eq!(library.macros[1].steps, [Step::Wait { ms: 5 }]);
+    }
+
+    #[test]
+    fn endless_playback_needs_a_pause() {
+        let clicks = [Step::Click {
+            button: SerializableMouseButton::Left,
+        }];
+        let forever = PlaybackSettings {
+            loops: None,
+            loop_delay_ms: 0,
+            ..PlaybackSettings::default()
+        };
+        assert!(forever.validate(&clicks).is_err());
+        let mut waits = clicks.to_vec();
+        waits.push(Step::Wait { ms: 10 });
+        forever.validate(&waits).unwrap();
+
+        let delayed = PlaybackSettings {
+            loop_delay_ms: 1,
+            ..forever.clone()
+        };
+        delayed.validate(&clicks).unwrap();
+        let counted = PlaybackSettings {
+            loops: Some(1000),
+            ..forever
+        };
+        counted.validate(&clicks).unwrap();
+    }
+}
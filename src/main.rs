@@ -1,8 +1,36 @@
 #![windows_subsystem = "windows"]
 mod app;
+mod backend;
+mod bounds;
+mod chain;
+mod cli;
 mod clicker;
+mod coords;
+mod delay;
+mod drag;
+mod hotkeys;
+mod idle;
+mod macro_editor;
+mod macros;
+mod monitors;
+mod motion;
 mod mouse_button;
 mod mouse_mover;
+mod noise;
+mod overlay;
+mod pattern;
+mod player;
+mod recorder;
+mod rules;
+mod scheduler;
+mod screen;
+mod script;
+mod seed;
+mod steps;
+mod typing;
+mod waypoints;
+mod windows;
+mod xdotool;
 
 use app::MourseApp;
 use eframe::egui::{IconData, ViewportBuilder};
@@ -21,7 +49,35 @@ fn load_icon() -> Arc<IconData> {
     })
 }
 
+/// The GUI subsystem starts without a console, so CLI output would be lost
+/// unless it goes to the console Mourse was started from.
+#[cfg(windows)]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    // Fails harmlessly when there is no parent console, e.g. from Explorer
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
 fn main() -> eframe::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        #[cfg(windows)]
+        attach_parent_console();
+        if let Err(e) = cli::run(&args) {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default()
             .with_inner_size([500.0, 400.0])
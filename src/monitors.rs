@@ -0,0 +1,113 @@
+use crate::coords::Rect;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    pub name: String,
+    /// Top-left corner on the virtual desktop; negative for monitors left of
+    /// or above the primary one.
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub primary: bool,
+}
+
+impl Monitor {
+    pub fn main_display(size: (i32, i32)) -> Self {
+        Self {
+            name: "Main".to_string(),
+            x: 0,
+            y: 0,
+            width: size.0,
+            height: size.1,
+            primary: true,
+        }
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    pub fn label(&self) -> String {
+        format!(
+            "{} {}x{} at ({}, {}){}",
+            self.name,
+            self.width,
+            self.height,
+            self.x,
+            self.y,
+            if self.primary { " *" } else { "" }
+        )
+    }
+
+    pub fn contains(&self, point: (i32, i32)) -> bool {
+        (self.x..self.x + self.width).contains(&point.0)
+            && (self.y..self.y + self.height).contains(&point.1)
+    }
+}
+
+/// The monitor showing `point`, falling back to the primary one.
+pub fn monitor_at(monitors: &[Monitor], point: (i32, i32)) -> Option<&Monitor> {
+    monitors
+        .iter()
+        .find(|monitor| monitor.contains(point))
+        .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+        .or_else(|| monitors.first())
+}
+
+/// Index of the primary monitor, or the first one if none is marked primary.
+pub fn primary_index(monitors: &[Monitor]) -> usize {
+    monitors
+        .iter()
+        .position(|monitor| monitor.primary)
+        .unwrap_or(0)
+}
+
+/// Asks the windowing system for the full monitor layout. Returns `None`
+/// where that is not supported, in which case callers should fall back to
+/// the backend's main display.
+pub fn query_system() -> Option<Vec<Monitor>> {
+    #[cfg(target_os = "linux")]
+    {
+        query_randr().filter(|monitors| !monitors.is_empty())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn query_randr() -> Option<Vec<Monitor>> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::randr::ConnectionExt as _;
+    use x11rb::protocol::xproto::ConnectionExt as _;
+
+    let (conn, screen_num) = x11rb::connect(None).ok()?;
+    let root = conn.setup().roots.get(screen_num)?.root;
+    let reply = conn.randr_get_monitors(root, true).ok()?.reply().ok()?;
+    reply
+        .monitors
+        .iter()
+        .map(|info| {
+            let name = conn.get_atom_name(info.name).ok()?.reply().ok()?.name;
+            Some(Monitor {
+                name: String::from_utf8_lossy(&name).into_owned(),
+                x: info.x as i32,
+                y: info.y as i32,
+                width: info.width as i32,
+                height: info.height as i32,
+                primary: info.primary,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+use crate::backend::InputBackend;
+use enigo::{Coordinate, InputResult};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum MotionStyle {
+    #[default]
+    Instant,
+    Linear,
+    EaseInOut,
+    Bezier,
+}
+
+impl MotionStyle {
+    pub const ALL: [MotionStyle; 4] = [
+        MotionStyle::Instant,
+        MotionStyle::Linear,
+        MotionStyle::EaseInOut,
+        MotionStyle::Bezier,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum CursorRestore {
+    #[default]
+    Off,
+    Instant,
+    Smooth,
+}
+
+impl CursorRestore {
+    /// Puts the cursor back at `home`. `Smooth` follows `glide`, falling back
+    /// to an eased path when the glide style itself is instant.
+    pub fn restore(
+        self,
+        backend: &mut impl InputBackend,
+        home: (i32, i32),
+        glide: GlideSettings,
+        rng: &mut impl Rng,
+    ) {
+        let result = match self {
+            CursorRestore::Off => return,
+            CursorRestore::Instant => backend.move_mouse(home.0, home.1, Coordinate::Abs),
+            CursorRestore::Smooth => {
+                let glide = GlideSettings {
+                    style: match glide.style {
+                        MotionStyle::Instant => MotionStyle::EaseInOut,
+                        style => style,
+                    },
+                    ..glide
+                };
+                let from = backend.location().unwrap_or(home);
+                // The worker's running flag is already cleared, so glide unconditionally
+                glide.glide(backend, from, home, rng, &AtomicBool::new(true))
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to restore cursor: {}", e);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlideSettings {
+    pub style: MotionStyle,
+    pub speed_px_per_s: f64,
+    pub step_hz: u32,
+}
+
+impl Default for GlideSettings {
+    fn default() -> Self {
+        Self {
+            style: MotionStyle::Instant,
+            speed_px_per_s: 800.0,
+            step_hz: 120,
+        }
+    }
+}
+
+impl GlideSettings {
+    /// Intermediate cursor positions from `from` (excluded) to `to` (included),
+    /// one per step at `step_hz`, spread so the cursor travels at the configured speed.
+    pub fn path(&self, from: (i32, i32), to: (i32, i32), rng: &mut impl Rng) -> Vec<(i32, i32)> {
+        let (fx, fy) = (from.0 as f64, from.1 as f64);
+        let (tx, ty) = (to.0 as f64, to.1 as f64);
+        let distance = (tx - fx).hypot(ty - fy);
+        if self.style == MotionStyle::Instant || distance < 1.0 {
+            return vec![to];
+        }
+
+        let seconds = distance / self.speed_px_per_s.max(1.0);
+        let steps = ((seconds * self.step_hz.max(1) as f64).ceil() as usize).max(1);
+
+        // Control points pushed sideways from the straight line give the curve its bow
+        let (nx, ny) = ((fy - ty) / distance, (tx - fx) / distance);
+        let mut control = |along: f64| {
+            let bow = rng.random_range(-0.3..=0.3) * distance;
+            (
+                fx + (tx - fx) * along + nx * bow,
+                fy + (ty - fy) * along + ny * bow,
+            )
+        };
+        let (c1, c2) = if self.style == MotionStyle::Bezier {
+            (control(1.0 / 3.0), control(2.0 / 3.0))
+        } else {
+            ((0.0, 0.0), (0.0, 0.0))
+        };
+
+        (1..=steps)
+            .map(|i| {
+                let t = i as f64 / steps as f64;
+                let (x, y) = match self.style {
+                    MotionStyle::Bezier => {
+                        let u = 1.0 - t;
+                        (
+                            u * u * u * fx
+                                + 3.0 * u * u * t * c1.0
+                                + 3.0 * u * t * t * c2.0
+                                + t * t * t * tx,
+                            u * u * u * fy
+                                + 3.0 * u * u * t * c1.1
+                                + 3.0 * u * t * t * c2.1
+                                + t * t * t * ty,
+                        )
+                    }
+                    MotionStyle::EaseInOut => {
+                        let e = t * t * (3.0 - 2.0 * t);
+                        (fx + (tx - fx) * e, fy + (ty - fy) * e)
+                    }
+                    _ => (fx + (tx - fx) * t, fy + (ty - fy) * t),
+                };
+                (x.round() as i32, y.round() as i32)
+            })
+            .collect()
+    }
+
+    /// Moves the cursor along `path` to `to`, stopping early if `running` is cleared.
+    pub fn glide(
+        &self,
+        backend: &mut impl InputBackend,
+        from: (i32, i32),
+        to: (i32, i32),
+        rng: &mut impl Rng,
+        running: &AtomicBool,
+    ) -> InputResult<()> {
+        let step = Duration::from_secs_f64(1.0 / self.step_hz.max(1) as f64);
+        let path = self.path(from, to, rng);
+        let last = path.len() - 1;
+        for (i, (x, y)) in path.into_iter().enumerate() {
+            backend.move_mouse(x, y, Coordinate::Abs)?;
+            if i < last {
+                if !running.load(Ordering::SeqCst) {
+                    break;
+                }
+                backend.sleep(step);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,18 +1,48 @@
-use enigo::{Coordinate, Enigo, Mouse, Settings};
+use crate::backend::{create_enigo, InputBackend};
+use crate::bounds::{BoundsBehavior, MoveBounds, Region};
+use crate::coords::{CoordSpace, Layout};
+use crate::delay::DelayDistribution;
+use crate::idle::{IdleTracker, YieldGate, YieldSettings};
+use crate::monitors::Monitor;
+use crate::motion::{CursorRestore, GlideSettings, MotionStyle};
+use crate::noise::Perlin;
+use crate::pattern::{
+    JiggleSettings, MovementPattern, PatternCenter, PatternSettings, WanderSettings,
+};
+use crate::scheduler::{DeadlineScheduler, JitterStats};
+use crate::seed::{session_rng, SessionRng};
+use crate::waypoints::{PathCursor, WaypointPath};
+use crate::windows::{WindowState, WindowTarget, WindowTracker};
+use enigo::Coordinate;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct MouseMoverConfig {
     pub move_interval_ms: u64,
     pub max_distance: i32,
     pub random_delay_enabled: bool,
     pub random_delay_min_ms: u64,
     pub random_delay_max_ms: u64,
+    pub delay_distribution: DelayDistribution,
+    pub seed: Option<u64>,
+    pub glide: GlideSettings,
+    pub bounds: MoveBounds,
+    pub bounds_behavior: BoundsBehavior,
+    pub pattern: MovementPattern,
+    pub pattern_settings: PatternSettings,
+    pub jiggle: JiggleSettings,
+    pub yield_to_user: YieldSettings,
+    pub wander: WanderSettings,
+    pub waypoints: WaypointPath,
+    pub restore_cursor: CursorRestore,
+    /// What window bounds and window-relative waypoints are measured against.
+    pub window: WindowTarget,
 }
 
 impl Default for MouseMoverConfig {
@@ -23,6 +53,294 @@ impl Default for MouseMoverConfig {
             random_delay_enabled: false,
             random_delay_min_ms: 0,
             random_delay_max_ms: 200,
+            delay_distribution: DelayDistribution::Uniform,
+            seed: None,
+            glide: GlideSettings::default(),
+            bounds: MoveBounds::Unbounded,
+            bounds_behavior: BoundsBehavior::Reflect,
+            pattern: MovementPattern::RandomJumps,
+            pattern_settings: PatternSettings::default(),
+            jiggle: JiggleSettings::default(),
+            yield_to_user: YieldSettings::default(),
+            wander: WanderSettings::default(),
+            waypoints: WaypointPath::default(),
+            restore_cursor: CursorRestore::Off,
+            window: WindowTarget::Focused,
+        }
+    }
+}
+
+impl MouseMoverConfig {
+    pub fn needs_window(&self) -> bool {
+        match self.pattern {
+            MovementPattern::RandomJumps | MovementPattern::Wander => {
+                self.bounds == MoveBounds::Window
+            }
+            MovementPattern::Waypoints => self
+                .waypoints
+                .waypoints
+                .iter()
+                .any(|w| !w.relative && w.space == CoordSpace::Window),
+            _ => false,
+        }
+    }
+}
+
+pub struct MouseMoverWorker<B: InputBackend> {
+    pub config: MouseMoverConfig,
+    pub seed: u64,
+    pub backend: B,
+    rng: SessionRng,
+    running: Arc<AtomicBool>,
+    region: Option<Region>,
+    anchor: (i32, i32),
+    path_cursor: PathCursor,
+    layout: Layout,
+    windows: Option<WindowTracker>,
+    pattern_center: (f64, f64),
+    phase: f64,
+    idle: Option<IdleTracker>,
+    since_jiggle: Duration,
+    noise: Perlin,
+    elapsed_s: f64,
+    wander_position: (f64, f64),
+    wander_heading_offset: f64,
+}
+
+pub struct MoveTick {
+    pub moved: bool,
+    pub delay: Duration,
+    /// Set when the movement has run its course, e.g. a one-shot waypoint path.
+    pub finished: bool,
+}
+
+impl<B: InputBackend> MouseMoverWorker<B> {
+    pub fn new(config: MouseMoverConfig, backend: B, running: Arc<AtomicBool>) -> Self {
+        let (mut rng, seed) = session_rng(config.seed);
+        let noise = Perlin::new(&mut rng);
+        let anchor = backend.location().unwrap_or_default();
+        let layout = Layout::capture(&backend);
+        let pattern_center = match config.pattern_settings.center {
+            PatternCenter::Cursor => anchor,
+            PatternCenter::Fixed { x, y } => (x, y),
+            PatternCenter::Monitor { index } => {
+                (layout.monitors.get(index).map(Monitor::center)).unwrap_or(anchor)
+            }
+        };
+        Self {
+            region: config.bounds.resolve(anchor, &layout),
+            layout,
+            windows: None,
+            anchor,
+            path_cursor: PathCursor::default(),
+            pattern_center: (pattern_center.0 as f64, pattern_center.1 as f64),
+            phase: 0.0,
+            idle: None,
+            since_jiggle: Duration::from_secs(config.jiggle.every_s),
+            noise,
+            elapsed_s: 0.0,
+            wander_position: (anchor.0 as f64, anchor.1 as f64),
+            wander_heading_offset: 0.0,
+            config,
+            seed,
+            backend,
+            rng,
+            running,
+        }
+    }
+
+    /// Cleans up after the last move, returning the cursor if configured.
+    pub fn finish(&mut self) {
+        let restore = self.config.restore_cursor;
+        let glide = self.config.glide;
+        restore.restore(&mut self.backend, self.anchor, glide, &mut self.rng);
+    }
+
+    pub fn with_idle_tracker(mut self, tracker: IdleTracker) -> Self {
+        self.idle = Some(tracker);
+        self
+    }
+
+    pub fn with_window_tracker(mut self, tracker: WindowTracker) -> Self {
+        self.windows = Some(tracker);
+        self
+    }
+
+    /// Whether moving can go ahead, i.e. the target window is open and
+    /// focused if the bounds or waypoints are relative to it. Also picks up
+    /// any move or resize of the window.
+    pub fn window_ready(&mut self) -> bool {
+        if !self.config.needs_window() {
+            return true;
+        }
+        let Some(tracker) = &mut self.windows else {
+            return true;
+        };
+        match tracker.state() {
+            WindowState::Ready(rect) => {
+                if self.layout.window != Some(rect) {
+                    self.layout.window = Some(rect);
+                    if self.config.bounds == MoveBounds::Window {
+                        self.region = Some(rect.into());
+                    }
+                }
+                true
+            }
+            WindowState::Unfocused | WindowState::Missing => false,
+        }
+    }
+
+    fn move_to(&mut self, from: (i32, i32), to: (i32, i32)) {
+        let glide = self.config.glide;
+        let result = if glide.style == MotionStyle::Instant {
+            self.backend.move_mouse(to.0, to.1, Coordinate::Abs)
+        } else {
+            glide.glide(&mut self.backend, from, to, &mut self.rng, &self.running)
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to move mouse: {}", e);
+        }
+    }
+
+    /// Moves once and returns how long to wait before the next move.
+    pub fn step(&mut self) -> MoveTick {
+        match self.config.pattern {
+            MovementPattern::KeepAwakeJiggle => return self.jiggle_step(),
+            MovementPattern::Waypoints => return self.waypoint_step(),
+            _ => {}
+        }
+        match self.config.pattern {
+            MovementPattern::RandomJumps => self.random_step(),
+            MovementPattern::Wander => self.wander_step(),
+            pattern => self.pattern_step(pattern),
+        }
+
+        let delay = if self.config.random_delay_enabled {
+            self.config.move_interval_ms
+                + self.config.delay_distribution.sample(
+                    &mut self.rng,
+                    self.config.random_delay_min_ms,
+                    self.config.random_delay_max_ms,
+                )
+        } else {
+            self.config.move_interval_ms
+        };
+        let delay = Duration::from_millis(delay);
+        self.phase += delay.as_secs_f64() / self.config.pattern_settings.period_s.max(0.1);
+        self.elapsed_s += delay.as_secs_f64();
+        MoveTick {
+            moved: true,
+            delay,
+            finished: false,
+        }
+    }
+
+    fn waypoint_step(&mut self) -> MoveTick {
+        let path = &self.config.waypoints;
+        let Some(index) = self.path_cursor.next(path.waypoints.len(), path.mode) else {
+            return MoveTick {
+                moved: false,
+                delay: Duration::ZERO,
+                finished: true,
+            };
+        };
+        let waypoint = path.waypoints[index];
+        let delay = Duration::from_millis(waypoint.dwell_ms);
+        let Some(to) = waypoint.resolve(self.anchor, &self.layout) else {
+            return MoveTick {
+                moved: false,
+                delay,
+                finished: false,
+            };
+        };
+        let from = self.backend.location().unwrap_or(to);
+        self.move_to(from, to);
+        if let Some(button) = waypoint.click {
+            if let Err(e) = self.backend.button(button.into(), enigo::Direction::Click) {
+                eprintln!("Failed to click mouse button: {}", e);
+            }
+        }
+        MoveTick {
+            moved: true,
+            delay,
+            finished: false,
+        }
+    }
+
+    fn jiggle_step(&mut self) -> MoveTick {
+        const POLL: Duration = Duration::from_millis(250);
+        let settings = self.config.jiggle;
+        // Without a tracker (e.g. a dry run) the user counts as always idle
+        let idle_for = self
+            .idle
+            .as_mut()
+            .map_or(Duration::MAX, IdleTracker::idle_for);
+        let moved = idle_for >= Duration::from_secs(settings.idle_threshold_s)
+            && self.since_jiggle >= Duration::from_secs(settings.every_s);
+        if moved {
+            let distance = settings.distance_px.max(1);
+            let _ = self.backend.move_mouse(distance, 0, Coordinate::Rel);
+            self.backend.sleep(Duration::from_millis(20));
+            let _ = self.backend.move_mouse(-distance, 0, Coordinate::Rel);
+            self.since_jiggle = Duration::ZERO;
+        }
+        self.since_jiggle += POLL;
+        MoveTick {
+            moved,
+            delay: POLL,
+            finished: false,
+        }
+    }
+
+    fn wander_step(&mut self) {
+        let settings = self.config.wander;
+        let from = self.backend.location().unwrap_or_default();
+        // Follow the user if they moved the cursor away since the last step
+        let (mut x, mut y) = self.wander_position;
+        if (x.round() as i32, y.round() as i32) != from {
+            (x, y) = (from.0 as f64, from.1 as f64);
+        }
+
+        let heading = self.noise.fractal(self.elapsed_s * settings.roughness)
+            * std::f64::consts::TAU
+            + self.wander_heading_offset;
+        let distance = settings.speed_px_per_s * self.config.move_interval_ms as f64 / 1000.0;
+        (x, y) = (x + heading.cos() * distance, y + heading.sin() * distance);
+        if let Some(region) = self.region {
+            if !region.contains(x, y) {
+                // Turn around at the edge instead of scraping along it
+                self.wander_heading_offset += std::f64::consts::PI;
+                (x, y) = region.clamp(x, y);
+            }
+        }
+
+        self.wander_position = (x, y);
+        self.move_to(from, (x.round() as i32, y.round() as i32));
+    }
+
+    fn pattern_step(&mut self, pattern: MovementPattern) {
+        let (ox, oy) = pattern.offset(self.phase);
+        let size = self.config.pattern_settings.size_px;
+        let to = (
+            (self.pattern_center.0 + ox * size).round() as i32,
+            (self.pattern_center.1 + oy * size).round() as i32,
+        );
+        let from = self.backend.location().unwrap_or(to);
+        self.move_to(from, to);
+    }
+
+    fn random_step(&mut self) {
+        let max_distance = self.config.max_distance;
+        let dx = self.rng.random_range(-max_distance..=max_distance);
+        let dy = self.rng.random_range(-max_distance..=max_distance);
+        if self.region.is_none() && self.config.glide.style == MotionStyle::Instant {
+            let _ = self.backend.move_mouse(dx, dy, Coordinate::Rel);
+        } else if let Ok(from) = self.backend.location() {
+            let to = match self.region {
+                Some(region) => region.step(from, (dx, dy), self.config.bounds_behavior),
+                None => (from.0 + dx, from.1 + dy),
+            };
+            self.move_to(from, to);
         }
     }
 }
@@ -32,6 +350,10 @@ pub struct MouseMover {
     pub config: MouseMoverConfig,
     is_moving: Arc<AtomicBool>,
     move_count: Arc<AtomicU64>,
+    jitter_stats: Arc<Mutex<JitterStats>>,
+    last_seed: Arc<AtomicU64>,
+    is_yielding: Arc<AtomicBool>,
+    waiting_for_window: Arc<AtomicBool>,
 }
 
 impl Default for MouseMover {
@@ -46,6 +368,10 @@ impl MouseMover {
             config,
             is_moving: Arc::new(AtomicBool::new(false)),
             move_count: Arc::new(AtomicU64::new(0)),
+            jitter_stats: Arc::new(Mutex::new(JitterStats::default())),
+            last_seed: Arc::new(AtomicU64::new(0)),
+            is_yielding: Arc::new(AtomicBool::new(false)),
+            waiting_for_window: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -54,30 +380,53 @@ impl MouseMover {
             self.is_moving.store(true, Ordering::SeqCst);
             let is_moving = Arc::clone(&self.is_moving);
             let move_count = Arc::clone(&self.move_count);
+            let jitter_stats = Arc::clone(&self.jitter_stats);
+            let last_seed = Arc::clone(&self.last_seed);
+            let is_yielding = Arc::clone(&self.is_yielding);
+            let waiting_for_window = Arc::clone(&self.waiting_for_window);
             let config = self.config.clone();
 
             thread::spawn(move || {
-                let settings = Settings::default();
-                let mut enigo = Enigo::new(&settings).expect("Failed to create Enigo instance");
-                let mut rng = rand::rng();
+                let mut worker =
+                    MouseMoverWorker::new(config, create_enigo(), Arc::clone(&is_moving))
+                        .with_idle_tracker(IdleTracker::new());
+                if worker.config.needs_window() {
+                    match WindowTracker::connect(worker.config.window.clone()) {
+                        Some(tracker) => worker = worker.with_window_tracker(tracker),
+                        None => eprintln!("Failed to open the display to track the target window"),
+                    }
+                }
+                last_seed.store(worker.seed, Ordering::SeqCst);
+                println!("Mouse mover started with seed {}", worker.seed);
+                let mut scheduler = DeadlineScheduler::new(jitter_stats);
+
+                let mut yield_gate = YieldGate::new(worker.config.yield_to_user, is_yielding);
 
                 while is_moving.load(Ordering::SeqCst) {
-                    let dx = rng.random_range(-config.max_distance..=config.max_distance);
-                    let dy = rng.random_range(-config.max_distance..=config.max_distance);
-                    let _ = enigo.move_mouse(dx, dy, Coordinate::Rel);
-                    move_count.fetch_add(1, Ordering::SeqCst);
-
-                    let delay = if config.random_delay_enabled {
-                        config.move_interval_ms
-                            + rng.random_range(
-                                config.random_delay_min_ms..=config.random_delay_max_ms,
-                            )
-                    } else {
-                        config.move_interval_ms
-                    };
-
-                    thread::sleep(Duration::from_millis(delay));
+                    if yield_gate.should_yield() {
+                        thread::sleep(YieldGate::POLL);
+                        scheduler.restart();
+                        continue;
+                    }
+                    let window_ready = worker.window_ready();
+                    waiting_for_window.store(!window_ready, Ordering::SeqCst);
+                    if !window_ready {
+                        thread::sleep(YieldGate::POLL);
+                        scheduler.restart();
+                        continue;
+                    }
+                    let tick = worker.step();
+                    if tick.moved {
+                        move_count.fetch_add(1, Ordering::SeqCst);
+                    }
+                    if tick.finished {
+                        is_moving.store(false, Ordering::SeqCst);
+                        break;
+                    }
+                    scheduler.wait(tick.delay);
                 }
+                waiting_for_window.store(false, Ordering::SeqCst);
+                worker.finish();
             });
         }
     }
@@ -134,6 +483,22 @@ impl MouseMover {
         self.move_count.store(0, Ordering::SeqCst);
     }
 
+    pub fn is_yielding(&self) -> bool {
+        self.is_yielding.load(Ordering::SeqCst)
+    }
+
+    pub fn is_waiting_for_window(&self) -> bool {
+        self.waiting_for_window.load(Ordering::SeqCst)
+    }
+
+    pub fn get_last_seed(&self) -> u64 {
+        self.last_seed.load(Ordering::SeqCst)
+    }
+
+    pub fn get_jitter_stats(&self) -> JitterStats {
+        *self.jitter_stats.lock().unwrap()
+    }
+
     pub fn get_config(&self) -> MouseMoverConfig {
         self.config.clone()
     }
@@ -143,3 +508,39 @@ impl MouseMover {
         self.config = config;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Action, MockBackend};
+    use crate::motion::MotionStyle;
+
+    fn run(pattern: MovementPattern, seed: u64) -> Vec<(Vec<Action>, Duration)> {
+        let config = MouseMoverConfig {
+            pattern,
+            random_delay_enabled: true,
+            glide: GlideSettings {
+                style: MotionStyle::Bezier,
+                ..GlideSettings::default()
+            },
+            seed: Some(seed),
+            ..MouseMoverConfig::default()
+        };
+        let running = Arc::new(AtomicBool::new(true));
+        let mut worker = MouseMoverWorker::new(config, MockBackend::default(), running);
+        (0..200)
+            .map(|_| {
+                let tick = worker.step();
+                (std::mem::take(&mut worker.backend.actions), tick.delay)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_repeats_moves() {
+        for pattern in [MovementPattern::RandomJumps, MovementPattern::Wander] {
+            assert_eq!(run(pattern, 7), run(pattern, 7), "{:?}", pattern);
+            assert_ne!(run(pattern, 7), run(pattern, 8), "{:?}", pattern);
+        }
+    }
+}
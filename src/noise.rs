@@ -0,0 +1,46 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Classic 1D gradient (Perlin) noise. The permutation table comes from the
+/// session RNG, so a seed reproduces the same wandering path.
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(rng);
+        let mut perm = [0; 512];
+        for (i, slot) in perm.iter_mut().enumerate() {
+            *slot = table[i % 256];
+        }
+        Self { perm }
+    }
+
+    /// Smooth noise in roughly -1..=1.
+    pub fn noise(&self, x: f64) -> f64 {
+        let cell = x.floor();
+        let i = (cell as i64).rem_euclid(256) as usize;
+        let t = x - cell;
+        let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let a = gradient(self.perm[i], t);
+        let b = gradient(self.perm[i + 1], t - 1.0);
+        2.0 * (a + fade * (b - a))
+    }
+
+    /// Two octaves of noise; the second adds small wobbles on top of the broad curve.
+    pub fn fractal(&self, x: f64) -> f64 {
+        (self.noise(x) + 0.5 * self.noise(2.0 * x + 17.0)) / 1.5
+    }
+}
+
+fn gradient(hash: u8, t: f64) -> f64 {
+    // Eight gradient slopes between -1 and 1, picked by the hash
+    let slope = 1.0 + (hash & 3) as f64 / 3.0;
+    if hash & 4 == 0 {
+        slope * t / 2.0
+    } else {
+        -slope * t / 2.0
+    }
+}
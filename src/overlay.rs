@@ -0,0 +1,53 @@
+use eframe::egui;
+
+/// Draws `points` (desktop pixels) in a borderless, click-through window
+/// spread over the primary monitor, so a path can be checked before it runs.
+pub fn show_path_overlay(ctx: &egui::Context, points: &[(i32, i32)]) {
+    let Some(monitor_size) = ctx.input(|i| i.viewport().monitor_size) else {
+        return;
+    };
+    let builder = egui::ViewportBuilder::default()
+        .with_title("Mourse Path Overlay")
+        .with_decorations(false)
+        .with_transparent(true)
+        .with_always_on_top()
+        .with_mouse_passthrough(true)
+        .with_taskbar(false)
+        .with_position([0.0, 0.0])
+        .with_inner_size(monitor_size);
+
+    ctx.show_viewport_immediate(
+        egui::ViewportId::from_hash_of("path_overlay"),
+        builder,
+        |ctx, _class| {
+            egui::CentralPanel::default()
+                .frame(egui::Frame::NONE)
+                .show(ctx, |ui| {
+                    let scale = ctx.pixels_per_point();
+                    let points: Vec<egui::Pos2> = points
+                        .iter()
+                        .map(|&(x, y)| egui::pos2(x as f32 / scale, y as f32 / scale))
+                        .collect();
+                    paint_path(ui.painter(), &points, egui::Color32::from_rgb(255, 80, 80));
+                });
+        },
+    );
+}
+
+/// Polyline through `points` with a numbered marker on each one.
+pub fn paint_path(painter: &egui::Painter, points: &[egui::Pos2], color: egui::Color32) {
+    painter.add(egui::Shape::line(
+        points.to_vec(),
+        egui::Stroke::new(2.0, color),
+    ));
+    for (i, &point) in points.iter().enumerate() {
+        painter.circle_filled(point, 5.0, color);
+        painter.text(
+            point + egui::vec2(7.0, -7.0),
+            egui::Align2::LEFT_BOTTOM,
+            (i + 1).to_string(),
+            egui::FontId::proportional(12.0),
+            color,
+        );
+    }
+}
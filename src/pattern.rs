@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum MovementPattern {
+    #[default]
+    RandomJumps,
+    Circle,
+    Square,
+    FigureEight,
+    Lissajous {
+        freq_x: u32,
+        freq_y: u32,
+    },
+    Zigzag {
+        teeth: u32,
+    },
+    /// Spirals out to `size` and back in once per period.
+    Spiral {
+        turns: f64,
+    },
+    /// Nudges the cursor a pixel and back, only while the user is idle.
+    KeepAwakeJiggle,
+    /// Meanders along a smooth noise-driven heading inside the mover bounds.
+    Wander,
+    /// Visits the configured waypoint path.
+    Waypoints,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum PatternCenter {
+    #[default]
+    Cursor,
+    Fixed {
+        x: i32,
+        y: i32,
+    },
+    /// The middle of a monitor, by index in the detected layout.
+    Monitor {
+        index: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PatternSettings {
+    /// Radius, or half the side length, in pixels.
+    pub size_px: f64,
+    /// Seconds for one full loop of the shape.
+    pub period_s: f64,
+    pub center: PatternCenter,
+}
+
+impl Default for PatternSettings {
+    fn default() -> Self {
+        Self {
+            size_px: 100.0,
+            period_s: 4.0,
+            center: PatternCenter::Cursor,
+        }
+    }
+}
+
+impl MovementPattern {
+    pub const ALL: [MovementPattern; 10] = [
+        MovementPattern::RandomJumps,
+        MovementPattern::Circle,
+        MovementPattern::Square,
+        MovementPattern::FigureEight,
+        MovementPattern::Lissajous {
+            freq_x: 3,
+            freq_y: 2,
+        },
+        MovementPattern::Zigzag { teeth: 4 },
+        MovementPattern::Spiral { turns: 3.0 },
+        MovementPattern::KeepAwakeJiggle,
+        MovementPattern::Wander,
+        MovementPattern::Waypoints,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MovementPattern::RandomJumps => "Random Jumps",
+            MovementPattern::Circle => "Circle",
+            MovementPattern::Square => "Square",
+            MovementPattern::FigureEight => "Figure Eight",
+            MovementPattern::Lissajous { .. } => "Lissajous",
+            MovementPattern::Zigzag { .. } => "Zigzag",
+            MovementPattern::Spiral { .. } => "Spiral",
+            MovementPattern::KeepAwakeJiggle => "Keep-Awake Jiggle",
+            MovementPattern::Wander => "Wander",
+            MovementPattern::Waypoints => "Waypoints",
+        }
+    }
+
+    /// Offset from the pattern center at `phase` (in loops, wraps at 1.0),
+    /// on a unit shape spanning -1..=1 on both axes.
+    pub fn offset(&self, phase: f64) -> (f64, f64) {
+        let phase = phase.rem_euclid(1.0);
+        let angle = TAU * phase;
+        match *self {
+            MovementPattern::RandomJumps
+            | MovementPattern::KeepAwakeJiggle
+            | MovementPattern::Wander
+            | MovementPattern::Waypoints => (0.0, 0.0),
+            MovementPattern::Circle => (angle.cos(), angle.sin()),
+            MovementPattern::Square => {
+                // Walk the four sides at constant speed
+                let side = (phase * 4.0).floor();
+                let along = phase * 4.0 - side;
+                let t = 2.0 * along - 1.0;
+                match side as u8 {
+                    0 => (t, -1.0),
+                    1 => (1.0, t),
+                    2 => (-t, 1.0),
+                    _ => (-1.0, -t),
+                }
+            }
+            MovementPattern::FigureEight => (angle.sin(), (2.0 * angle).sin()),
+            MovementPattern::Lissajous { freq_x, freq_y } => (
+                (freq_x.max(1) as f64 * angle).sin(),
+                (freq_y.max(1) as f64 * angle).cos(),
+            ),
+            MovementPattern::Zigzag { teeth } => {
+                // Across and back once per loop, bouncing between top and bottom
+                let teeth = teeth.max(1) as f64;
+                let x = 1.0 - 2.0 * (2.0 * phase - 1.0).abs();
+                let y = 1.0 - 2.0 * ((phase * 2.0 * teeth) % 2.0 - 1.0).abs();
+                (x, y)
+            }
+            MovementPattern::Spiral { turns } => {
+                let radius = 1.0 - (2.0 * phase - 1.0).abs();
+                let angle = angle * turns;
+                (radius * angle.cos(), radius * angle.sin())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JiggleSettings {
+    pub every_s: u64,
+    pub idle_threshold_s: u64,
+    pub distance_px: i32,
+}
+
+impl Default for JiggleSettings {
+    fn default() -> Self {
+        Self {
+            every_s: 30,
+            idle_threshold_s: 60,
+            distance_px: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WanderSettings {
+    pub speed_px_per_s: f64,
+    /// How quickly the heading changes; higher values give twistier paths.
+    pub roughness: f64,
+}
+
+impl Default for WanderSettings {
+    fn default() -> Self {
+        Self {
+            speed_px_per_s: 200.0,
+            roughness: 0.5,
+        }
+    }
+}
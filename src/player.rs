@@ -0,0 +1,657 @@
+use crate::backend::{create_enigo, InputBackend};
+use crate::coords::Layout;
+use crate::drag::{Drag, DragAction};
+use crate::macros::{Key, Macro, PlaybackSettings};
+use crate::mouse_button::SerializableMouseButton;
+use crate::scheduler::{DeadlineScheduler, JitterStats};
+use crate::seed::{session_rng, SessionRng};
+use crate::steps::{Condition, Input, Op, Program, Step};
+use crate::typing::Typist;
+use device_query::{DeviceQuery, DeviceState};
+use enigo::{Axis, Coordinate, Direction, InputResult};
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default)]
+pub struct PlaybackProgress {
+    /// Path of the step about to run, as in validation errors.
+    pub step: String,
+    /// Zero-based index of the current loop.
+    pub loop_index: u32,
+    pub loops: Option<u32>,
+}
+
+pub struct PlayTick {
+    pub delay: Duration,
+    pub finished: bool,
+}
+
+/// Runs a compiled macro one action at a time so playback can be driven by
+/// the scheduler thread or stepped through in a dry run.
+pub struct PlayerWorker<B: InputBackend> {
+    pub name: String,
+    pub settings: PlaybackSettings,
+    pub seed: u64,
+    pub backend: B,
+    program: Program,
+    rng: SessionRng,
+    pc: usize,
+    /// Remaining iterations of each loop, indexed by its `LoopStart` op.
+    counters: Vec<Option<u32>>,
+    key_state: Option<Box<dyn DeviceQuery>>,
+    waited_for_key: Duration,
+    /// The `Type` step being typed, one keystroke per tick.
+    typist: Option<Typist>,
+    /// The `Drag` step being dragged, one move per tick.
+    drag: Option<Drag>,
+    loop_index: u32,
+    // Released on stop so an interrupted macro does not leave a modifier or
+    // button stuck down
+    held_buttons: Vec<SerializableMouseButton>,
+    held_keys: Vec<Key>,
+}
+
+impl<B: InputBackend> PlayerWorker<B> {
+    const KEY_POLL: Duration = Duration::from_millis(20);
+    // Bounds the control flow run between two actions, in case every
+    // branch of an endless loop skips its waits
+    const MAX_OPS_PER_STEP: usize = 10_000;
+
+    pub fn new(name: String, program: Program, settings: PlaybackSettings, backend: B) -> Self {
+        let (rng, seed) = session_rng(settings.seed);
+        Self {
+            name,
+            settings,
+            seed,
+            backend,
+            counters: vec![None; program.ops.len()],
+            program,
+            rng,
+            pc: 0,
+            key_state: None,
+            waited_for_key: Duration::ZERO,
+            typist: None,
+            drag: None,
+            loop_index: 0,
+            held_buttons: Vec::new(),
+            held_keys: Vec::new(),
+        }
+    }
+
+    /// Without key state (e.g. in a dry run) keys count as released and
+    /// waits for a key end straight away.
+    pub fn with_key_state(mut self, key_state: impl DeviceQuery + 'static) -> Self {
+        self.key_state = Some(Box::new(key_state));
+        self
+    }
+
+    pub fn progress(&self) -> PlaybackProgress {
+        PlaybackProgress {
+            step: self.program.paths.get(self.pc).cloned().unwrap_or_default(),
+            loop_index: self.loop_index,
+            loops: self.settings.loops,
+        }
+    }
+
+    /// Runs up to the next action or wait and returns how long to pause
+    /// before the following one.
+    pub fn step(&mut self) -> PlayTick {
+        if let Some(typist) = &mut self.typist {
+            match typist.next(&mut self.rng) {
+                Some((stroke, delay)) => {
+                    if let Err(e) = stroke.send(&mut self.backend) {
+                        eprintln!("Failed to type {:?}: {}", stroke, e);
+                    }
+                    return PlayTick {
+                        delay: delay.div_f64(self.settings.clamped_speed()),
+                        finished: false,
+                    };
+                }
+                None => self.typist = None,
+            }
+        }
+        if let Some(drag) = &mut self.drag {
+            match drag.next() {
+                Some((action, delay)) => {
+                    let button = drag.button;
+                    let result = match action {
+                        DragAction::Press => self.input(Input::Button(button), true),
+                        DragAction::Release => self.input(Input::Button(button), false),
+                        DragAction::Move(..) => action.send(&mut self.backend, button),
+                    };
+                    if let Err(e) = result {
+                        eprintln!("Failed to drag: {}", e);
+                    }
+                    return PlayTick {
+                        delay: delay.div_f64(self.settings.clamped_speed()),
+                        finished: false,
+                    };
+                }
+                None => self.drag = None,
+            }
+        }
+        for _ in 0..Self::MAX_OPS_PER_STEP {
+            if self.loops_done() || self.program.ops.is_empty() {
+                return PlayTick {
+                    delay: Duration::ZERO,
+                    finished: true,
+                };
+            }
+            let Some(op) = self.program.ops.get(self.pc).cloned() else {
+                self.pc = 0;
+                self.loop_index += 1;
+                if self.loops_done() {
+                    continue;
+                }
+                // The loop delay is a pause of its own and is not scaled
+                return PlayTick {
+                    delay: Duration::from_millis(self.settings.loop_delay_ms),
+                    finished: false,
+                };
+            };
+            match op {
+                Op::Do(Step::Wait { ms }) => {
+                    self.pc += 1;
+                    return PlayTick {
+                        delay: self.scaled(ms as f64),
+                        finished: false,
+                    };
+                }
+                Op::Do(Step::WaitForKey { key, timeout_ms }) => {
+                    let timed_out = timeout_ms
+                        .is_some_and(|ms| self.waited_for_key >= Duration::from_millis(ms));
+                    if self.key_state.is_none() || self.is_key_down(key) || timed_out {
+                        self.waited_for_key = Duration::ZERO;
+                        self.pc += 1;
+                        continue;
+                    }
+                    self.waited_for_key += Self::KEY_POLL;
+                    return PlayTick {
+                        delay: Self::KEY_POLL,
+                        finished: false,
+                    };
+                }
+                Op::Do(Step::Type { text, style }) => {
+                    self.pc += 1;
+                    match Typist::new(&text, style) {
+                        Ok(typist) => self.typist = Some(typist),
+                        Err(e) => eprintln!("Failed to type {:?}: {}", text, e),
+                    }
+                    return self.step();
+                }
+                Op::Do(Step::Drag {
+                    button,
+                    from,
+                    to,
+                    glide,
+                    hover_ms,
+                }) => {
+                    self.pc += 1;
+                    let layout = Layout::capture(&self.backend);
+                    match (from.resolve(&layout), to.resolve(&layout)) {
+                        (Some(from), Some(to)) => {
+                            let drag = Drag::new(button, from, to, glide, hover_ms, &mut self.rng);
+                            self.drag = Some(drag);
+                        }
+                        _ => eprintln!(
+                            "Skipping drag from {:?} to {:?}, which is not on screen",
+                            from, to
+                        ),
+                    }
+                    return self.step();
+                }
+                Op::Do(step) => {
+                    self.pc += 1;
+                    self.play(&step);
+                    return PlayTick {
+                        delay: Duration::ZERO,
+                        finished: false,
+                    };
+                }
+                Op::LoopStart { count, .. } => {
+                    self.counters[self.pc] = count;
+                    self.pc += 1;
+                }
+                Op::LoopEnd { start } => {
+                    let remaining = self.counters[start].map(|n| n.saturating_sub(1));
+                    self.counters[start] = remaining;
+                    if remaining == Some(0) {
+                        self.pc += 1;
+                    } else {
+                        self.pc = start + 1;
+                    }
+                }
+                Op::Branch {
+                    condition,
+                    otherwise,
+                } => {
+                    self.pc = if self.check(&condition) {
+                        self.pc + 1
+                    } else {
+                        otherwise
+                    };
+                }
+                Op::Jump { to } => self.pc = to,
+            }
+        }
+        PlayTick {
+            delay: Self::KEY_POLL,
+            finished: false,
+        }
+    }
+
+    fn loops_done(&self) -> bool {
+        self.settings
+            .loops
+            .is_some_and(|loops| self.loop_index >= loops)
+    }
+
+    fn scaled(&mut self, gap_ms: f64) -> Duration {
+        let speed = self.settings.clamped_speed();
+        let jitter = self.settings.jitter_ms as f64;
+        let offset = if jitter > 0.0 {
+            self.rng.random_range(-jitter..=jitter)
+        } else {
+            0.0
+        };
+        Duration::from_secs_f64((gap_ms / speed + offset).max(0.0) / 1000.0)
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.key_state
+            .as_ref()
+            .is_some_and(|state| state.get_keys().contains(&key.0))
+    }
+
+    fn check(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Pixel {
+                x,
+                y,
+                rgb,
+                tolerance,
+            } => self.backend.pixel(*x, *y).is_some_and(|pixel| {
+                [rgb.0, rgb.1, rgb.2]
+                    .iter()
+                    .zip(pixel)
+                    .all(|(want, got)| want.abs_diff(got) <= *tolerance)
+            }),
+            Condition::KeyDown { key } => self.is_key_down(*key),
+            Condition::Not(inner) => !self.check(inner),
+        }
+    }
+
+    fn play(&mut self, step: &Step) {
+        let result = match step {
+            Step::Move { to } => match to.resolve(&Layout::capture(&self.backend)) {
+                Some((x, y)) => self.backend.move_mouse(x, y, Coordinate::Abs),
+                None => {
+                    eprintln!("Skipping move to {:?}, which is not on screen", to);
+                    Ok(())
+                }
+            },
+            Step::Click { button } => self.backend.button((*button).into(), Direction::Click),
+            Step::Press { input } => self.input(*input, true),
+            Step::Release { input } => self.input(*input, false),
+            Step::Scroll { amount, horizontal } => {
+                let axis = if *horizontal {
+                    Axis::Horizontal
+                } else {
+                    Axis::Vertical
+                };
+                self.backend.scroll(*amount, axis)
+            }
+            // Control flow, waits, typing and drags never reach here
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to play {:?}: {}", step, e);
+        }
+    }
+
+    fn input(&mut self, input: Input, pressed: bool) -> InputResult<()> {
+        match input {
+            Input::Button(button) => {
+                self.held_buttons.retain(|&held| held != button);
+                if pressed {
+                    self.held_buttons.push(button);
+                }
+                self.backend.button(button.into(), direction(pressed))
+            }
+            Input::Key(key) => {
+                self.held_keys.retain(|&held| held != key);
+                if pressed {
+                    self.held_keys.push(key);
+                }
+                self.backend.key(key.to_enigo(), direction(pressed))
+            }
+        }
+    }
+
+    /// Releases anything the macro left pressed.
+    pub fn finish(&mut self) {
+        for button in std::mem::take(&mut self.held_buttons) {
+            if let Err(e) = self.backend.button(button.into(), Direction::Release) {
+                eprintln!("Failed to release mouse button: {}", e);
+            }
+        }
+        for key in std::mem::take(&mut self.held_keys) {
+            if let Err(e) = self.backend.key(key.to_enigo(), Direction::Release) {
+                eprintln!("Failed to release key: {}", e);
+            }
+        }
+    }
+}
+
+fn direction(pressed: bool) -> Direction {
+    if pressed {
+        Direction::Press
+    } else {
+        Direction::Release
+    }
+}
+
+#[derive(Debug)]
+pub struct MacroPlayer {
+    is_playing: Arc<AtomicBool>,
+    event_count: Arc<AtomicU64>,
+    progress: Arc<Mutex<PlaybackProgress>>,
+    jitter_stats: Arc<Mutex<JitterStats>>,
+    last_seed: Arc<AtomicU64>,
+}
+
+impl Default for MacroPlayer {
+    fn default() -> Self {
+        Self {
+            is_playing: Arc::new(AtomicBool::new(false)),
+            event_count: Arc::new(AtomicU64::new(0)),
+            progress: Arc::new(Mutex::new(PlaybackProgress::default())),
+            jitter_stats: Arc::new(Mutex::new(JitterStats::default())),
+            last_seed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl MacroPlayer {
+    /// Validates the macro and starts playing it, unless already playing.
+    pub fn start_playing(
+        &mut self,
+        recording: &Macro,
+        settings: PlaybackSettings,
+    ) -> Result<(), String> {
+        let program = Program::compile(&recording.steps).map_err(|e| e.to_string())?;
+        settings.validate(&recording.steps)?;
+        let name = recording.name.clone();
+        if !self.is_playing.load(Ordering::SeqCst) {
+            self.is_playing.store(true, Ordering::SeqCst);
+            let is_playing = Arc::clone(&self.is_playing);
+            let event_count = Arc::clone(&self.event_count);
+            let progress = Arc::clone(&self.progress);
+            let jitter_stats = Arc::clone(&self.jitter_stats);
+            let last_seed = Arc::clone(&self.last_seed);
+
+            thread::spawn(move || {
+                let mut worker = PlayerWorker::new(name, program, settings, create_enigo())
+                    .with_key_state(DeviceState::new());
+                last_seed.store(worker.seed, Ordering::SeqCst);
+                println!(
+                    "Playing macro \"{}\" with seed {}",
+                    worker.name, worker.seed
+                );
+                *progress.lock().unwrap() = worker.progress();
+                let mut scheduler = DeadlineScheduler::new(jitter_stats).precise();
+
+                while is_playing.load(Ordering::SeqCst) {
+                    let tick = worker.step();
+                    *progress.lock().unwrap() = worker.progress();
+                    if tick.finished {
+                        is_playing.store(false, Ordering::SeqCst);
+                        break;
+                    }
+                    event_count.fetch_add(1, Ordering::SeqCst);
+                    scheduler.wait(tick.delay);
+                }
+                worker.finish();
+            });
+        }
+        Ok(())
+    }
+
+    pub fn stop_playing(&mut self) {
+        self.is_playing.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing.load(Ordering::SeqCst)
+    }
+
+    pub fn get_event_count(&self) -> u64 {
+        self.event_count.load(Ordering::SeqCst)
+    }
+
+    pub fn reset_event_count(&self) {
+        self.event_count.store(0, Ordering::SeqCst);
+    }
+
+    pub fn get_progress(&self) -> PlaybackProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn get_last_seed(&self) -> u64 {
+        self.last_seed.load(Ordering::SeqCst)
+    }
+
+    pub fn get_jitter_stats(&self) -> JitterStats {
+        *self.jitter_stats.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Action, MockBackend};
+    use crate::macros::MAX_SPEED;
+    use device_query::{Keycode, MouseState};
+    use enigo::Button;
+
+    /// Holds a fixed set of keys instead of reading the keyboard.
+    struct HeldKeys(Vec<Keycode>);
+
+    impl DeviceQuery for HeldKeys {
+        fn get_mouse(&self) -> MouseState {
+            MouseState::default()
+        }
+
+        fn get_keys(&self) -> Vec<Keycode> {
+            self.0.clone()
+        }
+    }
+
+    fn worker(steps: &[Step], settings: PlaybackSettings) -> PlayerWorker<MockBackend> {
+        let program = Program::compile(steps).unwrap();
+        let settings = PlaybackSettings {
+            seed: Some(7),
+            ..settings
+        };
+        PlayerWorker::new("test".into(), program, settings, MockBackend::default())
+    }
+
+    /// Steps until the macro finishes and returns the pause after each tick.
+    fn run(worker: &mut PlayerWorker<MockBackend>) -> Vec<Duration> {
+        let mut delays = Vec::new();
+        for _ in 0..10_000 {
+            let tick = worker.step();
+            if tick.finished {
+                return delays;
+            }
+            delays.push(tick.delay);
+        }
+        panic!("macro did not finish");
+    }
+
+    fn click(button: SerializableMouseButton) -> Step {
+        Step::Click { button }
+    }
+
+    fn clicked(button: Button) -> Action {
+        Action::Button(button, Direction::Click)
+    }
+
+    fn unless_f1(then: Vec<Step>) -> Step {
+        Step::If {
+            condition: Condition::Not(Box::new(Condition::KeyDown {
+                key: Key(Keycode::F1),
+            })),
+            then,
+            otherwise: Vec::new(),
+        }
+    }
+
+    fn break_out(label: &str) -> Step {
+        Step::Break {
+            label: label.into(),
+        }
+    }
+
+    #[test]
+    fn loops_repeat_and_breaks_leave_them() {
+        let steps = [Step::Loop {
+            label: "outer".into(),
+            count: Some(3),
+            steps: vec![
+                click(SerializableMouseButton::Left),
+                Step::Loop {
+                    label: "inner".into(),
+                    count: Some(5),
+                    steps: vec![
+                        click(SerializableMouseButton::Right),
+                        unless_f1(vec![break_out("inner")]),
+                    ],
+                },
+            ],
+        }];
+        let settings = PlaybackSettings {
+            loops: Some(2),
+            ..PlaybackSettings::default()
+        };
+        let mut player = worker(&steps, settings);
+        run(&mut player);
+        let once = vec![clicked(Button::Left), clicked(Button::Right)];
+        assert_eq!(player.backend.actions, vec![once; 6].concat());
+
+        // Breaking an outer loop from inside an inner one leaves both
+        let steps = [Step::Loop {
+            label: "outer".into(),
+            count: Some(4),
+            steps: vec![
+                click(SerializableMouseButton::Left),
+                Step::Loop {
+                    label: "inner".into(),
+                    count: Some(4),
+                    steps: vec![unless_f1(vec![break_out("outer")])],
+                },
+                click(SerializableMouseButton::Right),
+            ],
+        }];
+        let mut player = worker(&steps, PlaybackSettings::default());
+        run(&mut player);
+        assert_eq!(player.backend.actions, [clicked(Button::Left)]);
+    }
+
+    #[test]
+    fn speed_scales_waits() {
+        let steps = [Step::Wait { ms: 100 }];
+        let first_wait = |speed: f64| {
+            let settings = PlaybackSettings {
+                speed,
+                ..PlaybackSettings::default()
+            };
+            worker(&steps, settings).step().delay
+        };
+        assert_eq!(first_wait(1.0), Duration::from_millis(100));
+        assert_eq!(first_wait(2.0), Duration::from_millis(50));
+        assert_eq!(
+            first_wait(MAX_SPEED * 10.0),
+            Duration::from_secs_f64(0.1 / MAX_SPEED)
+        );
+        // Hand-edited values must not panic the playback thread
+        assert_eq!(first_wait(f64::NAN), Duration::from_millis(100));
+        assert_eq!(first_wait(f64::INFINITY), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let settings = PlaybackSettings {
+            loops: Some(500),
+            loop_delay_ms: 0,
+            jitter_ms: 20,
+            ..PlaybackSettings::default()
+        };
+        let mut player = worker(&[Step::Wait { ms: 100 }], settings);
+        let waits: Vec<Duration> = run(&mut player)
+            .into_iter()
+            .filter(|delay| !delay.is_zero())
+            .collect();
+        assert_eq!(waits.len(), 500);
+        let (low, high) = (Duration::from_millis(80), Duration::from_millis(120));
+        assert!(waits.iter().all(|wait| (low..=high).contains(wait)));
+        assert!(waits.iter().any(|wait| *wait < Duration::from_millis(95)));
+        assert!(waits.iter().any(|wait| *wait > Duration::from_millis(105)));
+    }
+
+    #[test]
+    fn wait_for_key_polls_until_pressed_or_timed_out() {
+        let steps = [
+            Step::WaitForKey {
+                key: Key(Keycode::F1),
+                timeout_ms: Some(100),
+            },
+            click(SerializableMouseButton::Left),
+        ];
+        let poll = PlayerWorker::<MockBackend>::KEY_POLL;
+
+        let mut player =
+            worker(&steps, PlaybackSettings::default()).with_key_state(HeldKeys(Vec::new()));
+        let delays = run(&mut player);
+        assert_eq!(delays, [poll, poll, poll, poll, poll, Duration::ZERO]);
+        assert_eq!(player.backend.actions, [clicked(Button::Left)]);
+
+        let mut player =
+            worker(&steps, PlaybackSettings::default()).with_key_state(HeldKeys(vec![Keycode::F1]));
+        assert_eq!(run(&mut player), [Duration::ZERO]);
+
+        // Dry runs have no keyboard to wait for
+        let mut player = worker(&steps, PlaybackSettings::default());
+        assert_eq!(run(&mut player), [Duration::ZERO]);
+    }
+
+    #[test]
+    fn finish_releases_held_inputs() {
+        let shift = Input::Key(Key(Keycode::LShift));
+        let control = Input::Key(Key(Keycode::LControl));
+        let steps = [
+            Step::Press { input: shift },
+            Step::Press {
+                input: Input::Button(SerializableMouseButton::Left),
+            },
+            Step::Press { input: control },
+            Step::Release { input: control },
+        ];
+        let mut player = worker(&steps, PlaybackSettings::default());
+        for _ in 0..steps.len() {
+            player.step();
+        }
+        player.backend.actions.clear();
+        player.finish();
+        assert_eq!(
+            player.backend.actions,
+            [
+                Action::Button(Button::Left, Direction::Release),
+                Action::Key(Key(Keycode::LShift).to_enigo(), Direction::Release),
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use crate::coords::ScreenPoint;
+use crate::macros::{Key, Macro, RecorderSettings};
+use crate::mouse_button::SerializableMouseButton;
+use crate::steps::{Input, Step};
+use device_query::{DeviceQuery, DeviceState, Keycode, MouseState};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// device_query numbers buttons from 1 like the platform does, and the
+// platforms disagree on whether 2 is the middle or the right button
+#[cfg(target_os = "windows")]
+const BUTTONS: [(usize, SerializableMouseButton); 3] = [
+    (1, SerializableMouseButton::Left),
+    (2, SerializableMouseButton::Right),
+    (3, SerializableMouseButton::Middle),
+];
+#[cfg(not(target_os = "windows"))]
+const BUTTONS: [(usize, SerializableMouseButton); 3] = [
+    (1, SerializableMouseButton::Left),
+    (2, SerializableMouseButton::Middle),
+    (3, SerializableMouseButton::Right),
+];
+
+/// Captures real mouse and keyboard input by polling it on a background
+/// thread into macro steps, with the gaps between inputs as waits. Mourse's
+/// own hotkeys are left out of the recording.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    is_recording: Arc<AtomicBool>,
+    steps: Arc<Mutex<Vec<Step>>>,
+}
+
+impl Recorder {
+    /// Records until stopped, leaving out the `hotkeys` held down.
+    pub fn start_recording(&mut self, settings: RecorderSettings, hotkeys: Vec<Keycode>) {
+        if self.is_recording.load(Ordering::SeqCst) {
+            return;
+        }
+        self.is_recording.store(true, Ordering::SeqCst);
+        self.steps.lock().unwrap().clear();
+        let is_recording = Arc::clone(&self.is_recording);
+        let steps = Arc::clone(&self.steps);
+
+        thread::spawn(move || {
+            let interval = Duration::from_secs_f64(1.0 / settings.poll_hz.max(1) as f64);
+            let device_state = DeviceState::new();
+            let start = Instant::now();
+            let mut last_mouse = device_state.get_mouse();
+            let mut last_keys = user_keys(&device_state, &hotkeys);
+            // Playback should begin where the recording did
+            let mut pending = vec![move_to(&last_mouse)];
+            let mut last_ms = None;
+
+            loop {
+                {
+                    let mut steps = steps.lock().unwrap();
+                    if !is_recording.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if !pending.is_empty() {
+                        let at_ms = start.elapsed().as_millis() as u64;
+                        if let Some(gap) = last_ms.map(|last| at_ms - last).filter(|&gap| gap > 0) {
+                            steps.push(Step::Wait { ms: gap });
+                        }
+                        last_ms = Some(at_ms);
+                        steps.append(&mut pending);
+                    }
+                }
+                thread::sleep(interval);
+
+                let mouse = device_state.get_mouse();
+                let keys = user_keys(&device_state, &hotkeys);
+                if settings.record_moves && mouse.coords != last_mouse.coords {
+                    pending.push(move_to(&mouse));
+                }
+                for (index, button) in BUTTONS {
+                    let pressed = is_pressed(&mouse, index);
+                    if pressed != is_pressed(&last_mouse, index) {
+                        // Without move recording a click still has to land
+                        // in the right place
+                        if !settings.record_moves {
+                            pending.push(move_to(&mouse));
+                        }
+                        pending.push(Step::press(Input::Button(button), pressed));
+                    }
+                }
+                for &key in keys.iter().filter(|key| !last_keys.contains(key)) {
+                    pending.push(Step::press(Input::Key(Key(key)), true));
+                }
+                for &key in last_keys.iter().filter(|key| !keys.contains(key)) {
+                    pending.push(Step::press(Input::Key(Key(key)), false));
+                }
+                last_mouse = mouse;
+                last_keys = keys;
+            }
+        });
+    }
+
+    /// Stops recording and returns what was captured under `name`.
+    pub fn finish_recording(&mut self, name: &str) -> Macro {
+        let steps = {
+            let mut steps = self.steps.lock().unwrap();
+            self.is_recording.store(false, Ordering::SeqCst);
+            mem::take(&mut *steps)
+        };
+        Macro {
+            name: name.to_string(),
+            steps,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.is_recording.load(Ordering::SeqCst)
+    }
+
+    pub fn get_step_count(&self) -> usize {
+        self.steps.lock().unwrap().len()
+    }
+}
+
+fn user_keys(device_state: &DeviceState, hotkeys: &[Keycode]) -> Vec<Keycode> {
+    let mut keys = device_state.get_keys();
+    keys.retain(|key| !hotkeys.contains(key));
+    keys
+}
+
+fn move_to(mouse: &MouseState) -> Step {
+    Step::Move {
+        to: ScreenPoint::absolute(mouse.coords),
+    }
+}
+
+fn is_pressed(mouse: &MouseState, index: usize) -> bool {
+    mouse.button_pressed.get(index).copied().unwrap_or(false)
+}
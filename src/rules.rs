@@ -0,0 +1,644 @@
+use crate::clicker::ClickerConfig;
+use crate::hotkeys;
+use crate::idle::IdleTracker;
+use crate::macros::Key;
+use crate::mouse_mover::MouseMoverConfig;
+use crate::windows::{FocusWatcher, WindowTarget};
+use chrono::{Local, Timelike};
+use device_query::{DeviceQuery, DeviceState, Keycode};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Saved clicker and mover settings, and which of the two were running
+/// when the profile was saved.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub clicker: ClickerConfig,
+    pub mover: MouseMoverConfig,
+    pub clicking: bool,
+    pub moving: bool,
+}
+
+/// The event a rule waits for. A trigger holds for as long as its
+/// condition does; the rule fires when it starts holding.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Trigger {
+    /// No mouse movement or key presses for this long. Clicks, hotkeys and
+    /// Mourse's own cursor moves do not count as input.
+    Idle {
+        secs: u64,
+    },
+    KeyHeld {
+        key: Key,
+    },
+    /// Every day during this minute, local time.
+    At {
+        hour: u32,
+        minute: u32,
+    },
+    /// The focused window's title contains this text, ignoring case.
+    WindowTitle {
+        pattern: String,
+    },
+}
+
+impl Trigger {
+    pub const ALL: [Trigger; 4] = [
+        Trigger::Idle { secs: 180 },
+        Trigger::KeyHeld {
+            key: Key(Keycode::LAlt),
+        },
+        Trigger::At { hour: 9, minute: 0 },
+        Trigger::WindowTitle {
+            pattern: String::new(),
+        },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Trigger::Idle { .. } => "Idle for",
+            Trigger::KeyHeld { .. } => "Key held",
+            Trigger::At { .. } => "Daily at",
+            Trigger::WindowTitle { .. } => "Window title has",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Automation {
+    Clicker,
+    Mover,
+    /// The chain selected in the Chains panel.
+    Chain,
+}
+
+impl Automation {
+    pub const ALL: [Automation; 3] = [Automation::Clicker, Automation::Mover, Automation::Chain];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Automation::Clicker => "Clicker",
+            Automation::Mover => "Mover",
+            Automation::Chain => "Chain",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RuleAction {
+    Start(Automation),
+    Stop(Automation),
+    /// Loads a profile's settings. Automations already running keep the
+    /// settings they started with.
+    SwitchProfile {
+        name: String,
+    },
+    /// Loads a profile's settings and starts what was running when it was
+    /// saved.
+    StartProfile {
+        name: String,
+    },
+}
+
+impl RuleAction {
+    pub const ALL: [RuleAction; 4] = [
+        RuleAction::Start(Automation::Clicker),
+        RuleAction::Stop(Automation::Clicker),
+        RuleAction::SwitchProfile {
+            name: String::new(),
+        },
+        RuleAction::StartProfile {
+            name: String::new(),
+        },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuleAction::Start(_) => "Start",
+            RuleAction::Stop(_) => "Stop",
+            RuleAction::SwitchProfile { .. } => "Switch profile",
+            RuleAction::StartProfile { .. } => "Start profile",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    pub trigger: Trigger,
+    pub action: RuleAction,
+    /// Stops what the action started once the trigger no longer holds, so
+    /// "while idle, jiggle" ends when the user comes back.
+    #[serde(default)]
+    pub revert: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Rule {
+    /// Whether `revert` means anything for this rule. Only starts can be
+    /// undone, and a daily time has no useful end.
+    pub fn can_revert(&self) -> bool {
+        matches!(
+            self.action,
+            RuleAction::Start(_) | RuleAction::StartProfile { .. }
+        ) && !matches!(self.trigger, Trigger::At { .. })
+    }
+
+    /// What to stop once the trigger no longer holds: whatever the action
+    /// started, if the rule reverts at all.
+    pub fn undo(&self, library: &RuleLibrary) -> Vec<Automation> {
+        if !self.revert || !self.can_revert() {
+            return Vec::new();
+        }
+        match &self.action {
+            RuleAction::Start(automation) => vec![*automation],
+            RuleAction::StartProfile { name } => library
+                .profile(name)
+                .map(|profile| {
+                    let started = [
+                        (profile.clicking, Automation::Clicker),
+                        (profile.moving, Automation::Mover),
+                    ];
+                    started
+                        .into_iter()
+                        .filter_map(|(running, automation)| running.then_some(automation))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            RuleAction::Stop(_) | RuleAction::SwitchProfile { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Everything stored in `rules.ron` next to the executable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleLibrary {
+    /// Whether the monitor runs, and so whether any rule can fire.
+    pub watching: bool,
+    pub rules: Vec<Rule>,
+    pub profiles: Vec<Profile>,
+}
+
+impl RuleLibrary {
+    fn path() -> PathBuf {
+        env::current_exe()
+            .expect("Failed to get executable path")
+            .parent()
+            .expect("Failed to get executable directory")
+            .join("rules.ron")
+    }
+
+    pub fn load() -> Self {
+        let Ok(text) = fs::read_to_string(Self::path()) else {
+            return Self::default();
+        };
+        ron::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Failed to parse rules: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Ok(text) = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            if let Err(e) = fs::write(Self::path(), text) {
+                eprintln!("Failed to save rules: {}", e);
+            }
+        }
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Adds a profile, replacing any existing one with the same name.
+    pub fn insert_profile(&mut self, new: Profile) {
+        match self.profiles.iter_mut().find(|p| p.name == new.name) {
+            Some(existing) => *existing = new,
+            None => self.profiles.push(new),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleState {
+    Disabled,
+    /// Waiting for the trigger.
+    Armed,
+    /// The trigger holds and the action has been sent.
+    Fired,
+    /// The trigger cannot be checked here, such as window titles without
+    /// an X11 display.
+    Unavailable,
+}
+
+impl RuleState {
+    pub fn name(self) -> &'static str {
+        match self {
+            RuleState::Disabled => "Disabled",
+            RuleState::Armed => "Armed",
+            RuleState::Fired => "Fired",
+            RuleState::Unavailable => "Unavailable",
+        }
+    }
+}
+
+/// A rule's trigger started (`fired`) or stopped holding. `rule` indexes
+/// the rules last given to the monitor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuleEvent {
+    pub rule: usize,
+    pub fired: bool,
+}
+
+/// What the monitor reads once per poll and checks every trigger against.
+struct Inputs {
+    idle: Duration,
+    keys: Vec<Keycode>,
+    hour: u32,
+    minute: u32,
+}
+
+/// A rule's state between polls.
+struct Watch {
+    holding: bool,
+    focus: Option<FocusWatcher>,
+}
+
+impl Watch {
+    fn new(rule: &Rule) -> Self {
+        let focus = match &rule.trigger {
+            Trigger::WindowTitle { .. } if rule.enabled => FocusWatcher::connect(),
+            _ => None,
+        };
+        Self {
+            holding: false,
+            focus,
+        }
+    }
+
+    /// Whether the trigger holds, or `None` if it cannot be checked.
+    fn holds(&mut self, trigger: &Trigger, inputs: &Inputs) -> Option<bool> {
+        Some(match trigger {
+            Trigger::Idle { secs } => inputs.idle >= Duration::from_secs(*secs),
+            Trigger::KeyHeld { key } => inputs.keys.contains(&key.0),
+            Trigger::At { hour, minute } => (inputs.hour, inputs.minute) == (*hour, *minute),
+            // An empty pattern matches no window, so needs no display
+            Trigger::WindowTitle { pattern } if pattern.trim().is_empty() => false,
+            // Read the title afresh each poll, since it changes with focus
+            // and with the page or document shown in the same window
+            Trigger::WindowTitle { pattern } => self
+                .focus
+                .as_ref()?
+                .focused()
+                .is_some_and(|info| WindowTarget::Title(pattern.clone()).matches(&info)),
+        })
+    }
+}
+
+/// Watches for `update`, keeping the state of each rule that kept its place,
+/// trigger and enabled flag, and re-arming the rest.
+fn swap_watches(rules: &[Rule], watches: Vec<Watch>, update: &[Rule]) -> Vec<Watch> {
+    let mut old: Vec<Option<Watch>> = watches.into_iter().map(Some).collect();
+    update
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| {
+            let kept = rules
+                .get(i)
+                .is_some_and(|r| r.enabled == rule.enabled && r.trigger == rule.trigger);
+            old.get_mut(i)
+                .and_then(Option::take)
+                .filter(|_| kept)
+                .unwrap_or_else(|| Watch::new(rule))
+        })
+        .collect()
+}
+
+/// Checks every rule against one poll's inputs, returning each rule's state
+/// and the triggers that started or stopped holding.
+fn evaluate(
+    rules: &[Rule],
+    watches: &mut [Watch],
+    inputs: &Inputs,
+) -> (Vec<RuleState>, Vec<RuleEvent>) {
+    let mut changes = Vec::new();
+    let states = rules
+        .iter()
+        .zip(watches)
+        .enumerate()
+        .map(|(i, (rule, watch))| {
+            if !rule.enabled {
+                return RuleState::Disabled;
+            }
+            let Some(holds) = watch.holds(&rule.trigger, inputs) else {
+                return RuleState::Unavailable;
+            };
+            if holds != watch.holding {
+                watch.holding = holds;
+                changes.push(RuleEvent {
+                    rule: i,
+                    fired: holds,
+                });
+            }
+            if holds {
+                RuleState::Fired
+            } else {
+                RuleState::Armed
+            }
+        })
+        .collect();
+    (states, changes)
+}
+
+/// Checks the rules on a background thread and queues an event whenever a
+/// trigger starts or stops holding. The app applies the events, since it
+/// owns the automations they start and stop.
+#[derive(Debug)]
+pub struct RuleMonitor {
+    is_monitoring: Arc<AtomicBool>,
+    /// Rules waiting for the thread to pick them up.
+    pending: Arc<Mutex<Option<Vec<Rule>>>>,
+    states: Arc<Mutex<Vec<RuleState>>>,
+    events: Arc<Mutex<Vec<RuleEvent>>>,
+}
+
+impl Default for RuleMonitor {
+    fn default() -> Self {
+        Self {
+            is_monitoring: Arc::new(AtomicBool::new(false)),
+            pending: Arc::new(Mutex::new(None)),
+            states: Arc::new(Mutex::new(Vec::new())),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl RuleMonitor {
+    const POLL: Duration = Duration::from_millis(100);
+
+    pub fn start_monitoring(&mut self, rules: Vec<Rule>) {
+        self.set_rules(rules);
+        if !self.is_monitoring.load(Ordering::SeqCst) {
+            self.is_monitoring.store(true, Ordering::SeqCst);
+            let is_monitoring = Arc::clone(&self.is_monitoring);
+            let pending = Arc::clone(&self.pending);
+            let states = Arc::clone(&self.states);
+            let events = Arc::clone(&self.events);
+
+            thread::spawn(move || {
+                let device_state = DeviceState::new();
+                let mut idle = IdleTracker::new()
+                    .ignoring_buttons()
+                    .ignoring_keys(hotkeys::ALL);
+                let mut rules = Vec::new();
+                let mut watches: Vec<Watch> = Vec::new();
+
+                while is_monitoring.load(Ordering::SeqCst) {
+                    if let Some(update) = pending.lock().unwrap().take() {
+                        watches = swap_watches(&rules, watches, &update);
+                        rules = update;
+                    }
+                    let now = Local::now();
+                    let inputs = Inputs {
+                        idle: idle.idle_for(),
+                        keys: device_state.get_keys(),
+                        hour: now.hour(),
+                        minute: now.minute(),
+                    };
+
+                    let (next, changes) = evaluate(&rules, &mut watches, &inputs);
+                    *states.lock().unwrap() = next;
+                    // Events about rules that have just been replaced would
+                    // point at the wrong rules
+                    if pending.lock().unwrap().is_none() {
+                        events.lock().unwrap().extend(changes);
+                    }
+                    thread::sleep(Self::POLL);
+                }
+                states.lock().unwrap().clear();
+            });
+        }
+    }
+
+    pub fn stop_monitoring(&mut self) {
+        self.is_monitoring.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_monitoring(&self) -> bool {
+        self.is_monitoring.load(Ordering::SeqCst)
+    }
+
+    /// Replaces the rules being checked. A rule that kept its place,
+    /// trigger and enabled flag keeps its state; the rest are re-armed.
+    /// Events still queued for the old rules are dropped.
+    pub fn set_rules(&mut self, rules: Vec<Rule>) {
+        let mut pending = self.pending.lock().unwrap();
+        *pending = Some(rules);
+        self.events.lock().unwrap().clear();
+    }
+
+    /// The live state of each rule, empty while not monitoring.
+    pub fn get_states(&self) -> Vec<RuleState> {
+        self.states.lock().unwrap().clone()
+    }
+
+    /// Triggers that started or stopped holding since the last call.
+    pub fn take_events(&self) -> Vec<RuleEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(trigger: Trigger, action: RuleAction) -> Rule {
+        Rule {
+            name: "rule".into(),
+            enabled: true,
+            trigger,
+            action,
+            revert: true,
+        }
+    }
+
+    fn idle(secs: u64) -> Trigger {
+        Trigger::Idle { secs }
+    }
+
+    fn inputs(idle_secs: u64, keys: Vec<Keycode>) -> Inputs {
+        Inputs {
+            idle: Duration::from_secs(idle_secs),
+            keys,
+            hour: 9,
+            minute: 30,
+        }
+    }
+
+    /// A watch with no display, as in a test run.
+    fn watch() -> Watch {
+        Watch {
+            holding: false,
+            focus: None,
+        }
+    }
+
+    #[test]
+    fn triggers_hold_while_their_condition_does() {
+        let mut watch = watch();
+        let quiet = inputs(180, vec![Keycode::LAlt]);
+        let busy = inputs(179, Vec::new());
+        let alt = Trigger::KeyHeld {
+            key: Key(Keycode::LAlt),
+        };
+        for (trigger, holds) in [
+            (idle(180), true),
+            (alt, true),
+            (
+                Trigger::At {
+                    hour: 9,
+                    minute: 30,
+                },
+                true,
+            ),
+            (
+                Trigger::At {
+                    hour: 9,
+                    minute: 31,
+                },
+                false,
+            ),
+        ] {
+            assert_eq!(watch.holds(&trigger, &quiet), Some(holds), "{:?}", trigger);
+            assert_eq!(
+                watch.holds(&trigger, &busy),
+                Some(holds && matches!(trigger, Trigger::At { .. })),
+                "{:?}",
+                trigger
+            );
+        }
+
+        let empty = Trigger::WindowTitle {
+            pattern: " ".into(),
+        };
+        assert_eq!(watch.holds(&empty, &quiet), Some(false));
+        let title = Trigger::WindowTitle {
+            pattern: "Editor".into(),
+        };
+        assert_eq!(watch.holds(&title, &quiet), None);
+    }
+
+    #[test]
+    fn events_mark_where_triggers_start_and_stop_holding() {
+        let start = RuleAction::Start(Automation::Mover);
+        let mut disabled = rule(idle(1), start.clone());
+        disabled.enabled = false;
+        let title = Trigger::WindowTitle {
+            pattern: "Editor".into(),
+        };
+        let rules = [rule(idle(60), start.clone()), disabled, rule(title, start)];
+        let mut watches = [watch(), watch(), watch()];
+        let fired = |fired| RuleEvent { rule: 0, fired };
+
+        let (states, events) = evaluate(&rules, &mut watches, &inputs(0, Vec::new()));
+        assert_eq!(
+            states,
+            [
+                RuleState::Armed,
+                RuleState::Disabled,
+                RuleState::Unavailable
+            ]
+        );
+        assert!(events.is_empty());
+
+        let (states, events) = evaluate(&rules, &mut watches, &inputs(60, Vec::new()));
+        assert_eq!(states[0], RuleState::Fired);
+        assert_eq!(events, [fired(true)]);
+        let (_, events) = evaluate(&rules, &mut watches, &inputs(61, Vec::new()));
+        assert!(events.is_empty());
+        let (states, events) = evaluate(&rules, &mut watches, &inputs(0, Vec::new()));
+        assert_eq!(states[0], RuleState::Armed);
+        assert_eq!(events, [fired(false)]);
+    }
+
+    #[test]
+    fn swapping_rules_keeps_unchanged_watches() {
+        let start = RuleAction::Start(Automation::Clicker);
+        let rules = vec![
+            rule(idle(60), start.clone()),
+            rule(idle(120), start.clone()),
+        ];
+        let holding = || Watch {
+            holding: true,
+            focus: None,
+        };
+
+        // Renaming or changing the action keeps the state; a new trigger
+        // re-arms the rule
+        let mut update = rules.clone();
+        update[0].name = "renamed".into();
+        update[0].action = RuleAction::Stop(Automation::Mover);
+        update[1].trigger = idle(5);
+        let watches = swap_watches(&rules, vec![holding(), holding()], &update);
+        assert!(watches[0].holding);
+        assert!(!watches[1].holding);
+
+        let mut update = rules.clone();
+        update[0].enabled = false;
+        update.push(rule(idle(1), start));
+        let watches = swap_watches(&rules, vec![holding(), holding()], &update);
+        assert_eq!(watches.len(), 3);
+        assert!(!watches[0].holding);
+        assert!(watches[1].holding);
+        assert!(!watches[2].holding);
+
+        // Rules that moved are different rules as far as events go
+        let swapped = vec![rules[1].clone(), rules[0].clone()];
+        let watches = swap_watches(&rules, vec![holding(), holding()], &swapped);
+        assert!(watches.iter().all(|watch| !watch.holding));
+    }
+
+    #[test]
+    fn reverting_stops_what_the_rule_started() {
+        let mut library = RuleLibrary::default();
+        library.insert_profile(Profile {
+            name: "work".into(),
+            clicking: true,
+            ..Profile::default()
+        });
+        let start = RuleAction::Start(Automation::Chain);
+        let profile = |name: &str| RuleAction::StartProfile { name: name.into() };
+
+        assert_eq!(
+            rule(idle(60), start.clone()).undo(&library),
+            [Automation::Chain]
+        );
+        assert_eq!(
+            rule(idle(60), profile("work")).undo(&library),
+            [Automation::Clicker]
+        );
+        assert!(rule(idle(60), profile("gone")).undo(&library).is_empty());
+        assert!(rule(idle(60), RuleAction::Stop(Automation::Clicker))
+            .undo(&library)
+            .is_empty());
+        let daily = rule(Trigger::At { hour: 9, minute: 0 }, start.clone());
+        assert!(daily.undo(&library).is_empty());
+        let mut kept = rule(idle(60), start);
+        kept.revert = false;
+        assert!(kept.undo(&library).is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JitterStats {
+    pub ticks: u64,
+    pub resyncs: u64,
+    pub mean_lateness_us: f64,
+    pub max_lateness_us: f64,
+    pub mean_period_us: f64,
+    m2: f64,
+}
+
+impl JitterStats {
+    fn record(&mut self, lateness: Duration) {
+        // Welford's online algorithm so we never keep the samples around
+        let sample = lateness.as_secs_f64() * 1_000_000.0;
+        self.ticks += 1;
+        let delta = sample - self.mean_lateness_us;
+        self.mean_lateness_us += delta / self.ticks as f64;
+        self.m2 += delta * (sample - self.mean_lateness_us);
+        self.max_lateness_us = self.max_lateness_us.max(sample);
+    }
+
+    fn record_period(&mut self, period: Duration) {
+        // Exponential moving average so the readout follows rate changes quickly
+        const ALPHA: f64 = 0.05;
+        let sample = period.as_secs_f64() * 1_000_000.0;
+        if self.mean_period_us == 0.0 {
+            self.mean_period_us = sample;
+        } else {
+            self.mean_period_us += ALPHA * (sample - self.mean_period_us);
+        }
+    }
+
+    pub fn measured_rate_hz(&self) -> f64 {
+        if self.mean_period_us > 0.0 {
+            1_000_000.0 / self.mean_period_us
+        } else {
+            0.0
+        }
+    }
+
+    pub fn stddev_lateness_us(&self) -> f64 {
+        if self.ticks < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.ticks - 1) as f64).sqrt()
+        }
+    }
+}
+
+/// Ticks on absolute deadlines (previous deadline + interval) so that the time
+/// spent doing the work and sleep overshoot do not accumulate as drift.
+#[derive(Debug)]
+pub struct DeadlineScheduler {
+    deadline: Instant,
+    last_wake: Option<Instant>,
+    spin_threshold: Duration,
+    stats: Arc<Mutex<JitterStats>>,
+}
+
+impl DeadlineScheduler {
+    pub fn new(stats: Arc<Mutex<JitterStats>>) -> Self {
+        *stats.lock().unwrap() = JitterStats::default();
+        Self {
+            deadline: Instant::now(),
+            last_wake: None,
+            spin_threshold: Duration::ZERO,
+            stats,
+        }
+    }
+
+    /// Sleep until shortly before each deadline and busy-wait the rest, trading
+    /// CPU time for sub-millisecond precision at high rates.
+    pub fn precise(mut self) -> Self {
+        self.spin_threshold = Duration::from_millis(2);
+        self
+    }
+
+    /// Starts counting deadlines from now again, e.g. after a pause.
+    pub fn restart(&mut self) {
+        self.deadline = Instant::now();
+        self.last_wake = None;
+    }
+
+    pub fn wait(&mut self, interval: Duration) {
+        if interval.is_zero() {
+            // Back-to-back actions have no deadline to be late for; the next
+            // pause is measured from when they are done
+            self.deadline = Instant::now();
+            return;
+        }
+        self.deadline += interval;
+        let now = Instant::now();
+        if self.deadline > now {
+            let remaining = self.deadline - now;
+            if remaining > self.spin_threshold {
+                thread::sleep(remaining - self.spin_threshold);
+            }
+            while Instant::now() < self.deadline {
+                std::hint::spin_loop();
+            }
+        }
+
+        let woke = Instant::now();
+        let lateness = woke.saturating_duration_since(self.deadline);
+        let mut stats = self.stats.lock().unwrap();
+        stats.record(lateness);
+        if let Some(last_wake) = self.last_wake.replace(woke) {
+            stats.record_period(woke - last_wake);
+        }
+        // More than a whole interval behind (system suspend, debugger, ...):
+        // start over from now instead of firing a burst of catch-up ticks.
+        if lateness > interval {
+            self.deadline = woke;
+            stats.resyncs += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn back_to_back_ticks_are_not_resyncs() {
+        let stats = Arc::new(Mutex::new(JitterStats::default()));
+        let mut scheduler = DeadlineScheduler::new(Arc::clone(&stats)).precise();
+        for _ in 0..100 {
+            scheduler.wait(Duration::ZERO);
+        }
+        scheduler.wait(Duration::from_millis(1));
+        let stats = *stats.lock().unwrap();
+        assert_eq!((stats.ticks, stats.resyncs), (1, 0));
+    }
+}
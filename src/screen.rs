@@ -0,0 +1,35 @@
+/// Reads one pixel of the desktop as RGB, or `None` where screen capture is
+/// not supported.
+pub fn pixel_at(x: i32, y: i32) -> Option<[u8; 3]> {
+    #[cfg(target_os = "linux")]
+    {
+        x11_pixel(x, y)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (x, y);
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn x11_pixel(x: i32, y: i32) -> Option<[u8; 3]> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{ConnectionExt as _, ImageFormat, ImageOrder};
+
+    let (conn, screen_num) = x11rb::connect(None).ok()?;
+    let setup = conn.setup();
+    let root = setup.roots.get(screen_num)?.root;
+    let image = conn
+        .get_image(ImageFormat::Z_PIXMAP, root, x as i16, y as i16, 1, 1, !0)
+        .ok()?
+        .reply()
+        .ok()?;
+    // Only the usual 24/32-bit TrueColor layout, 0xRRGGBB in a 32-bit unit
+    let bytes: [u8; 4] = image.data.get(..4)?.try_into().ok()?;
+    let value = match setup.image_byte_order {
+        ImageOrder::MSB_FIRST => u32::from_be_bytes(bytes),
+        _ => u32::from_le_bytes(bytes),
+    };
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
@@ -0,0 +1,363 @@
+use crate::backend::{create_enigo, InputBackend};
+use crate::macros::Key;
+use crate::mouse_button::SerializableMouseButton;
+use crate::seed::{session_rng, SessionRng};
+use device_query::Keycode;
+use enigo::{Coordinate, Direction};
+use rand::Rng;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, INT};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptSettings {
+    /// Name of the script to run.
+    pub selected: String,
+    /// Rhai operations a run may take before it is stopped. `None` is
+    /// unlimited.
+    pub max_operations: Option<u64>,
+    /// Wall-clock time a run may take, sleeps included. `None` is unlimited.
+    pub time_limit_secs: Option<u64>,
+    pub seed: Option<u64>,
+}
+
+impl Default for ScriptSettings {
+    fn default() -> Self {
+        Self {
+            selected: String::new(),
+            max_operations: Some(10_000_000),
+            time_limit_secs: Some(300),
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Script {
+    pub name: String,
+    pub source: String,
+}
+
+/// Everything stored in `scripts.ron` next to the executable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptLibrary {
+    pub settings: ScriptSettings,
+    pub scripts: Vec<Script>,
+}
+
+impl ScriptLibrary {
+    fn path() -> PathBuf {
+        env::current_exe()
+            .expect("Failed to get executable path")
+            .parent()
+            .expect("Failed to get executable directory")
+            .join("scripts.ron")
+    }
+
+    pub fn load() -> Self {
+        let Ok(text) = fs::read_to_string(Self::path()) else {
+            return Self::default();
+        };
+        ron::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Failed to parse scripts: {}", e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Ok(text) = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            if let Err(e) = fs::write(Self::path(), text) {
+                eprintln!("Failed to save scripts: {}", e);
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Script> {
+        self.scripts.iter().find(|s| s.name == name)
+    }
+
+    /// Looks `name` up in the library, or reads it as a `.rhai` file.
+    pub fn source(&self, name: &str) -> Result<String, String> {
+        if let Some(script) = self.get(name) {
+            return Ok(script.source.clone());
+        }
+        fs::read_to_string(name).map_err(|e| format!("No script named {:?}: {}", name, e))
+    }
+}
+
+/// Why a run was cut short, checked between Rhai operations and during
+/// sleeps so neither a busy loop nor a long sleep outlives a stop.
+#[derive(Clone)]
+struct Guard {
+    running: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl Guard {
+    const SLEEP_SLICE: Duration = Duration::from_millis(100);
+
+    fn check(&self) -> Option<&'static str> {
+        if !self.running.load(Ordering::SeqCst) {
+            Some("stopped")
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            Some("time limit reached")
+        } else {
+            None
+        }
+    }
+}
+
+type FnResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Runs Rhai scripts against an input backend. The engine only sees the
+/// functions registered here, so scripts cannot touch files or processes.
+pub struct ScriptWorker<B: InputBackend> {
+    pub settings: ScriptSettings,
+    pub seed: u64,
+    pub backend: Rc<RefCell<B>>,
+    rng: Rc<RefCell<SessionRng>>,
+}
+
+impl<B: InputBackend + 'static> ScriptWorker<B> {
+    pub fn new(settings: ScriptSettings, backend: B) -> Self {
+        let (rng, seed) = session_rng(settings.seed);
+        Self {
+            settings,
+            seed,
+            backend: Rc::new(RefCell::new(backend)),
+            rng: Rc::new(RefCell::new(rng)),
+        }
+    }
+
+    /// Runs `source` to completion, sending `print` output to `output`.
+    /// Clearing `running` stops the script at its next operation or sleep.
+    pub fn run(
+        &mut self,
+        source: &str,
+        running: Arc<AtomicBool>,
+        output: impl Fn(String) + 'static,
+    ) -> Result<(), String> {
+        let guard = Guard {
+            running,
+            deadline: self
+                .settings
+                .time_limit_secs
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+        };
+        let engine = self.engine(guard, output);
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        engine.run_ast(&ast).map_err(|e| match *e {
+            EvalAltResult::ErrorTerminated(reason, _) => format!("Script {}", reason),
+            EvalAltResult::ErrorTooManyOperations(_) => "Script hit its operation limit".into(),
+            e => e.to_string(),
+        })
+    }
+
+    fn engine(&self, guard: Guard, output: impl Fn(String) + 'static) -> Engine {
+        let mut engine = Engine::new();
+        // Rhai's default resolver loads `import`ed modules from disk
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+        engine.set_max_operations(self.settings.max_operations.unwrap_or(0));
+        engine.set_max_call_levels(64);
+        engine.set_max_string_size(1 << 20);
+        engine.set_max_array_size(100_000);
+        engine.set_max_map_size(100_000);
+
+        let output = Rc::new(output);
+        let print = Rc::clone(&output);
+        engine.on_print(move |text| print(text.to_string()));
+        engine.on_debug(move |text, _, pos| output(format!("[{}] {}", pos, text)));
+        let progress = guard.clone();
+        engine.on_progress(move |_| progress.check().map(Dynamic::from));
+
+        let backend = Rc::clone(&self.backend);
+        engine.register_fn("click", move |button: &str| -> FnResult<()> {
+            let button = parse_button(button)?;
+            let result = backend.borrow_mut().button(button.into(), Direction::Click);
+            result.map_err(|e| format!("click failed: {}", e).into())
+        });
+        let backend = Rc::clone(&self.backend);
+        engine.register_fn("click", move || -> FnResult<()> {
+            let button = SerializableMouseButton::Left.into();
+            let result = backend.borrow_mut().button(button, Direction::Click);
+            result.map_err(|e| format!("click failed: {}", e).into())
+        });
+        let backend = Rc::clone(&self.backend);
+        engine.register_fn("move_to", move |x: INT, y: INT| -> FnResult<()> {
+            let result = backend
+                .borrow_mut()
+                .move_mouse(x as i32, y as i32, Coordinate::Abs);
+            result.map_err(|e| format!("move_to failed: {}", e).into())
+        });
+        let backend = Rc::clone(&self.backend);
+        engine.register_fn("key", move |name: &str| -> FnResult<()> {
+            let key = Keycode::from_str(name)
+                .map(Key)
+                .map_err(|_| format!("unknown key {:?}", name))?;
+            let result = backend.borrow_mut().key(key.to_enigo(), Direction::Click);
+            result.map_err(|e| format!("key failed: {}", e).into())
+        });
+        let backend = Rc::clone(&self.backend);
+        engine.register_fn("cursor", move || -> FnResult<Array> {
+            let (x, y) = backend
+                .borrow()
+                .location()
+                .map_err(|e| format!("cursor failed: {}", e))?;
+            Ok(vec![Dynamic::from(x as INT), Dynamic::from(y as INT)])
+        });
+        let backend = Rc::clone(&self.backend);
+        engine.register_fn("sleep", move |ms: INT| -> FnResult<()> {
+            let mut remaining = Duration::from_millis(ms.max(0) as u64);
+            while !remaining.is_zero() {
+                if let Some(reason) = guard.check() {
+                    return Err(EvalAltResult::ErrorTerminated(
+                        Dynamic::from(reason),
+                        rhai::Position::NONE,
+                    )
+                    .into());
+                }
+                let slice = remaining.min(Guard::SLEEP_SLICE);
+                backend.borrow_mut().sleep(slice);
+                remaining -= slice;
+            }
+            Ok(())
+        });
+        let rng = Rc::clone(&self.rng);
+        engine.register_fn("rand", move |low: INT, high: INT| -> FnResult<INT> {
+            if low > high {
+                return Err(format!("rand({}, {}): low is above high", low, high).into());
+            }
+            Ok(rng.borrow_mut().random_range(low..=high))
+        });
+        engine
+    }
+}
+
+fn parse_button(name: &str) -> FnResult<SerializableMouseButton> {
+    match name.to_ascii_lowercase().as_str() {
+        "left" => Ok(SerializableMouseButton::Left),
+        "middle" => Ok(SerializableMouseButton::Middle),
+        "right" => Ok(SerializableMouseButton::Right),
+        _ => Err(format!("unknown button {:?}, use left, middle or right", name).into()),
+    }
+}
+
+#[derive(Debug)]
+pub struct ScriptRunner {
+    is_running: Arc<AtomicBool>,
+    output: Arc<Mutex<Vec<String>>>,
+    last_seed: Arc<AtomicU64>,
+}
+
+impl Default for ScriptRunner {
+    fn default() -> Self {
+        Self {
+            is_running: Arc::new(AtomicBool::new(false)),
+            output: Arc::new(Mutex::new(Vec::new())),
+            last_seed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl ScriptRunner {
+    pub fn start_running(&mut self, name: String, source: String, settings: ScriptSettings) {
+        if !self.is_running.load(Ordering::SeqCst) {
+            self.is_running.store(true, Ordering::SeqCst);
+            let is_running = Arc::clone(&self.is_running);
+            let output = Arc::clone(&self.output);
+            let last_seed = Arc::clone(&self.last_seed);
+
+            thread::spawn(move || {
+                let log = move |line: String| push_line(&output, line);
+                let mut worker = ScriptWorker::new(settings, create_enigo());
+                last_seed.store(worker.seed, Ordering::SeqCst);
+                log(format!("Running \"{}\" with seed {}", name, worker.seed));
+                let started = Instant::now();
+                let print = log.clone();
+                match worker.run(&source, Arc::clone(&is_running), print) {
+                    Ok(()) => log(format!(
+                        "Finished in {:.1} s",
+                        started.elapsed().as_secs_f64()
+                    )),
+                    Err(e) => log(format!("Error: {}", e)),
+                }
+                is_running.store(false, Ordering::SeqCst);
+            });
+        }
+    }
+
+    pub fn stop_running(&mut self) {
+        self.is_running.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.is_running.load(Ordering::SeqCst)
+    }
+
+    pub fn get_output(&self) -> Vec<String> {
+        self.output.lock().unwrap().clone()
+    }
+
+    pub fn log(&self, line: String) {
+        push_line(&self.output, line);
+    }
+
+    pub fn clear_output(&self) {
+        self.output.lock().unwrap().clear();
+    }
+
+    pub fn get_last_seed(&self) -> u64 {
+        self.last_seed.load(Ordering::SeqCst)
+    }
+}
+
+const MAX_OUTPUT_LINES: usize = 500;
+
+fn push_line(output: &Mutex<Vec<String>>, line: String) {
+    let mut lines = output.lock().unwrap();
+    lines.push(line);
+    let excess = lines.len().saturating_sub(MAX_OUTPUT_LINES);
+    lines.drain(..excess);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+
+    fn run(source: &str) -> Result<(), String> {
+        let mut worker = ScriptWorker::new(ScriptSettings::default(), MockBackend::default());
+        worker.run(source, Arc::new(AtomicBool::new(true)), |_| {})
+    }
+
+    #[test]
+    fn import_cannot_load_files() {
+        let path = env::temp_dir().join(format!("mourse_import_{}.rhai", std::process::id()));
+        fs::write(&path, "fn hello() { 1 }").unwrap();
+        let module = path.with_extension("");
+        let result = run(&format!(
+            "import {:?} as m; m::hello();",
+            module.to_string_lossy()
+        ));
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn eval_is_disabled() {
+        assert!(run("eval(\"1\")").is_err());
+    }
+}
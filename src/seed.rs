@@ -0,0 +1,13 @@
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// ChaCha is portable and stable across platforms and releases, so a seed
+/// reproduces the same run everywhere.
+pub type SessionRng = ChaCha8Rng;
+
+/// Returns the RNG for one automation run together with the seed it was built
+/// from, picking a fresh seed when none is configured.
+pub fn session_rng(seed: Option<u64>) -> (SessionRng, u64) {
+    let seed = seed.unwrap_or_else(rand::random);
+    (SessionRng::seed_from_u64(seed), seed)
+}
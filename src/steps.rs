@@ -0,0 +1,720 @@
+use crate::coords::ScreenPoint;
+use crate::drag;
+use crate::macros::Key;
+use crate::motion::GlideSettings;
+use crate::mouse_button::SerializableMouseButton;
+use crate::typing::{self, TypingStyle};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    Button(SerializableMouseButton),
+    Key(Key),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Condition {
+    /// The screen pixel at `x`, `y` is within `tolerance` of `rgb` on every
+    /// channel.
+    Pixel {
+        x: i32,
+        y: i32,
+        rgb: (u8, u8, u8),
+        #[serde(default)]
+        tolerance: u8,
+    },
+    KeyDown {
+        key: Key,
+    },
+    Not(Box<Condition>),
+}
+
+fn one() -> Option<u32> {
+    Some(1)
+}
+
+/// One instruction of a macro. Loops and conditions nest further steps.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Step {
+    Move {
+        to: ScreenPoint,
+    },
+    Click {
+        button: SerializableMouseButton,
+    },
+    Press {
+        input: Input,
+    },
+    Release {
+        input: Input,
+    },
+    /// Presses `button` at `from`, glides to `to`, waits `hover_ms` and
+    /// releases.
+    Drag {
+        button: SerializableMouseButton,
+        from: ScreenPoint,
+        to: ScreenPoint,
+        #[serde(default = "drag::default_glide")]
+        glide: GlideSettings,
+        #[serde(default)]
+        hover_ms: u64,
+    },
+    /// Positive amounts scroll down (or right), negative up (or left).
+    Scroll {
+        amount: i32,
+        #[serde(default)]
+        horizontal: bool,
+    },
+    /// Types `text`, where `{ENTER}`-style escapes press special keys.
+    Type {
+        text: String,
+        #[serde(default)]
+        style: TypingStyle,
+    },
+    Wait {
+        ms: u64,
+    },
+    /// Blocks until `key` is down, or until the timeout passes.
+    WaitForKey {
+        key: Key,
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+    },
+    /// Repeats `steps` `count` times, or until stopped if `count` is `None`.
+    Loop {
+        label: String,
+        #[serde(default = "one")]
+        count: Option<u32>,
+        steps: Vec<Step>,
+    },
+    /// Leaves the enclosing loop called `label`.
+    Break {
+        label: String,
+    },
+    If {
+        condition: Condition,
+        then: Vec<Step>,
+        #[serde(default)]
+        otherwise: Vec<Step>,
+    },
+}
+
+impl Step {
+    /// A `Press` or `Release` of `input`.
+    pub fn press(input: Input, pressed: bool) -> Self {
+        if pressed {
+            Step::Press { input }
+        } else {
+            Step::Release { input }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Step::Move { .. } => "Move",
+            Step::Click { .. } => "Click",
+            Step::Press { .. } => "Press",
+            Step::Release { .. } => "Release",
+            Step::Drag { .. } => "Drag",
+            Step::Scroll { .. } => "Scroll",
+            Step::Type { .. } => "Type",
+            Step::Wait { .. } => "Wait",
+            Step::WaitForKey { .. } => "Wait for key",
+            Step::Loop { .. } => "Loop",
+            Step::Break { .. } => "Break",
+            Step::If { .. } => "If",
+        }
+    }
+
+    /// The ways this step can finish without having paused.
+    fn unpaused(&self) -> Unpaused<'_> {
+        match self {
+            Step::Wait { ms } if *ms > 0 => Unpaused::default(),
+            Step::Break { label } => Unpaused {
+                falls_through: false,
+                breaks: vec![label.as_str()],
+            },
+            // Ends at once when the key is already down, the timeout is
+            // zero or there is no keyboard to read, as in a dry run
+            Step::WaitForKey { .. } => Unpaused::through(),
+            Step::Loop { count: Some(0), .. } => Unpaused::through(),
+            Step::Loop { label, steps, .. } => {
+                let mut body = unpaused(steps);
+                let breaks_out = body.breaks.contains(&label.as_str());
+                body.breaks.retain(|target| *target != label);
+                Unpaused {
+                    falls_through: body.falls_through || breaks_out,
+                    breaks: body.breaks,
+                }
+            }
+            Step::If {
+                then, otherwise, ..
+            } => {
+                let (mut then, otherwise) = (unpaused(then), unpaused(otherwise));
+                then.breaks.extend(otherwise.breaks);
+                Unpaused {
+                    falls_through: then.falls_through || otherwise.falls_through,
+                    breaks: then.breaks,
+                }
+            }
+            _ => Unpaused::through(),
+        }
+    }
+}
+
+/// How running some steps can end without a `Wait` having run: by reaching
+/// the end, or by breaking out to one of the labelled loops.
+#[derive(Debug, Default)]
+struct Unpaused<'a> {
+    falls_through: bool,
+    breaks: Vec<&'a str>,
+}
+
+impl Unpaused<'_> {
+    fn through() -> Self {
+        Self {
+            falls_through: true,
+            breaks: Vec::new(),
+        }
+    }
+}
+
+fn unpaused(steps: &[Step]) -> Unpaused<'_> {
+    let mut result = Unpaused::through();
+    for step in steps {
+        if !result.falls_through {
+            break;
+        }
+        let next = step.unpaused();
+        result.falls_through = next.falls_through;
+        result.breaks.extend(next.breaks);
+    }
+    result
+}
+
+/// Whether running `steps` always takes some time, so repeating them
+/// forever does not spin. `Wait for key` does not count, since it can end
+/// straight away.
+pub fn always_waits(steps: &[Step]) -> bool {
+    let unpaused = unpaused(steps);
+    !unpaused.falls_through && unpaused.breaks.is_empty()
+}
+
+/// Labels of the loops around `steps` that a `Break` in them can leave.
+fn breaks_out(steps: &[Step]) -> Vec<&str> {
+    let mut labels = Vec::new();
+    for step in steps {
+        match step {
+            Step::Break { label } => labels.push(label.as_str()),
+            Step::Loop { label, steps, .. } => {
+                labels.extend(
+                    breaks_out(steps)
+                        .into_iter()
+                        .filter(|target| target != label),
+                );
+            }
+            Step::If {
+                then, otherwise, ..
+            } => {
+                labels.extend(breaks_out(then));
+                labels.extend(breaks_out(otherwise));
+            }
+            _ => {}
+        }
+    }
+    labels
+}
+
+/// Whether running `steps` can never finish, because it always reaches an
+/// endless loop with no `Break` out of it.
+pub fn never_ends(steps: &[Step]) -> bool {
+    for step in steps {
+        let stuck = match step {
+            Step::Loop {
+                count: None, steps, ..
+            } => breaks_out(steps).is_empty() || never_ends(steps),
+            Step::Loop { steps, .. } => never_ends(steps),
+            Step::If {
+                then, otherwise, ..
+            } => never_ends(then) && never_ends(otherwise),
+            _ => false,
+        };
+        if stuck {
+            return true;
+        }
+        // A break here may skip the rest
+        if !breaks_out(std::slice::from_ref(step)).is_empty() {
+            return false;
+        }
+    }
+    false
+}
+
+/// A step that failed validation. `path` is 1-based and dotted through
+/// nested steps, e.g. `3.then.2` for the second step of the `then` branch
+/// of step 3.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepError {
+    pub path: String,
+    pub step: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {} ({}): {}", self.path, self.step, self.message)
+    }
+}
+
+/// A flattened instruction. Control flow becomes jumps so the player only
+/// has to track a program counter and loop counters.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Do(Step),
+    LoopStart {
+        count: Option<u32>,
+        end: usize,
+    },
+    LoopEnd {
+        start: usize,
+    },
+    Branch {
+        condition: Condition,
+        otherwise: usize,
+    },
+    Jump {
+        to: usize,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub ops: Vec<Op>,
+    /// Source step path for every op, for progress display.
+    pub paths: Vec<String>,
+}
+
+impl Program {
+    /// Validates `steps` and flattens them, pointing at the first bad step.
+    pub fn compile(steps: &[Step]) -> Result<Self, StepError> {
+        let mut compiler = Compiler {
+            program: Program::default(),
+            loops: Vec::new(),
+        };
+        compiler.block(steps, "")?;
+        Ok(compiler.program)
+    }
+}
+
+struct OpenLoop {
+    label: String,
+    /// Jumps that must land after this loop once its end is known.
+    breaks: Vec<usize>,
+}
+
+struct Compiler {
+    program: Program,
+    loops: Vec<OpenLoop>,
+}
+
+impl Compiler {
+    fn push(&mut self, op: Op, path: &str) -> usize {
+        self.program.ops.push(op);
+        self.program.paths.push(path.to_string());
+        self.program.ops.len() - 1
+    }
+
+    fn block(&mut self, steps: &[Step], prefix: &str) -> Result<(), StepError> {
+        for (i, step) in steps.iter().enumerate() {
+            let path = format!("{}{}", prefix, i + 1);
+            self.step(step, &path)?;
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, step: &Step, path: &str) -> Result<(), StepError> {
+        let fail = |message: String| StepError {
+            path: path.to_string(),
+            step: step.name(),
+            message,
+        };
+        match step {
+            Step::Scroll { amount: 0, .. } => return Err(fail("scroll amount is 0".into())),
+            Step::Type { text, .. } if text.is_empty() => {
+                return Err(fail("there is no text to type".into()))
+            }
+            Step::Type { text, .. } => {
+                typing::parse(text).map_err(fail)?;
+            }
+            Step::Loop {
+                label,
+                count,
+                steps,
+            } => {
+                if label.trim().is_empty() {
+                    return Err(fail("loop needs a label".into()));
+                }
+                if self.loops.iter().any(|open| open.label == *label) {
+                    return Err(fail(format!(
+                        "label {:?} is already used by an enclosing loop",
+                        label
+                    )));
+                }
+                if *count == Some(0) {
+                    return Err(fail("loop count must be at least 1".into()));
+                }
+                if steps.is_empty() {
+                    return Err(fail("loop has no steps".into()));
+                }
+                // Breaking out ends the loop, so only repeats need to pause
+                if count.is_none() && unpaused(steps).falls_through {
+                    return Err(fail("an endless loop needs a Wait that always runs".into()));
+                }
+                let start = self.push(
+                    Op::LoopStart {
+                        count: *count,
+                        end: 0,
+                    },
+                    path,
+                );
+                self.loops.push(OpenLoop {
+                    label: label.clone(),
+                    breaks: Vec::new(),
+                });
+                self.block(steps, &format!("{}.", path))?;
+                let open = self.loops.pop().expect("loop opened above");
+                self.push(Op::LoopEnd { start }, path);
+                let end = self.program.ops.len();
+                self.program.ops[start] = Op::LoopStart { count: *count, end };
+                for jump in open.breaks {
+                    self.program.ops[jump] = Op::Jump { to: end };
+                }
+                return Ok(());
+            }
+            Step::Break { label } => {
+                let jump = self.push(Op::Jump { to: 0 }, path);
+                let Some(open) = self
+                    .loops
+                    .iter_mut()
+                    .rev()
+                    .find(|open| open.label == *label)
+                else {
+                    return Err(fail(format!("no enclosing loop is labelled {:?}", label)));
+                };
+                open.breaks.push(jump);
+                return Ok(());
+            }
+            Step::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = self.push(
+                    Op::Branch {
+                        condition: condition.clone(),
+                        otherwise: 0,
+                    },
+                    path,
+                );
+                self.block(then, &format!("{}.then.", path))?;
+                let skip = self.push(Op::Jump { to: 0 }, path);
+                self.program.ops[branch] = Op::Branch {
+                    condition: condition.clone(),
+                    otherwise: self.program.ops.len(),
+                };
+                self.block(otherwise, &format!("{}.else.", path))?;
+                self.program.ops[skip] = Op::Jump {
+                    to: self.program.ops.len(),
+                };
+                return Ok(());
+            }
+            _ => {}
+        }
+        self.push(Op::Do(step.clone()), path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use device_query::Keycode;
+
+    fn click() -> Step {
+        Step::Click {
+            button: SerializableMouseButton::Left,
+        }
+    }
+
+    fn lp(label: &str, count: Option<u32>, steps: Vec<Step>) -> Step {
+        Step::Loop {
+            label: label.into(),
+            count,
+            steps,
+        }
+    }
+
+    fn brk(label: &str) -> Step {
+        Step::Break {
+            label: label.into(),
+        }
+    }
+
+    fn if_key(then: Vec<Step>) -> Step {
+        Step::If {
+            condition: Condition::KeyDown {
+                key: Key(Keycode::A),
+            },
+            then,
+            otherwise: Vec::new(),
+        }
+    }
+
+    fn error(steps: &[Step]) -> StepError {
+        Program::compile(steps).expect_err("steps should not compile")
+    }
+
+    #[test]
+    fn errors_point_at_nested_steps() {
+        let steps = [
+            click(),
+            Step::Wait { ms: 10 },
+            Step::If {
+                condition: Condition::KeyDown {
+                    key: Key(Keycode::A),
+                },
+                then: vec![
+                    click(),
+                    Step::Scroll {
+                        amount: 0,
+                        horizontal: false,
+                    },
+                ],
+                otherwise: Vec::new(),
+            },
+        ];
+        let e = error(&steps);
+        assert_eq!((e.path.as_str(), e.step), ("3.then.2", "Scroll"));
+
+        let steps = [Step::If {
+            condition: Condition::Not(Box::new(Condition::KeyDown {
+                key: Key(Keycode::A),
+            })),
+            then: vec![click()],
+            otherwise: vec![lp(
+                "a",
+                Some(2),
+                vec![Step::Type {
+                    text: String::new(),
+                    style: TypingStyle::default(),
+                }],
+            )],
+        }];
+        let e = error(&steps);
+        assert_eq!((e.path.as_str(), e.step), ("1.else.1.1", "Type"));
+        assert_eq!(
+            e.to_string(),
+            "step 1.else.1.1 (Type): there is no text to type"
+        );
+    }
+
+    #[test]
+    fn labels_must_be_unique_and_known() {
+        let e = error(&[lp("a", Some(1), vec![lp("a", Some(1), vec![click()])])]);
+        assert_eq!(e.path, "1.1");
+        assert!(e.message.contains("already used"), "{}", e.message);
+
+        // Loops side by side may share a label
+        Program::compile(&[
+            lp("a", Some(1), vec![click()]),
+            lp("a", Some(1), vec![click()]),
+        ])
+        .unwrap();
+
+        let e = error(&[lp("a", Some(2), vec![Step::Break { label: "b".into() }])]);
+        assert_eq!((e.path.as_str(), e.step), ("1.1", "Break"));
+        let e = error(&[Step::Break { label: "a".into() }]);
+        assert_eq!(e.path, "1");
+        let e = error(&[lp(" ", Some(1), vec![click()])]);
+        assert_eq!(e.message, "loop needs a label");
+    }
+
+    #[test]
+    fn endless_loops_need_a_wait() {
+        assert!(error(&[lp("a", None, vec![click()])])
+            .message
+            .contains("endless loop"));
+        error(&[lp("a", None, vec![click(), Step::Wait { ms: 0 }])]);
+        // A wait in only one branch does not always run
+        error(&[lp(
+            "a",
+            None,
+            vec![Step::If {
+                condition: Condition::KeyDown {
+                    key: Key(Keycode::A),
+                },
+                then: vec![Step::Wait { ms: 10 }],
+                otherwise: vec![click()],
+            }],
+        )]);
+
+        Program::compile(&[lp("a", None, vec![click(), Step::Wait { ms: 10 }])]).unwrap();
+        // Waiting for a key can end straight away
+        error(&[lp(
+            "a",
+            None,
+            vec![Step::WaitForKey {
+                key: Key(Keycode::Space),
+                timeout_ms: None,
+            }],
+        )]);
+        // Breaking out before the wait leaves the loop rather than spinning
+        Program::compile(&[lp(
+            "a",
+            None,
+            vec![if_key(vec![brk("a")]), Step::Wait { ms: 10 }],
+        )])
+        .unwrap();
+        Program::compile(&[lp(
+            "a",
+            None,
+            vec![lp("b", Some(3), vec![Step::Wait { ms: 10 }])],
+        )])
+        .unwrap();
+    }
+
+    #[test]
+    fn always_waits_needs_a_pause_on_every_path() {
+        let wait = || Step::Wait { ms: 10 };
+        assert!(always_waits(&[click(), wait()]));
+        assert!(always_waits(&[lp("a", Some(3), vec![wait()])]));
+        assert!(always_waits(&[lp("a", Some(2), vec![wait(), brk("a")])]));
+        assert!(always_waits(&[Step::If {
+            condition: Condition::KeyDown {
+                key: Key(Keycode::A),
+            },
+            then: vec![wait()],
+            otherwise: vec![wait()],
+        }]));
+        // Leaving an inner loop early still reaches the wait after it
+        assert!(always_waits(&[
+            lp("inner", Some(2), vec![if_key(vec![brk("inner")]), wait()]),
+            wait(),
+        ]));
+
+        assert!(!always_waits(&[click()]));
+        assert!(!always_waits(&[Step::Wait { ms: 0 }]));
+        assert!(!always_waits(&[Step::WaitForKey {
+            key: Key(Keycode::Space),
+            timeout_ms: Some(0),
+        }]));
+        assert!(!always_waits(&[lp("a", Some(0), vec![wait()])]));
+        assert!(!always_waits(&[lp(
+            "a",
+            None,
+            vec![if_key(vec![brk("a")]), wait()]
+        )]));
+        assert!(!always_waits(&[lp(
+            "outer",
+            Some(2),
+            vec![
+                lp("inner", Some(2), vec![if_key(vec![brk("outer")]), wait()]),
+                wait(),
+            ],
+        )]));
+    }
+
+    #[test]
+    fn never_ends_needs_an_endless_loop_without_a_way_out() {
+        let forever = |steps| lp("a", None, steps);
+        let wait = || Step::Wait { ms: 10 };
+        assert!(never_ends(&[click(), forever(vec![wait()])]));
+        assert!(never_ends(&[lp("b", Some(2), vec![forever(vec![wait()])])]));
+        // Breaking out of an inner loop only leaves that loop
+        assert!(never_ends(&[forever(vec![lp(
+            "b",
+            Some(2),
+            vec![if_key(vec![brk("b")]), wait()]
+        )])]));
+
+        assert!(!never_ends(&[click(), wait()]));
+        assert!(!never_ends(&[forever(vec![
+            if_key(vec![brk("a")]),
+            wait()
+        ])]));
+        assert!(!never_ends(&[lp(
+            "b",
+            None,
+            vec![wait(), lp("c", Some(2), vec![if_key(vec![brk("b")])])]
+        )]));
+        // The endless loop may be skipped
+        assert!(!never_ends(&[if_key(vec![forever(vec![wait()])])]));
+        assert!(!never_ends(&[lp(
+            "b",
+            Some(2),
+            vec![if_key(vec![brk("b")]), forever(vec![wait()])]
+        )]));
+    }
+
+    #[test]
+    fn loops_need_a_count_and_steps() {
+        assert_eq!(
+            error(&[lp("a", Some(0), vec![click()])]).message,
+            "loop count must be at least 1"
+        );
+        assert_eq!(
+            error(&[lp("a", Some(1), vec![])]).message,
+            "loop has no steps"
+        );
+    }
+
+    #[test]
+    fn control_flow_becomes_jumps() {
+        let program = Program::compile(&[
+            lp(
+                "a",
+                Some(2),
+                vec![click(), Step::Break { label: "a".into() }],
+            ),
+            Step::Wait { ms: 5 },
+        ])
+        .unwrap();
+        assert_eq!(
+            program.ops,
+            vec![
+                Op::LoopStart {
+                    count: Some(2),
+                    end: 4
+                },
+                Op::Do(click()),
+                Op::Jump { to: 4 },
+                Op::LoopEnd { start: 0 },
+                Op::Do(Step::Wait { ms: 5 }),
+            ]
+        );
+        assert_eq!(program.paths, ["1", "1.1", "1.2", "1", "2"]);
+
+        let condition = Condition::KeyDown {
+            key: Key(Keycode::A),
+        };
+        let program = Program::compile(&[Step::If {
+            condition: condition.clone(),
+            then: vec![click()],
+            otherwise: vec![Step::Wait { ms: 5 }],
+        }])
+        .unwrap();
+        assert_eq!(
+            program.ops,
+            vec![
+                Op::Branch {
+                    condition,
+                    otherwise: 3
+                },
+                Op::Do(click()),
+                Op::Jump { to: 4 },
+                Op::Do(Step::Wait { ms: 5 }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use crate::backend::{create_enigo, InputBackend};
+use crate::delay::DelayDistribution;
+use crate::macros::Key;
+use crate::scheduler::{DeadlineScheduler, JitterStats};
+use crate::seed::{session_rng, SessionRng};
+use device_query::Keycode;
+use enigo::Direction;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Names accepted inside `{...}` escapes, ignoring case. `F1` to `F12` are
+/// accepted as well.
+const SPECIAL_KEYS: &[(&str, Keycode)] = &[
+    ("ENTER", Keycode::Enter),
+    ("TAB", Keycode::Tab),
+    ("ESC", Keycode::Escape),
+    ("ESCAPE", Keycode::Escape),
+    ("BACKSPACE", Keycode::Backspace),
+    ("BS", Keycode::Backspace),
+    ("DELETE", Keycode::Delete),
+    ("DEL", Keycode::Delete),
+    ("INSERT", Keycode::Insert),
+    ("HOME", Keycode::Home),
+    ("END", Keycode::End),
+    ("PGUP", Keycode::PageUp),
+    ("PGDN", Keycode::PageDown),
+    ("UP", Keycode::Up),
+    ("DOWN", Keycode::Down),
+    ("LEFT", Keycode::Left),
+    ("RIGHT", Keycode::Right),
+    ("SPACE", Keycode::Space),
+];
+
+// Rows of a US QWERTY keyboard, for picking a plausible wrong key
+const QWERTY: [&str; 4] = ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"];
+
+/// How text is typed. The default types the whole text at once, as a
+/// `Type` step always did.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypingStyle {
+    /// Pause after each character. Equal bounds give a fixed delay.
+    pub min_char_delay_ms: u64,
+    pub max_char_delay_ms: u64,
+    pub distribution: DelayDistribution,
+    /// Chance per letter, digit or punctuation mark of hitting a
+    /// neighbouring key first and correcting it with Backspace.
+    pub typo_percent: f64,
+}
+
+impl Default for TypingStyle {
+    fn default() -> Self {
+        Self {
+            min_char_delay_ms: 0,
+            max_char_delay_ms: 0,
+            distribution: DelayDistribution::Uniform,
+            typo_percent: 0.0,
+        }
+    }
+}
+
+impl TypingStyle {
+    pub fn is_instant(&self) -> bool {
+        self.max_char_delay_ms == 0 && self.typo_chance() == 0.0
+    }
+
+    /// `typo_percent` as a probability. A hand-edited NaN counts as no typos
+    /// rather than reaching `random_bool`, which panics on it.
+    fn typo_chance(&self) -> f64 {
+        let chance = self.typo_percent / 100.0;
+        if chance.is_nan() {
+            0.0
+        } else {
+            chance.clamp(0.0, 1.0)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    Char(char),
+    Key(Key),
+}
+
+/// Splits `text` into characters and `{ENTER}`-style key escapes. `{{` and
+/// `}}` type literal braces.
+pub fn parse(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                tokens.push(Token::Char('{'));
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                tokens.push(Token::Char('}'));
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest
+                    .find('}')
+                    .ok_or_else(|| format!("unclosed {{ in {:?}", text))?;
+                let name = &rest[..end];
+                tokens.push(Token::Key(special_key(name)?));
+                chars = rest[end + 1..].chars();
+            }
+            '}' => return Err("unmatched } (type }} for a literal brace)".to_string()),
+            c => tokens.push(Token::Char(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+fn special_key(name: &str) -> Result<Key, String> {
+    let upper = name.trim().to_ascii_uppercase();
+    if let Some((_, code)) = SPECIAL_KEYS.iter().find(|(key, _)| *key == upper) {
+        return Ok(Key(*code));
+    }
+    match upper.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+        Some(1..=12) => Ok(Key(upper.parse().expect("F1 to F12 are keycodes"))),
+        _ => Err(format!("unknown key {{{}}}", name)),
+    }
+}
+
+/// Writes `text` so `parse` reads it back as the same characters.
+pub fn escape(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+/// A neighbour of `c` on a QWERTY keyboard, keeping its case.
+fn neighbour(c: char, rng: &mut impl Rng) -> Option<char> {
+    let lower = c.to_ascii_lowercase();
+    let (row, col) = QWERTY
+        .iter()
+        .enumerate()
+        .find_map(|(row, keys)| keys.find(lower).map(|col| (row, col)))?;
+    let mut candidates = Vec::new();
+    for (r, c) in [
+        (row, col.wrapping_sub(1)),
+        (row, col + 1),
+        (row.wrapping_sub(1), col),
+        (row + 1, col),
+    ] {
+        if let Some(key) = QWERTY.get(r).and_then(|keys| keys.chars().nth(c)) {
+            candidates.push(key);
+        }
+    }
+    let wrong = candidates[rng.random_range(0..candidates.len())];
+    Some(if c.is_ascii_uppercase() {
+        wrong.to_ascii_uppercase()
+    } else {
+        wrong
+    })
+}
+
+/// One keystroke for the backend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stroke {
+    Text(String),
+    Key(Key),
+}
+
+impl Stroke {
+    pub fn send(&self, backend: &mut impl InputBackend) -> enigo::InputResult<()> {
+        match self {
+            Stroke::Text(text) => backend.text(text),
+            Stroke::Key(key) => {
+                backend.key(key.to_enigo(), Direction::Press)?;
+                backend.key(key.to_enigo(), Direction::Release)
+            }
+        }
+    }
+}
+
+/// Hands out the keystrokes for a text one at a time, with the pause to
+/// leave after each.
+#[derive(Debug, Clone)]
+pub struct Typist {
+    style: TypingStyle,
+    queue: VecDeque<Token>,
+    /// Characters already mistyped once, which are retyped correctly.
+    corrections: usize,
+}
+
+impl Typist {
+    pub fn new(text: &str, style: TypingStyle) -> Result<Self, String> {
+        Ok(Self {
+            style,
+            queue: parse(text)?.into(),
+            corrections: 0,
+        })
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn next(&mut self, rng: &mut impl Rng) -> Option<(Stroke, Duration)> {
+        let token = self.queue.pop_front()?;
+        let retyping = self.corrections > 0;
+        self.corrections = self.corrections.saturating_sub(1);
+        let c = match token {
+            Token::Key(key) => return Some((Stroke::Key(key), self.delay(rng))),
+            Token::Char(c) => c,
+        };
+        if self.style.is_instant() {
+            // Nothing to pace, so send the whole run of text at once
+            let mut text = c.to_string();
+            while let Some(Token::Char(c)) = self.queue.front() {
+                text.push(*c);
+                self.queue.pop_front();
+            }
+            return Some((Stroke::Text(text), Duration::ZERO));
+        }
+        if !retyping && rng.random_bool(self.style.typo_chance()) {
+            if let Some(wrong) = neighbour(c, rng) {
+                self.queue.push_front(Token::Char(c));
+                self.queue.push_front(Token::Key(Key(Keycode::Backspace)));
+                self.corrections = 2;
+                return Some((Stroke::Text(wrong.to_string()), self.delay(rng)));
+            }
+        }
+        Some((Stroke::Text(c.to_string()), self.delay(rng)))
+    }
+
+    fn delay(&self, rng: &mut impl Rng) -> Duration {
+        let ms = self.style.distribution.sample(
+            rng,
+            self.style.min_char_delay_ms,
+            self.style.max_char_delay_ms,
+        );
+        Duration::from_millis(ms)
+    }
+}
+
+/// What the Type Text panel and `mourse --type` type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypeTextSettings {
+    pub text: String,
+    pub style: TypingStyle,
+    /// Time to click into the target field before typing starts.
+    pub start_delay_ms: u64,
+    pub seed: Option<u64>,
+}
+
+impl Default for TypeTextSettings {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            style: TypingStyle {
+                min_char_delay_ms: 30,
+                max_char_delay_ms: 120,
+                ..TypingStyle::default()
+            },
+            start_delay_ms: 3000,
+            seed: None,
+        }
+    }
+}
+
+pub struct TypeTick {
+    pub delay: Duration,
+    pub finished: bool,
+}
+
+pub struct TypingWorker<B: InputBackend> {
+    pub seed: u64,
+    pub backend: B,
+    typist: Typist,
+    rng: SessionRng,
+}
+
+impl<B: InputBackend> TypingWorker<B> {
+    pub fn new(settings: &TypeTextSettings, backend: B) -> Result<Self, String> {
+        let (rng, seed) = session_rng(settings.seed);
+        Ok(Self {
+            seed,
+            backend,
+            typist: Typist::new(&settings.text, settings.style)?,
+            rng,
+        })
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.typist.remaining()
+    }
+
+    pub fn step(&mut self) -> TypeTick {
+        let Some((stroke, delay)) = self.typist.next(&mut self.rng) else {
+            return TypeTick {
+                delay: Duration::ZERO,
+                finished: true,
+            };
+        };
+        if let Err(e) = stroke.send(&mut self.backend) {
+            eprintln!("Failed to type {:?}: {}", stroke, e);
+        }
+        TypeTick {
+            delay,
+            finished: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TextTyper {
+    is_typing: Arc<AtomicBool>,
+    remaining: Arc<AtomicU64>,
+    jitter_stats: Arc<Mutex<JitterStats>>,
+    last_seed: Arc<AtomicU64>,
+}
+
+impl Default for TextTyper {
+    fn default() -> Self {
+        Self {
+            is_typing: Arc::new(AtomicBool::new(false)),
+            remaining: Arc::new(AtomicU64::new(0)),
+            jitter_stats: Arc::new(Mutex::new(JitterStats::default())),
+            last_seed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl TextTyper {
+    const POLL: Duration = Duration::from_millis(50);
+
+    /// Checks the text and starts typing it after the start delay, unless
+    /// already typing.
+    pub fn start_typing(&mut self, settings: TypeTextSettings) -> Result<(), String> {
+        Typist::new(&settings.text, settings.style)?;
+        if !self.is_typing.load(Ordering::SeqCst) {
+            self.is_typing.store(true, Ordering::SeqCst);
+            let is_typing = Arc::clone(&self.is_typing);
+            let remaining = Arc::clone(&self.remaining);
+            let jitter_stats = Arc::clone(&self.jitter_stats);
+            let last_seed = Arc::clone(&self.last_seed);
+
+            thread::spawn(move || {
+                let mut worker = TypingWorker::new(&settings, create_enigo())
+                    .expect("text was checked before starting");
+                last_seed.store(worker.seed, Ordering::SeqCst);
+                remaining.store(worker.remaining() as u64, Ordering::SeqCst);
+                println!("Typing with seed {}", worker.seed);
+
+                let mut waited = Duration::ZERO;
+                let start_delay = Duration::from_millis(settings.start_delay_ms);
+                while waited < start_delay && is_typing.load(Ordering::SeqCst) {
+                    let slice = Self::POLL.min(start_delay - waited);
+                    thread::sleep(slice);
+                    waited += slice;
+                }
+
+                let mut scheduler = DeadlineScheduler::new(jitter_stats);
+                while is_typing.load(Ordering::SeqCst) {
+                    let tick = worker.step();
+                    remaining.store(worker.remaining() as u64, Ordering::SeqCst);
+                    if tick.finished {
+                        break;
+                    }
+                    scheduler.wait(tick.delay);
+                }
+                is_typing.store(false, Ordering::SeqCst);
+            });
+        }
+        Ok(())
+    }
+
+    pub fn stop_typing(&mut self) {
+        self.is_typing.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.is_typing.load(Ordering::SeqCst)
+    }
+
+    pub fn get_remaining(&self) -> u64 {
+        self.remaining.load(Ordering::SeqCst)
+    }
+
+    pub fn get_last_seed(&self) -> u64 {
+        self.last_seed.load(Ordering::SeqCst)
+    }
+
+    pub fn get_jitter_stats(&self) -> JitterStats {
+        *self.jitter_stats.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(
+            parse("a{enter}{{b}}{ F5 }").unwrap(),
+            vec![
+                Token::Char('a'),
+                Token::Key(Key(Keycode::Enter)),
+                Token::Char('{'),
+                Token::Char('b'),
+                Token::Char('}'),
+                Token::Key(Key(Keycode::F5)),
+            ]
+        );
+        assert_eq!(parse("").unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn rejects_bad_escapes() {
+        for text in ["{ENTER", "a}", "{NOPE}", "{F13}", "{}"] {
+            assert!(parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn nan_typo_rate_types_plainly() {
+        let style = TypingStyle {
+            max_char_delay_ms: 5,
+            typo_percent: f64::NAN,
+            ..TypingStyle::default()
+        };
+        let mut typist = Typist::new("abc", style).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut typed = String::new();
+        while let Some((stroke, _)) = typist.next(&mut rng) {
+            match stroke {
+                Stroke::Text(text) => typed.push_str(&text),
+                Stroke::Key(key) => panic!("unexpected key {:?}", key),
+            }
+        }
+        assert_eq!(typed, "abc");
+    }
+
+    #[test]
+    fn escape_types_text_literally() {
+        let text = "{ENTER} }{ {{";
+        let chars: Vec<Token> = text.chars().map(Token::Char).collect();
+        assert_eq!(parse(&escape(text)).unwrap(), chars);
+    }
+}
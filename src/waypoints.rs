@@ -0,0 +1,218 @@
+use crate::coords::{CoordSpace, Layout, ScreenPoint};
+use crate::mouse_button::SerializableMouseButton;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Waypoint {
+    pub x: f64,
+    pub y: f64,
+    pub space: CoordSpace,
+    /// Pixel offset from where the cursor was when the mover started.
+    /// Overrides `space`.
+    pub relative: bool,
+    pub dwell_ms: u64,
+    pub click: Option<SerializableMouseButton>,
+}
+
+impl Default for Waypoint {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            space: CoordSpace::Absolute,
+            relative: false,
+            dwell_ms: 500,
+            click: None,
+        }
+    }
+}
+
+impl Waypoint {
+    pub fn point(&self) -> ScreenPoint {
+        ScreenPoint {
+            x: self.x,
+            y: self.y,
+            space: self.space,
+        }
+    }
+
+    pub fn resolve(&self, anchor: (i32, i32), layout: &Layout) -> Option<(i32, i32)> {
+        if self.relative {
+            Some((
+                anchor.0 + self.x.round() as i32,
+                anchor.1 + self.y.round() as i32,
+            ))
+        } else {
+            self.point().resolve(layout)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum PathMode {
+    #[default]
+    Loop,
+    PingPong,
+    Once,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WaypointPath {
+    pub waypoints: Vec<Waypoint>,
+    pub mode: PathMode,
+}
+
+/// Walks the indices of a path according to its mode.
+#[derive(Debug, Default)]
+pub struct PathCursor {
+    index: usize,
+    backwards: bool,
+}
+
+impl PathCursor {
+    /// Returns the index to visit now, or `None` once a `Once` path is done.
+    pub fn next(&mut self, len: usize, mode: PathMode) -> Option<usize> {
+        if self.index >= len {
+            return None;
+        }
+        let current = self.index;
+        match mode {
+            PathMode::Once => self.index += 1,
+            PathMode::Loop => self.index = (self.index + 1) % len,
+            PathMode::PingPong if len == 1 => {}
+            PathMode::PingPong => {
+                if self.backwards && self.index == 0 {
+                    self.backwards = false;
+                } else if !self.backwards && self.index == len - 1 {
+                    self.backwards = true;
+                }
+                if self.backwards {
+                    self.index -= 1;
+                } else {
+                    self.index += 1;
+                }
+            }
+        }
+        Some(current)
+    }
+}
+
+/// Parses `x,y[,dwell_ms[,click[,relative]]]` rows. Blank lines, `#` comments
+/// and a header row starting with `x` are skipped; `click` is `left`,
+/// `right`, `middle` or empty, and `relative` is `true`/`false` or `1`/`0`.
+/// Writing both coordinates as percentages (`50%,25%`) places the point on
+/// the monitor at index `primary`, normally the primary one, independent of
+/// its resolution.
+pub fn parse_csv(text: &str, primary: usize) -> Result<Vec<Waypoint>, String> {
+    let mut waypoints = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.to_lowercase().starts_with('x') {
+            continue;
+        }
+        let fail = |what: &str| format!("Line {}: {}", number + 1, what);
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() < 2 || fields.len() > 5 {
+            return Err(fail("expected x,y[,dwell_ms[,click[,relative]]]"));
+        }
+
+        let mut waypoint = Waypoint::default();
+        match (fields[0].strip_suffix('%'), fields[1].strip_suffix('%')) {
+            (Some(x), Some(y)) => {
+                waypoint.x = x.trim().parse().map_err(|_| fail("invalid x"))?;
+                waypoint.y = y.trim().parse().map_err(|_| fail("invalid y"))?;
+                waypoint.space = CoordSpace::MonitorPercent { monitor: primary };
+            }
+            (None, None) => {
+                waypoint.x = fields[0].parse().map_err(|_| fail("invalid x"))?;
+                waypoint.y = fields[1].parse().map_err(|_| fail("invalid y"))?;
+            }
+            _ => return Err(fail("x and y must both be pixels or both percentages")),
+        }
+        if let Some(dwell) = fields.get(2).filter(|f| !f.is_empty()) {
+            waypoint.dwell_ms = dwell.parse().map_err(|_| fail("invalid dwell_ms"))?;
+        }
+        if let Some(click) = fields.get(3).filter(|f| !f.is_empty()) {
+            waypoint.click = Some(match click.to_lowercase().as_str() {
+                "left" => SerializableMouseButton::Left,
+                "right" => SerializableMouseButton::Right,
+                "middle" => SerializableMouseButton::Middle,
+                _ => return Err(fail("click must be left, right or middle")),
+            });
+        }
+        if let Some(relative) = fields.get(4).filter(|f| !f.is_empty()) {
+            waypoint.relative = match relative.to_lowercase().as_str() {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                _ => return Err(fail("relative must be true or false")),
+            };
+        }
+        waypoints.push(waypoint);
+    }
+    Ok(waypoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rows() {
+        let text = "x,y,dwell_ms,click,relative\n\
+                    # comment\n\
+                    \n\
+                    10, 20\n\
+                    50%,25%,100,right\n\
+                    -5,5,,,true\n";
+        let waypoints = parse_csv(text, 1).unwrap();
+        assert_eq!(waypoints.len(), 3);
+        assert_eq!((waypoints[0].x, waypoints[0].y), (10.0, 20.0));
+        assert_eq!(waypoints[0].dwell_ms, Waypoint::default().dwell_ms);
+        assert_eq!(
+            waypoints[1].space,
+            CoordSpace::MonitorPercent { monitor: 1 }
+        );
+        assert_eq!(waypoints[1].dwell_ms, 100);
+        assert_eq!(waypoints[1].click, Some(SerializableMouseButton::Right));
+        assert!(waypoints[2].relative);
+        assert_eq!(
+            waypoints[2].resolve((100, 100), &Layout::default()),
+            Some((95, 105))
+        );
+    }
+
+    #[test]
+    fn reports_bad_rows() {
+        for (text, line) in [
+            ("1", "Line 1"),
+            ("1,2\n1,2,3,left,true,extra", "Line 2"),
+            ("50%,25", "Line 1"),
+            ("a,2", "Line 1"),
+            ("1,2,3,up", "Line 1"),
+            ("1,2,3,left,maybe", "Line 1"),
+        ] {
+            let error = parse_csv(text, 0).unwrap_err();
+            assert!(error.starts_with(line), "{:?}: {}", text, error);
+        }
+    }
+
+    fn visits(len: usize, mode: PathMode, count: usize) -> Vec<Option<usize>> {
+        let mut cursor = PathCursor::default();
+        (0..count).map(|_| cursor.next(len, mode)).collect()
+    }
+
+    #[test]
+    fn cursor_follows_mode() {
+        let some = |indices: &[usize]| indices.iter().map(|&i| Some(i)).collect::<Vec<_>>();
+        assert_eq!(visits(3, PathMode::Loop, 5), some(&[0, 1, 2, 0, 1]));
+        assert_eq!(
+            visits(3, PathMode::PingPong, 7),
+            some(&[0, 1, 2, 1, 0, 1, 2])
+        );
+        assert_eq!(visits(1, PathMode::PingPong, 3), some(&[0, 0, 0]));
+        assert_eq!(visits(2, PathMode::Once, 3), vec![Some(0), Some(1), None]);
+        assert_eq!(visits(0, PathMode::Loop, 1), vec![None]);
+    }
+}
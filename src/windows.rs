@@ -0,0 +1,508 @@
+use crate::coords::Rect;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Which top-level window window-relative coordinates are measured against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub enum WindowTarget {
+    /// Whichever window has focus.
+    #[default]
+    Focused,
+    /// The first window whose title contains this text, ignoring case.
+    Title(String),
+    /// The first window whose WM_CLASS instance or class name equals this,
+    /// ignoring case.
+    Class(String),
+}
+
+impl WindowTarget {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowTarget::Focused => "Focused window",
+            WindowTarget::Title(_) => "Title contains",
+            WindowTarget::Class(_) => "WM_CLASS is",
+        }
+    }
+
+    /// Blank titles and classes match nothing rather than the first window
+    /// that happens to be listed.
+    pub fn matches(&self, info: &WindowInfo) -> bool {
+        match self {
+            WindowTarget::Focused => true,
+            WindowTarget::Title(title) | WindowTarget::Class(title) if title.trim().is_empty() => {
+                false
+            }
+            WindowTarget::Title(title) => info
+                .title
+                .to_lowercase()
+                .contains(&title.trim().to_lowercase()),
+            WindowTarget::Class(class) => {
+                let class = class.trim();
+                info.instance.eq_ignore_ascii_case(class) || info.class.eq_ignore_ascii_case(class)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WindowInfo {
+    pub title: String,
+    pub instance: String,
+    pub class: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowState {
+    Ready(Rect),
+    /// The window exists but another one has focus.
+    Unfocused,
+    /// No matching window is open.
+    Missing,
+}
+
+/// Follows the target window across moves and resizes over one long-lived
+/// display connection. Lookups are throttled so fast clickers do not turn
+/// every click into several X round trips.
+#[derive(Debug)]
+pub struct WindowTracker {
+    target: WindowTarget,
+    #[cfg(target_os = "linux")]
+    display: x11::Display,
+    #[cfg(target_os = "linux")]
+    window: Option<u32>,
+    checked: Option<(Instant, WindowState)>,
+}
+
+impl WindowTracker {
+    const REFRESH: Duration = Duration::from_millis(100);
+
+    /// `None` where window queries are not supported or the display cannot
+    /// be opened.
+    pub fn connect(target: WindowTarget) -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            Some(Self {
+                target,
+                display: x11::Display::open()?,
+                window: None,
+                checked: None,
+            })
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = target;
+            None
+        }
+    }
+
+    pub fn state(&mut self) -> WindowState {
+        match self.checked {
+            Some((at, state)) if at.elapsed() < Self::REFRESH => state,
+            _ => {
+                let state = self.query();
+                self.checked = Some((Instant::now(), state));
+                state
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn query(&mut self) -> WindowState {
+        let focused = self.display.focused_window();
+        let window = match self.target {
+            WindowTarget::Focused => focused,
+            // Stick with the window found last time while it still matches,
+            // so two candidates do not make the target flip between them
+            _ => self
+                .window
+                .filter(|&w| {
+                    self.display
+                        .info(w)
+                        .is_some_and(|i| self.target.matches(&i))
+                })
+                .or_else(|| self.display.find(&self.target)),
+        };
+        self.window = window;
+        let Some(window) = window else {
+            return WindowState::Missing;
+        };
+        let Some(rect) = self.display.geometry(window) else {
+            self.window = None;
+            return WindowState::Missing;
+        };
+        if focused.is_some_and(|f| self.display.is_within(f, window)) {
+            WindowState::Ready(rect)
+        } else {
+            WindowState::Unfocused
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn query(&mut self) -> WindowState {
+        WindowState::Missing
+    }
+}
+
+/// Reads whichever window has focus right now over one long-lived display
+/// connection. Unlike [`WindowTracker`] it never sticks to a window, so it
+/// follows focus as it moves between windows.
+#[derive(Debug)]
+pub struct FocusWatcher {
+    #[cfg(target_os = "linux")]
+    display: x11::Display,
+}
+
+impl FocusWatcher {
+    /// `None` where window queries are not supported or the display cannot
+    /// be opened.
+    pub fn connect() -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            Some(Self {
+                display: x11::Display::open()?,
+            })
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    pub fn focused(&self) -> Option<WindowInfo> {
+        #[cfg(target_os = "linux")]
+        {
+            self.display.info(self.display.focused_window()?)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+}
+
+/// The top-level window under a desktop position, for picking a target by
+/// clicking it.
+pub fn window_at(point: (i32, i32)) -> Option<WindowInfo> {
+    #[cfg(target_os = "linux")]
+    {
+        let display = x11::Display::open()?;
+        let window = display.window_at(point)?;
+        display.info(window)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = point;
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use super::{WindowInfo, WindowTarget};
+    use crate::coords::Rect;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, Window};
+    use x11rb::rust_connection::RustConnection;
+
+    pub struct Display {
+        conn: RustConnection,
+        root: Window,
+        net_active_window: Atom,
+        net_client_list: Atom,
+        net_wm_name: Atom,
+        utf8_string: Atom,
+        wm_state: Atom,
+    }
+
+    impl std::fmt::Debug for Display {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Display").field("root", &self.root).finish()
+        }
+    }
+
+    impl Display {
+        pub fn open() -> Option<Self> {
+            let (conn, screen_num) = x11rb::connect(None).ok()?;
+            let root = conn.setup().roots.get(screen_num)?.root;
+            let atom = |name: &[u8]| -> Option<Atom> {
+                Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom)
+            };
+            Some(Self {
+                net_active_window: atom(b"_NET_ACTIVE_WINDOW")?,
+                net_client_list: atom(b"_NET_CLIENT_LIST")?,
+                net_wm_name: atom(b"_NET_WM_NAME")?,
+                utf8_string: atom(b"UTF8_STRING")?,
+                wm_state: atom(b"WM_STATE")?,
+                conn,
+                root,
+            })
+        }
+
+        fn property(&self, window: Window, property: Atom, kind: AtomEnum) -> Option<Vec<u8>> {
+            self.property_of(window, property, kind.into())
+        }
+
+        fn property_of(&self, window: Window, property: Atom, kind: Atom) -> Option<Vec<u8>> {
+            let reply = self
+                .conn
+                .get_property(false, window, property, kind, 0, u32::MAX / 4)
+                .ok()?
+                .reply()
+                .ok()?;
+            (reply.type_ != x11rb::NONE).then_some(reply.value)
+        }
+
+        fn windows_property(&self, window: Window, property: Atom) -> Vec<Window> {
+            let reply = self
+                .conn
+                .get_property(false, window, property, AtomEnum::WINDOW, 0, u32::MAX / 4)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok());
+            reply
+                .and_then(|reply| Some(reply.value32()?.filter(|&w| w != x11rb::NONE).collect()))
+                .unwrap_or_default()
+        }
+
+        /// The window manager's `_NET_ACTIVE_WINDOW`, which names the
+        /// top-level window, falling back to the raw input focus when no
+        /// EWMH window manager is running.
+        pub fn focused_window(&self) -> Option<Window> {
+            if let Some(&active) = self
+                .windows_property(self.root, self.net_active_window)
+                .first()
+            {
+                return Some(active);
+            }
+            let focus = self.conn.get_input_focus().ok()?.reply().ok()?.focus;
+            // 0 is None and 1 is PointerRoot
+            (focus > 1 && focus != self.root).then_some(focus)
+        }
+
+        /// Whether `window` is `ancestor` or one of its descendants.
+        pub fn is_within(&self, mut window: Window, ancestor: Window) -> bool {
+            loop {
+                if window == ancestor {
+                    return true;
+                }
+                match self
+                    .conn
+                    .query_tree(window)
+                    .ok()
+                    .and_then(|c| c.reply().ok())
+                {
+                    Some(tree) if tree.parent != x11rb::NONE && tree.parent != self.root => {
+                        window = tree.parent;
+                    }
+                    _ => return false,
+                }
+            }
+        }
+
+        /// Managed windows from `_NET_CLIENT_LIST`, or the root's children
+        /// when no EWMH window manager is running.
+        fn clients(&self) -> Vec<Window> {
+            let clients = self.windows_property(self.root, self.net_client_list);
+            if !clients.is_empty() {
+                return clients;
+            }
+            self.conn
+                .query_tree(self.root)
+                .ok()
+                .and_then(|c| c.reply().ok())
+                .map(|tree| tree.children)
+                .unwrap_or_default()
+        }
+
+        pub fn find(&self, target: &WindowTarget) -> Option<Window> {
+            self.clients()
+                .into_iter()
+                .find(|&window| self.info(window).is_some_and(|info| target.matches(&info)))
+        }
+
+        pub fn info(&self, window: Window) -> Option<WindowInfo> {
+            let title = self
+                .property_of(window, self.net_wm_name, self.utf8_string)
+                .or_else(|| self.property(window, AtomEnum::WM_NAME.into(), AtomEnum::ANY))
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+            let class = self.property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING);
+            if title.is_none() && class.is_none() {
+                return None;
+            }
+            // WM_CLASS is two NUL-terminated strings: instance, then class
+            let class = class.unwrap_or_default();
+            let mut parts = class
+                .split(|&b| b == 0)
+                .map(|part| String::from_utf8_lossy(part).into_owned());
+            Some(WindowInfo {
+                title: title.unwrap_or_default(),
+                instance: parts.next().unwrap_or_default(),
+                class: parts.next().unwrap_or_default(),
+            })
+        }
+
+        pub fn geometry(&self, window: Window) -> Option<Rect> {
+            let geometry = self.conn.get_geometry(window).ok()?.reply().ok()?;
+            let origin = self
+                .conn
+                .translate_coordinates(window, self.root, 0, 0)
+                .ok()?
+                .reply()
+                .ok()?;
+            Some(Rect {
+                x: origin.dst_x as i32,
+                y: origin.dst_y as i32,
+                width: geometry.width as i32,
+                height: geometry.height as i32,
+            })
+        }
+
+        /// The client window under `point`. The root's child there is
+        /// usually a window manager frame, so this looks inside it for the
+        /// window carrying `WM_STATE`, as xprop and xdotool do.
+        pub fn window_at(&self, point: (i32, i32)) -> Option<Window> {
+            let frame = self
+                .conn
+                .translate_coordinates(self.root, self.root, point.0 as i16, point.1 as i16)
+                .ok()?
+                .reply()
+                .ok()?
+                .child;
+            if frame == x11rb::NONE {
+                return None;
+            }
+            Some(self.client_of(frame, 4).unwrap_or(frame))
+        }
+
+        fn client_of(&self, window: Window, depth: u32) -> Option<Window> {
+            if self
+                .property(window, self.wm_state, AtomEnum::ANY)
+                .is_some()
+            {
+                return Some(window);
+            }
+            if depth == 0 {
+                return None;
+            }
+            let tree = self.conn.query_tree(window).ok()?.reply().ok()?;
+            tree.children
+                .into_iter()
+                .rev()
+                .find_map(|child| self.client_of(child, depth - 1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(title: &str, instance: &str, class: &str) -> WindowInfo {
+        WindowInfo {
+            title: title.into(),
+            instance: instance.into(),
+            class: class.into(),
+        }
+    }
+
+    #[test]
+    fn titles_match_anywhere_ignoring_case() {
+        let browser = info("Inbox - Mozilla Firefox", "Navigator", "firefox");
+        let title = |pattern: &str| WindowTarget::Title(pattern.into());
+        assert!(title("firefox").matches(&browser));
+        assert!(title("  INBOX ").matches(&browser));
+        assert!(title("Inbox - Mozilla Firefox").matches(&browser));
+        assert!(!title("Thunderbird").matches(&browser));
+        assert!(!title("").matches(&browser));
+        assert!(!title("   ").matches(&browser));
+        assert!(WindowTarget::Focused.matches(&browser));
+        assert!(WindowTarget::Focused.matches(&WindowInfo::default()));
+    }
+
+    #[test]
+    fn classes_match_the_instance_or_class_exactly() {
+        let terminal = info("~/crate", "xterm", "XTerm");
+        let class = |pattern: &str| WindowTarget::Class(pattern.into());
+        assert!(class("xterm").matches(&terminal));
+        assert!(class("XTERM").matches(&terminal));
+        assert!(class(" XTerm ").matches(&terminal));
+        assert!(!class("xter").matches(&terminal));
+        assert!(!class("crate").matches(&terminal));
+        assert!(!class("").matches(&info("", "", "")));
+    }
+
+    /// Needs an X server without a window manager, e.g.
+    /// `xvfb-run cargo test -- --ignored`.
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs an X display such as Xvfb"]
+    fn tracks_a_dummy_window() {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::{
+            AtomEnum, ConfigureWindowAux, ConnectionExt as _, CreateWindowAux, InputFocus,
+            PropMode, WindowClass,
+        };
+        use x11rb::wrapper::ConnectionExt as _;
+
+        let (conn, screen_num) = x11rb::connect(None).expect("no X display");
+        let screen = &conn.setup().roots[screen_num];
+        let window = conn.generate_id().unwrap();
+        conn.create_window(
+            screen.root_depth,
+            window,
+            screen.root,
+            10,
+            20,
+            200,
+            100,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new(),
+        )
+        .unwrap();
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            b"Mourse Test Window",
+        )
+        .unwrap();
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_CLASS,
+            AtomEnum::STRING,
+            b"mourse-test\0MourseTest\0",
+        )
+        .unwrap();
+        conn.map_window(window).unwrap();
+        conn.set_input_focus(InputFocus::PARENT, window, x11rb::CURRENT_TIME)
+            .unwrap();
+        conn.sync().unwrap();
+
+        let mut tracker = WindowTracker::connect(WindowTarget::Class("MourseTest".into())).unwrap();
+        let rect = Rect {
+            x: 10,
+            y: 20,
+            width: 200,
+            height: 100,
+        };
+        assert_eq!(tracker.state(), WindowState::Ready(rect));
+        let focused = FocusWatcher::connect().unwrap().focused().unwrap();
+        assert_eq!(focused.title, "Mourse Test Window");
+        assert_eq!(window_at((50, 50)).unwrap().instance, "mourse-test");
+
+        // Following the window as it moves
+        conn.configure_window(window, &ConfigureWindowAux::new().x(300))
+            .unwrap();
+        conn.sync().unwrap();
+        std::thread::sleep(WindowTracker::REFRESH);
+        assert_eq!(tracker.state(), WindowState::Ready(Rect { x: 300, ..rect }));
+
+        conn.destroy_window(window).unwrap();
+        conn.sync().unwrap();
+        std::thread::sleep(WindowTracker::REFRESH);
+        assert_eq!(tracker.state(), WindowState::Missing);
+    }
+}
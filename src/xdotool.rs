@@ -0,0 +1,985 @@
+use crate::backend::{Action, MockBackend};
+use crate::coords::{Layout, ScreenPoint};
+use crate::drag;
+use crate::macros::{Key, PlaybackSettings};
+use crate::mouse_button::SerializableMouseButton;
+use crate::player::PlayerWorker;
+use crate::seed::session_rng;
+use crate::steps::{Input, Program, Step, StepError};
+use crate::typing::{self, Token, TypingStyle};
+use device_query::Keycode;
+
+/// X keysym names for the keys Mourse knows. Export uses the first name for
+/// a key; import also accepts the later aliases.
+const KEYSYMS: &[(&str, Keycode)] = &[
+    ("Return", Keycode::Enter),
+    ("KP_Enter", Keycode::NumpadEnter),
+    ("space", Keycode::Space),
+    ("Tab", Keycode::Tab),
+    ("Escape", Keycode::Escape),
+    ("BackSpace", Keycode::Backspace),
+    ("Delete", Keycode::Delete),
+    ("Insert", Keycode::Insert),
+    ("Home", Keycode::Home),
+    ("End", Keycode::End),
+    ("Page_Up", Keycode::PageUp),
+    ("Prior", Keycode::PageUp),
+    ("Page_Down", Keycode::PageDown),
+    ("Next", Keycode::PageDown),
+    ("Up", Keycode::Up),
+    ("Down", Keycode::Down),
+    ("Left", Keycode::Left),
+    ("Right", Keycode::Right),
+    ("Caps_Lock", Keycode::CapsLock),
+    ("Control_L", Keycode::LControl),
+    ("Control_R", Keycode::RControl),
+    ("Shift_L", Keycode::LShift),
+    ("Shift_R", Keycode::RShift),
+    ("Alt_L", Keycode::LAlt),
+    ("Alt_R", Keycode::RAlt),
+    ("Super_L", Keycode::LMeta),
+    ("Super_R", Keycode::RMeta),
+    ("Super_L", Keycode::Command),
+    ("Super_R", Keycode::RCommand),
+    ("Alt_L", Keycode::LOption),
+    ("Alt_R", Keycode::ROption),
+    ("minus", Keycode::Minus),
+    ("equal", Keycode::Equal),
+    ("bracketleft", Keycode::LeftBracket),
+    ("bracketright", Keycode::RightBracket),
+    ("backslash", Keycode::BackSlash),
+    ("semicolon", Keycode::Semicolon),
+    ("apostrophe", Keycode::Apostrophe),
+    ("grave", Keycode::Grave),
+    ("comma", Keycode::Comma),
+    ("period", Keycode::Dot),
+    ("slash", Keycode::Slash),
+    ("KP_0", Keycode::Numpad0),
+    ("KP_1", Keycode::Numpad1),
+    ("KP_2", Keycode::Numpad2),
+    ("KP_3", Keycode::Numpad3),
+    ("KP_4", Keycode::Numpad4),
+    ("KP_5", Keycode::Numpad5),
+    ("KP_6", Keycode::Numpad6),
+    ("KP_7", Keycode::Numpad7),
+    ("KP_8", Keycode::Numpad8),
+    ("KP_9", Keycode::Numpad9),
+    ("KP_Subtract", Keycode::NumpadSubtract),
+    ("KP_Add", Keycode::NumpadAdd),
+    ("KP_Divide", Keycode::NumpadDivide),
+    ("KP_Multiply", Keycode::NumpadMultiply),
+    ("KP_Equal", Keycode::NumpadEquals),
+    ("KP_Decimal", Keycode::NumpadDecimal),
+];
+
+/// xdotool's own shorthands for modifiers.
+const ALIASES: &[(&str, Keycode)] = &[
+    ("ctrl", Keycode::LControl),
+    ("control", Keycode::LControl),
+    ("shift", Keycode::LShift),
+    ("alt", Keycode::LAlt),
+    ("super", Keycode::LMeta),
+    ("meta", Keycode::LMeta),
+];
+
+/// Default pause between `click --repeat` clicks, as in xdotool.
+const REPEAT_DELAY_MS: u64 = 100;
+
+const COMMANDS: &[&str] = &[
+    "mousemove",
+    "click",
+    "mousedown",
+    "mouseup",
+    "key",
+    "keydown",
+    "keyup",
+    "type",
+    "sleep",
+];
+
+fn keysym(key: Keycode) -> String {
+    if let Some((name, _)) = KEYSYMS.iter().find(|(_, code)| *code == key) {
+        return name.to_string();
+    }
+    let name = key.to_string();
+    match name.strip_prefix("Key") {
+        // Key0..Key9 are the digits
+        Some(digit) if digit.len() == 1 => digit.to_string(),
+        _ if name.len() == 1 => name.to_lowercase(),
+        _ => name,
+    }
+}
+
+/// The keys one keysym presses. An upper-case letter is Shift plus the
+/// letter, which is what xdotool sends for it.
+fn keycodes(name: &str) -> Option<Vec<Keycode>> {
+    let known = KEYSYMS.iter().find(|(sym, _)| *sym == name).or_else(|| {
+        ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+    });
+    if let Some((_, code)) = known {
+        return Some(vec![*code]);
+    }
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_digit() => format!("Key{}", c),
+        (Some(c), None) if c.is_ascii_uppercase() => {
+            return Some(vec![Keycode::LShift, c.to_string().parse().ok()?]);
+        }
+        (Some(c), None) => c.to_ascii_uppercase().to_string(),
+        // F1 to F20 share their names
+        _ if name.starts_with('F') => name.to_string(),
+        _ => return None,
+    };
+    code.parse().ok().map(|code| vec![code])
+}
+
+/// Splits a shell script into commands of words, with the line each starts
+/// on. Handles the quoting, comments, `;` and line continuations that
+/// hand-written xdotool scripts use; expansions are left as literal text.
+fn split_commands(script: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut commands = Vec::new();
+    let mut words = Vec::new();
+    // `Some` once a word has started, so `''` still makes an empty word
+    let mut word: Option<String> = None;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        if word.is_none() && words.is_empty() {
+            start = line;
+        }
+        match c {
+            '\n' | ';' => {
+                words.extend(word.take());
+                if !words.is_empty() {
+                    commands.push((start, std::mem::take(&mut words)));
+                }
+                if c == '\n' {
+                    line += 1;
+                }
+            }
+            ' ' | '\t' | '\r' => words.extend(word.take()),
+            '#' if word.is_none() => while chars.next_if(|&c| c != '\n').is_some() {},
+            '\\' => match chars.next() {
+                Some('\n') => line += 1,
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => {}
+            },
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some('\\') if c == '"' => match chars.next() {
+                            Some('\n') => line += 1,
+                            Some(escaped @ ('"' | '\\' | '$' | '`')) => word.push(escaped),
+                            Some(other) => {
+                                word.push('\\');
+                                word.push(other);
+                            }
+                            None => {}
+                        },
+                        Some(inner) => {
+                            if inner == '\n' {
+                                line += 1;
+                            }
+                            word.push(inner);
+                        }
+                        None => return Err(format!("line {}: unterminated {} quote", start, c)),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    if !words.is_empty() {
+        commands.push((start, words));
+    }
+    Ok(commands)
+}
+
+fn number<T: std::str::FromStr>(word: Option<&String>, what: &str) -> Result<T, String> {
+    let word = word.ok_or_else(|| format!("missing {}", what))?;
+    word.parse()
+        .map_err(|_| format!("{} {:?} is not a number", what, word))
+}
+
+/// `sleep` durations in seconds, with the `s`, `m` and `h` suffixes GNU
+/// sleep accepts.
+fn seconds_to_ms(word: Option<&String>) -> Result<u64, String> {
+    let word = word.ok_or("missing sleep duration")?;
+    let (number, unit) = match word.char_indices().last() {
+        Some((i, 's')) => (&word[..i], 1.0),
+        Some((i, 'm')) => (&word[..i], 60.0),
+        Some((i, 'h')) => (&word[..i], 3600.0),
+        _ => (word.as_str(), 1.0),
+    };
+    let seconds: f64 = number
+        .parse()
+        .ok()
+        .filter(|s: &f64| s.is_finite() && *s >= 0.0)
+        .ok_or_else(|| format!("sleep duration {:?} is not a number", word))?;
+    Ok((seconds * unit * 1000.0).round() as u64)
+}
+
+fn button(number: u32) -> Option<SerializableMouseButton> {
+    match number {
+        1 => Some(SerializableMouseButton::Left),
+        2 => Some(SerializableMouseButton::Middle),
+        3 => Some(SerializableMouseButton::Right),
+        _ => None,
+    }
+}
+
+fn button_number(button: SerializableMouseButton) -> u32 {
+    match button {
+        SerializableMouseButton::Left => 1,
+        SerializableMouseButton::Middle => 2,
+        SerializableMouseButton::Right => 3,
+    }
+}
+
+/// Options a command accepts: each name and whether it takes a value.
+/// `--clearmodifiers` and `--sync` only matter to a live X session, so they
+/// are accepted and ignored.
+fn options<'a>(
+    args: &mut std::iter::Peekable<impl Iterator<Item = &'a String>>,
+    command: &str,
+    known: &[(&str, bool)],
+) -> Result<Vec<(&'a str, Option<&'a String>)>, String> {
+    let mut found = Vec::new();
+    while let Some(arg) = args.next_if(|arg| arg.starts_with("--")) {
+        if arg == "--" {
+            break;
+        }
+        match known.iter().find(|(name, _)| name == arg) {
+            Some((_, true)) => found.push((arg.as_str(), args.next())),
+            Some((_, false)) => found.push((arg.as_str(), None)),
+            None => return Err(format!("{} {} is not supported", command, arg)),
+        }
+    }
+    Ok(found)
+}
+
+fn option_value<T: std::str::FromStr>(
+    options: &[(&str, Option<&String>)],
+    name: &str,
+) -> Result<Option<T>, String> {
+    match options.iter().find(|(option, _)| *option == name) {
+        Some((_, value)) => number(*value, name).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Turns one `xdotool` invocation, which may chain several commands, into
+/// steps.
+fn xdotool(words: &[String], steps: &mut Vec<Step>) -> Result<(), String> {
+    let mut args = words.iter().peekable();
+    let is_command = |word: &&String| COMMANDS.contains(&word.as_str());
+    while let Some(command) = args.next() {
+        match command.as_str() {
+            "mousemove" => {
+                options(
+                    &mut args,
+                    command,
+                    &[("--sync", false), ("--clearmodifiers", false)],
+                )?;
+                let x = number(args.next(), "x")?;
+                let y = number(args.next(), "y")?;
+                steps.push(Step::Move {
+                    to: ScreenPoint::absolute((x, y)),
+                });
+            }
+            "click" => {
+                let opts = options(
+                    &mut args,
+                    command,
+                    &[
+                        ("--clearmodifiers", false),
+                        ("--repeat", true),
+                        ("--delay", true),
+                    ],
+                )?;
+                let repeat: u32 = option_value(&opts, "--repeat")?.unwrap_or(1).max(1);
+                let delay = option_value(&opts, "--delay")?.unwrap_or(REPEAT_DELAY_MS);
+                let number: u32 = number(args.next(), "button")?;
+                // Buttons 4 to 7 are the scroll wheel: up, down, left, right
+                let (amount, horizontal) = match number {
+                    4 => (-1, false),
+                    5 => (1, false),
+                    6 => (-1, true),
+                    7 => (1, true),
+                    _ => {
+                        let button = button(number)
+                            .ok_or_else(|| format!("mouse button {} is not supported", number))?;
+                        for i in 0..repeat {
+                            if i > 0 && delay > 0 {
+                                steps.push(Step::Wait { ms: delay });
+                            }
+                            steps.push(Step::Click { button });
+                        }
+                        continue;
+                    }
+                };
+                steps.push(Step::Scroll {
+                    amount: amount * repeat as i32,
+                    horizontal,
+                });
+            }
+            "mousedown" | "mouseup" => {
+                options(&mut args, command, &[("--clearmodifiers", false)])?;
+                let number = number(args.next(), "button")?;
+                let button = button(number)
+                    .ok_or_else(|| format!("mouse button {} is not supported", number))?;
+                steps.push(Step::press(Input::Button(button), command == "mousedown"));
+            }
+            "key" | "keydown" | "keyup" => {
+                let opts = options(
+                    &mut args,
+                    command,
+                    &[("--clearmodifiers", false), ("--delay", true)],
+                )?;
+                let delay: Option<u64> = option_value(&opts, "--delay")?;
+                let mut first = true;
+                while let Some(sequence) = args.next_if(|arg| !is_command(arg)) {
+                    if !first {
+                        if let Some(ms) = delay.filter(|&ms| ms > 0) {
+                            steps.push(Step::Wait { ms });
+                        }
+                    }
+                    first = false;
+                    let mut keys = Vec::new();
+                    for name in sequence.split('+') {
+                        let codes =
+                            keycodes(name).ok_or_else(|| format!("unknown key {:?}", name))?;
+                        keys.extend(codes.into_iter().map(|code| Input::Key(Key(code))));
+                    }
+                    if command != "keyup" {
+                        steps.extend(keys.iter().map(|&key| Step::press(key, true)));
+                    }
+                    if command != "keydown" {
+                        steps.extend(keys.iter().rev().map(|&key| Step::press(key, false)));
+                    }
+                }
+                if first {
+                    return Err(format!("{} needs a key", command));
+                }
+            }
+            "type" => {
+                let opts = options(
+                    &mut args,
+                    command,
+                    &[("--clearmodifiers", false), ("--delay", true)],
+                )?;
+                let delay = option_value(&opts, "--delay")?.unwrap_or(0);
+                let text: String = std::iter::from_fn(|| args.next_if(|arg| !is_command(arg)))
+                    .map(String::as_str)
+                    .collect();
+                if !text.is_empty() {
+                    steps.push(Step::Type {
+                        text: typing::escape(&text),
+                        style: TypingStyle {
+                            min_char_delay_ms: delay,
+                            max_char_delay_ms: delay,
+                            ..TypingStyle::default()
+                        },
+                    });
+                }
+            }
+            "sleep" => {
+                let ms = seconds_to_ms(args.next())?;
+                if ms > 0 {
+                    steps.push(Step::Wait { ms });
+                }
+            }
+            other => return Err(format!("xdotool {} is not supported", other)),
+        }
+    }
+    Ok(())
+}
+
+/// A loop being read, closed by `done`.
+struct OpenLoop {
+    line: usize,
+    count: Option<u32>,
+    steps: Vec<Step>,
+}
+
+/// Reads an xdotool shell script into macro steps. Besides `xdotool`
+/// commands (mousemove, click, mousedown, mouseup, key, keydown, keyup,
+/// type, sleep) it understands `sleep`, `for _ in $(seq N)` and
+/// `while true` loops with `break`, which is what `export` writes.
+pub fn import(script: &str) -> Result<Vec<Step>, String> {
+    let mut root = Vec::new();
+    let mut loops: Vec<OpenLoop> = Vec::new();
+    let mut awaiting_do = None;
+    for (line, words) in split_commands(script)? {
+        let fail = |message: String| format!("line {}: {}", line, message);
+        let mut words = words.as_slice();
+        if let Some(header) = awaiting_do.take() {
+            if words[0] != "do" {
+                return Err(format!(
+                    "line {}: expected `do` after the loop header",
+                    header
+                ));
+            }
+            words = &words[1..];
+            if words.is_empty() {
+                continue;
+            }
+        }
+        let depth = loops.len();
+        let steps = match loops.last_mut() {
+            Some(open) => &mut open.steps,
+            None => &mut root,
+        };
+        let word = |i: usize| words.get(i).map(String::as_str);
+        match words[0].as_str() {
+            "xdotool" => xdotool(&words[1..], steps).map_err(fail)?,
+            "sleep" if words.len() == 2 => {
+                let ms = seconds_to_ms(words.get(1)).map_err(fail)?;
+                if ms > 0 {
+                    steps.push(Step::Wait { ms });
+                }
+            }
+            "for" if word(2) == Some("in") && word(3) == Some("$(seq") && words.len() == 5 => {
+                let count = words[4]
+                    .strip_suffix(')')
+                    .and_then(|count| count.parse().ok())
+                    .ok_or_else(|| fail(format!("unsupported loop count {:?}", words[4])))?;
+                loops.push(OpenLoop {
+                    line,
+                    count: Some(count),
+                    steps: Vec::new(),
+                });
+                awaiting_do = Some(line);
+            }
+            "while" if matches!(word(1), Some("true" | ":")) && words.len() == 2 => {
+                loops.push(OpenLoop {
+                    line,
+                    count: None,
+                    steps: Vec::new(),
+                });
+                awaiting_do = Some(line);
+            }
+            "done" if words.len() == 1 => {
+                let open = loops
+                    .pop()
+                    .ok_or_else(|| fail("`done` without a loop".into()))?;
+                let step = Step::Loop {
+                    label: format!("loop{}", loops.len() + 1),
+                    count: open.count,
+                    steps: open.steps,
+                };
+                match loops.last_mut() {
+                    Some(parent) => parent.steps.push(step),
+                    None => root.push(step),
+                }
+            }
+            "break" if words.len() <= 2 => {
+                let levels: usize = match words.get(1) {
+                    Some(levels) => number(Some(levels), "break level").map_err(fail)?,
+                    None => 1,
+                };
+                if levels == 0 || levels > depth {
+                    return Err(fail(format!("break {} is outside any loop", levels)));
+                }
+                steps.push(Step::Break {
+                    label: format!("loop{}", depth + 1 - levels),
+                });
+            }
+            other => return Err(fail(format!("`{}` is not supported", other))),
+        }
+    }
+    if let Some(open) = loops.last() {
+        return Err(format!(
+            "line {}: loop is never closed with `done`",
+            open.line
+        ));
+    }
+    if let Some(line) = awaiting_do {
+        return Err(format!(
+            "line {}: expected `do` after the loop header",
+            line
+        ));
+    }
+    Program::compile(&root).map_err(|e| e.to_string())?;
+    Ok(root)
+}
+
+/// Quotes `text` for the shell so it reaches xdotool unchanged.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+fn seconds(ms: u64) -> String {
+    (ms as f64 / 1000.0).to_string()
+}
+
+struct Exporter<'a> {
+    layout: &'a Layout,
+    out: String,
+    /// Labels of the loops being written, innermost last.
+    labels: Vec<&'a str>,
+}
+
+impl<'a> Exporter<'a> {
+    fn line(&mut self, text: String) {
+        for _ in 0..self.labels.len() {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(&text);
+        self.out.push('\n');
+    }
+
+    /// Types and clears the characters collected so far.
+    fn type_run(&mut self, run: &mut String, delay_ms: u64) {
+        if run.is_empty() {
+            return;
+        }
+        let delay = match delay_ms {
+            0 => String::new(),
+            ms => format!("--delay {} ", ms),
+        };
+        let separator = if run.starts_with('-') { "-- " } else { "" };
+        self.line(format!(
+            "xdotool type {}{}{}",
+            delay,
+            separator,
+            quote(&std::mem::take(run))
+        ));
+    }
+
+    fn block(&mut self, steps: &'a [Step], prefix: &str) -> Result<(), StepError> {
+        let mut i = 0;
+        while i < steps.len() {
+            let path = format!("{}{}", prefix, i + 1);
+            // A press straight followed by its release is a tap
+            if let (
+                Step::Press {
+                    input: Input::Key(down),
+                },
+                Some(Step::Release {
+                    input: Input::Key(up),
+                }),
+            ) = (&steps[i], steps.get(i + 1))
+            {
+                if down == up {
+                    self.line(format!("xdotool key {}", keysym(down.0)));
+                    i += 2;
+                    continue;
+                }
+            }
+            self.step(&steps[i], &path)?;
+            i += 1;
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, step: &'a Step, path: &str) -> Result<(), StepError> {
+        let fail = |message: &str| StepError {
+            path: path.to_string(),
+            step: step.name(),
+            message: message.to_string(),
+        };
+        match step {
+            Step::Move { to } => {
+                let (x, y) = to
+                    .resolve(self.layout)
+                    .ok_or_else(|| fail("the point is not on the current screen layout"))?;
+                self.line(format!("xdotool mousemove {} {}", x, y));
+            }
+            Step::Click { button } => {
+                self.line(format!("xdotool click {}", button_number(*button)));
+            }
+            Step::Drag {
+                button,
+                from,
+                to,
+                glide,
+                hover_ms,
+            } => {
+                let resolve = |point: &ScreenPoint| {
+                    point
+                        .resolve(self.layout)
+                        .ok_or_else(|| fail("the point is not on the current screen layout"))
+                };
+                let (from, to) = (resolve(from)?, resolve(to)?);
+                let button = button_number(*button);
+                self.line(format!(
+                    "xdotool mousemove {} {} mousedown {}",
+                    from.0, from.1, button
+                ));
+                self.line(format!(
+                    "sleep {}",
+                    seconds(drag::PRESS_SETTLE.as_millis() as u64)
+                ));
+                // Bezier drags bow randomly on every run; the script keeps
+                // one sample of the curve
+                let path = glide.path(from, to, &mut session_rng(Some(0)).0);
+                let step_ms = 1000 / glide.step_hz.max(1) as u64;
+                let last = path.len() - 1;
+                for (i, (x, y)) in path.into_iter().enumerate() {
+                    self.line(format!("xdotool mousemove {} {}", x, y));
+                    let pause = if i < last { step_ms } else { *hover_ms };
+                    if pause > 0 {
+                        self.line(format!("sleep {}", seconds(pause)));
+                    }
+                }
+                self.line(format!("xdotool mouseup {}", button));
+            }
+            Step::Press { input } | Step::Release { input } => {
+                let pressed = matches!(step, Step::Press { .. });
+                let line = match input {
+                    Input::Button(button) => format!(
+                        "xdotool {} {}",
+                        if pressed { "mousedown" } else { "mouseup" },
+                        button_number(*button)
+                    ),
+                    Input::Key(key) => format!(
+                        "xdotool {} {}",
+                        if pressed { "keydown" } else { "keyup" },
+                        keysym(key.0)
+                    ),
+                };
+                self.line(line);
+            }
+            Step::Scroll { amount, horizontal } => {
+                let button = match (*horizontal, *amount < 0) {
+                    (false, true) => 4,
+                    (false, false) => 5,
+                    (true, true) => 6,
+                    (true, false) => 7,
+                };
+                let repeat = amount.unsigned_abs();
+                if repeat == 1 {
+                    self.line(format!("xdotool click {}", button));
+                } else {
+                    self.line(format!("xdotool click --repeat {} {}", repeat, button));
+                }
+            }
+            Step::Type { text, style } => {
+                // xdotool only has a fixed per-character delay, so random
+                // delays become their midpoint and typos are left out
+                let delay_ms = (style.min_char_delay_ms + style.max_char_delay_ms) / 2;
+                let mut run = String::new();
+                for token in typing::parse(text).map_err(|e| fail(&e))? {
+                    match token {
+                        Token::Char(c) => run.push(c),
+                        Token::Key(key) => {
+                            self.type_run(&mut run, delay_ms);
+                            self.line(format!("xdotool key {}", keysym(key.0)));
+                            if delay_ms > 0 {
+                                self.line(format!("sleep {}", seconds(delay_ms)));
+                            }
+                        }
+                    }
+                }
+                self.type_run(&mut run, delay_ms);
+            }
+            Step::Wait { ms } => self.line(format!("sleep {}", seconds(*ms))),
+            Step::WaitForKey { .. } => {
+                return Err(fail("xdotool scripts cannot wait for a key press"));
+            }
+            Step::If { .. } => return Err(fail("xdotool scripts cannot test conditions")),
+            Step::Loop {
+                label,
+                count,
+                steps,
+            } => {
+                self.line(match count {
+                    Some(count) => format!("for _ in $(seq {}); do", count),
+                    None => "while true; do".to_string(),
+                });
+                self.labels.push(label);
+                self.block(steps, &format!("{}.", path))?;
+                self.labels.pop();
+                self.line("done".to_string());
+            }
+            Step::Break { label } => {
+                let levels = self
+                    .labels
+                    .iter()
+                    .rev()
+                    .position(|open| open == label)
+                    .ok_or_else(|| fail("no enclosing loop has this label"))?
+                    + 1;
+                self.line(match levels {
+                    1 => "break".to_string(),
+                    levels => format!("break {}", levels),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes `steps` as a POSIX shell script of xdotool commands. Points are
+/// resolved to pixels on `layout`; waiting for keys and conditions have no
+/// xdotool equivalent and are reported as errors.
+pub fn export(name: &str, steps: &[Step], layout: &Layout) -> Result<String, StepError> {
+    let mut exporter = Exporter {
+        layout,
+        out: format!("#!/bin/sh\n# Macro {:?}, exported from Mourse\n", name),
+        labels: Vec::new(),
+    };
+    exporter.block(steps, "")?;
+    Ok(exporter.out)
+}
+
+/// Plays `steps` once on the mock backend, stopping endless macros after a
+/// fixed number of ticks. Waits between ticks are folded into the actions as
+/// sleeps, so the same input split into different steps compares equal.
+fn mock_playback(steps: &[Step]) -> Result<Vec<Action>, String> {
+    const MAX_TICKS: usize = 10_000;
+    let program = Program::compile(steps).map_err(|e| e.to_string())?;
+    let settings = PlaybackSettings {
+        jitter_ms: 0,
+        ..PlaybackSettings::default()
+    };
+    let mut worker = PlayerWorker::new(String::new(), program, settings, MockBackend::default());
+    let mut actions = Vec::new();
+    for _ in 0..MAX_TICKS {
+        let tick = worker.step();
+        if tick.finished {
+            break;
+        }
+        actions.append(&mut worker.backend.actions);
+        if !tick.delay.is_zero() {
+            actions.push(Action::Sleep(tick.delay));
+        }
+    }
+    worker.finish();
+    actions.append(&mut worker.backend.actions);
+
+    let mut merged: Vec<Action> = Vec::new();
+    for action in actions {
+        match (merged.last_mut(), action) {
+            (Some(Action::Sleep(total)), Action::Sleep(more)) => *total += more,
+            (_, action) => merged.push(action),
+        }
+    }
+    Ok(merged)
+}
+
+/// Exports `steps`, imports the script again and checks both play the same
+/// actions on the mock backend. Returns how many actions were compared.
+pub fn check_round_trip(name: &str, steps: &[Step], layout: &Layout) -> Result<usize, String> {
+    let script = export(name, steps, layout).map_err(|e| e.to_string())?;
+    let round_trip = import(&script).map_err(|e| format!("re-import failed: {}", e))?;
+
+    let before = mock_playback(steps)?;
+    let after = mock_playback(&round_trip)?;
+    if let Some(i) = (0..before.len().max(after.len())).find(|&i| before.get(i) != after.get(i)) {
+        return Err(format!(
+            "Round trip differs at action {}: {:?} before, {:?} after",
+            i,
+            before.get(i),
+            after.get(i)
+        ));
+    }
+    Ok(before.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::InputBackend;
+    use enigo::{Axis, Direction};
+
+    fn key(code: Keycode) -> Input {
+        Input::Key(Key(code))
+    }
+
+    /// Exports, re-imports and compares the playback of both.
+    fn round_trip(steps: &[Step]) -> Vec<Action> {
+        let layout = Layout::capture(&MockBackend::default());
+        let script = export("test", steps, &layout).unwrap();
+        let imported = import(&script).unwrap_or_else(|e| panic!("{}\n{}", e, script));
+        let before = mock_playback(steps).unwrap();
+        assert_eq!(before, mock_playback(&imported).unwrap(), "\n{}", script);
+        before
+    }
+
+    fn click() -> Step {
+        Step::Click {
+            button: SerializableMouseButton::Left,
+        }
+    }
+
+    #[test]
+    fn loops_and_break() {
+        let steps = vec![Step::Loop {
+            label: "outer".into(),
+            count: Some(3),
+            steps: vec![
+                click(),
+                Step::Loop {
+                    label: "inner".into(),
+                    count: None,
+                    steps: vec![
+                        Step::Wait { ms: 20 },
+                        Step::Click {
+                            button: SerializableMouseButton::Right,
+                        },
+                        Step::Break {
+                            label: "outer".into(),
+                        },
+                    ],
+                },
+            ],
+        }];
+        let actions = round_trip(&steps);
+        let clicks = actions
+            .iter()
+            .filter(|action| matches!(action, Action::Button(_, Direction::Click)))
+            .count();
+        // Breaking out of both loops leaves one left and one right click
+        assert_eq!(clicks, 2);
+
+        let counted = vec![Step::Loop {
+            label: "a".into(),
+            count: Some(2),
+            steps: vec![
+                Step::Loop {
+                    label: "b".into(),
+                    count: Some(2),
+                    steps: vec![click(), Step::Wait { ms: 5 }],
+                },
+                Step::Break { label: "a".into() },
+            ],
+        }];
+        round_trip(&counted);
+    }
+
+    #[test]
+    fn key_combos() {
+        let steps = import("xdotool key ctrl+shift+t").unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step::Press {
+                    input: key(Keycode::LControl)
+                },
+                Step::Press {
+                    input: key(Keycode::LShift)
+                },
+                Step::Press {
+                    input: key(Keycode::T)
+                },
+                Step::Release {
+                    input: key(Keycode::T)
+                },
+                Step::Release {
+                    input: key(Keycode::LShift)
+                },
+                Step::Release {
+                    input: key(Keycode::LControl)
+                },
+            ]
+        );
+        round_trip(&steps);
+        round_trip(&[
+            Step::Press {
+                input: key(Keycode::LAlt),
+            },
+            Step::Press {
+                input: key(Keycode::Tab),
+            },
+            Step::Release {
+                input: key(Keycode::Tab),
+            },
+            Step::Wait { ms: 300 },
+            Step::Release {
+                input: key(Keycode::LAlt),
+            },
+        ]);
+    }
+
+    #[test]
+    fn scroll_buttons() {
+        let steps =
+            import("xdotool click 4 click --repeat 3 5 click 6 click --repeat 2 7").unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step::Scroll {
+                    amount: -1,
+                    horizontal: false
+                },
+                Step::Scroll {
+                    amount: 3,
+                    horizontal: false
+                },
+                Step::Scroll {
+                    amount: -1,
+                    horizontal: true
+                },
+                Step::Scroll {
+                    amount: 2,
+                    horizontal: true
+                },
+            ]
+        );
+        let actions = round_trip(&steps);
+        let mut expected = MockBackend::default();
+        expected.scroll(-1, Axis::Vertical).unwrap();
+        expected.scroll(3, Axis::Vertical).unwrap();
+        expected.scroll(-1, Axis::Horizontal).unwrap();
+        expected.scroll(2, Axis::Horizontal).unwrap();
+        assert_eq!(actions, expected.actions);
+    }
+
+    #[test]
+    fn quoting() {
+        let text = "it's \"quoted\" $HOME `ls` \\ {{braces}}";
+        let steps = [
+            Step::Type {
+                text: text.into(),
+                style: TypingStyle::default(),
+            },
+            Step::Type {
+                text: "-starts with a dash{ENTER}".into(),
+                style: TypingStyle::default(),
+            },
+        ];
+        let actions = round_trip(&steps);
+        assert!(actions.contains(&Action::Text(
+            "it's \"quoted\" $HOME `ls` \\ {braces}".into()
+        )));
+
+        let steps = import("xdotool type 'it'\\''s' \"a \\\"b\\\"\"").unwrap();
+        assert_eq!(
+            steps,
+            vec![Step::Type {
+                text: "it'sa \"b\"".into(),
+                style: TypingStyle::default(),
+            }]
+        );
+    }
+
+    #[test]
+    fn sleep_suffixes() {
+        let steps =
+            import("sleep 1.5\nsleep 2m\nsleep 0.25s\nsleep 1h\nxdotool sleep 0.1").unwrap();
+        let waits: Vec<u64> = steps
+            .iter()
+            .map(|step| match step {
+                Step::Wait { ms } => *ms,
+                other => panic!("expected a wait, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(waits, [1500, 120_000, 250, 3_600_000, 100]);
+        assert!(import("sleep 1x").is_err());
+
+        round_trip(&[
+            click(),
+            Step::Wait { ms: 1250 },
+            click(),
+            Step::Wait { ms: 1 },
+        ]);
+    }
+}